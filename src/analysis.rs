@@ -0,0 +1,933 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use iced::Color;
+
+use crate::canvas::overlay::{Mark, Overlay};
+use crate::netwk::algorithms::path::{self, Method, NegativeCycle, Path};
+use crate::netwk::algorithms::centrality::{self, Measure};
+use crate::netwk::algorithms::coloring::{self, Strategy};
+use crate::netwk::algorithms::cycles::{self, Cycle};
+use crate::netwk::algorithms::{connectivity, flow, matrix, mincost, multipath, spanning};
+use crate::netwk::algorithms::Network;
+use crate::netwk::attribute::{Attributes, EdgeAttributes};
+use crate::netwk::format::GraphData;
+use crate::netwk::geometry::Point;
+use crate::netwk::job::Progress;
+
+// The editor's analysis tools. Each takes a Network snapshot of the document, so it can run
+// as a job, and turns what the algorithms find into an Analysis to show beside the canvas and
+// draw over the graph.
+
+/// What an analysis found, kept on the tab until cleared or the document changes.
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    pub title: String,
+    /// Findings, one per line
+    pub lines: Vec<String>,
+    pub overlay: Overlay,
+    pub table: Option<Table>,
+    /// Separate results, such as one path of several, which can be looked at one at a time
+    pub steps: Vec<Step>,
+    /// Step being shown, or None for everything together
+    pub step: Option<usize>,
+    /// Edges, with their weights, which can be taken out into a document of their own
+    pub subgraph: Vec<(String, String, f32)>,
+    /// Column the table is sorted by, and whether largest first
+    pub sorted: Option<(usize, bool)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Step {
+    pub label: String,
+    pub overlay: Overlay,
+}
+
+/// Rows of values under a row of headings, shown in the panel and exported as CSV.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn to_csv(&self) -> String {
+        std::iter::once(&self.header)
+            .chain(self.rows.iter())
+            .map(|row| row.iter().map(|cell| csv_cell(cell)).collect::<Vec<_>>().join(","))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Order the rows by one column, as numbers where both cells are. Blank cells, such as
+    /// distances to nodes out of reach, go last either way.
+    pub fn sort(&mut self, column: usize, descending: bool) {
+        self.rows.sort_by(|a, b| {
+            let (a, b) = (a.get(column).map_or("", String::as_str), b.get(column).map_or("", String::as_str));
+
+            match (a.is_empty(), b.is_empty()) {
+                (true, false) => return Ordering::Greater,
+                (false, true) => return Ordering::Less,
+                _ => {}
+            }
+
+            let order = match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.total_cmp(&b),
+                _ => a.cmp(b),
+            };
+
+            if descending { order.reverse() } else { order }
+        });
+    }
+}
+
+/// Cells holding separators or quotes are quoted, with the quotes inside doubled.
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Colour results are picked out in.
+const HIGHLIGHT: Color = Color { r: 0.95, g: 0.5, b: 0.1, a: 1.0 };
+/// Colour for things which stop an analysis giving an answer, such as negative cycles.
+const PROBLEM: Color = Color { r: 0.85, g: 0.2, b: 0.2, a: 1.0 };
+
+/// Colours for telling several results apart, used in turn.
+const PALETTE: [Color; 8] = [
+    Color { r: 0.95, g: 0.5, b: 0.1, a: 1.0 },
+    Color { r: 0.2, g: 0.4, b: 0.85, a: 1.0 },
+    Color { r: 0.2, g: 0.65, b: 0.3, a: 1.0 },
+    Color { r: 0.6, g: 0.3, b: 0.75, a: 1.0 },
+    Color { r: 0.85, g: 0.2, b: 0.5, a: 1.0 },
+    Color { r: 0.1, g: 0.65, b: 0.7, a: 1.0 },
+    Color { r: 0.55, g: 0.4, b: 0.2, a: 1.0 },
+    Color { r: 0.6, g: 0.6, b: 0.1, a: 1.0 },
+];
+
+/// Most names given in a line before the rest are just counted.
+const LISTED: usize = 20;
+
+/// Most equal cost paths listed, as there can be exponentially many.
+const EQUAL_COST_LIMIT: usize = 64;
+
+/// Most simple cycles listed, as there can be exponentially many.
+const CYCLE_LIMIT: usize = 200;
+
+/// Colour of the least important nodes when sizing them by importance, shading to HIGHLIGHT.
+const UNIMPORTANT: Color = Color { r: 0.55, g: 0.65, b: 0.85, a: 1.0 };
+
+/// Most nodes colouring is done exactly for, since backtracking can take exponentially long.
+const EXACT_COLORING_LIMIT: usize = 60;
+
+/// Most nodes the full table of distances is worked out and shown for, as it grows with the
+/// square of the node count. Past it only how far out each node is gets reported.
+const DISTANCE_TABLE_LIMIT: usize = 500;
+
+impl Analysis {
+    fn new(title: String) -> Self {
+        Analysis { title, ..Analysis::default() }
+    }
+
+    /// What to draw over the graph for the step being looked at.
+    pub fn shown(&self) -> &Overlay {
+        match self.step.and_then(|step| self.steps.get(step)) {
+            Some(step) => &step.overlay,
+            None => &self.overlay,
+        }
+    }
+
+    /// Drop every edge which isn't part of the subgraph. Where there are parallel edges
+    /// only as many are kept, of the same weights, as the subgraph has.
+    pub fn extract(&self, data: &mut GraphData) {
+        let mut wanted: HashMap<(String, String), Vec<f32>> = HashMap::new();
+
+        for (from, to, weight) in self.subgraph.iter() {
+            wanted.entry((from.clone(), to.clone())).or_default().push(*weight);
+        }
+
+        data.edges.retain(|edge| {
+            let weights = match wanted.get_mut(&(edge.from.clone(), edge.to.clone())) {
+                Some(weights) => weights,
+                None => return false,
+            };
+
+            match weights.iter().position(|&weight| weight == edge.weight.unwrap_or(1.0)) {
+                Some(i) => {
+                    weights.swap_remove(i);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Sort the table by a column, largest first unless it already is, when it flips round.
+    pub fn sort_table(&mut self, column: usize) {
+        let descending = self.sorted != Some((column, true));
+
+        if let Some(table) = &mut self.table {
+            table.sort(column, descending);
+            self.sorted = Some((column, descending));
+        }
+    }
+
+    fn line(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn mark_node(&mut self, net: &Network, node: usize, mark: Mark) {
+        self.overlay.nodes.insert(net.names[node].clone(), mark);
+    }
+
+    fn mark_link(&mut self, net: &Network, link: usize, mark: Mark) {
+        self.overlay.edges.insert(net.link_names(link), mark);
+    }
+
+    /// Pick out a route, fading everything else. Anything already marked keeps its mark.
+    fn mark_route(&mut self, net: &Network, nodes: &[usize], links: &[usize], color: Color) {
+        self.overlay.extend_under(route_overlay(net, nodes, links, color));
+    }
+
+    /// Diameter, radius and center from how far out each node is, marking the center.
+    fn eccentricity(&mut self, net: &Network, eccentricity: &[f32]) {
+        let diameter = matrix::diameter(eccentricity);
+        let center = matrix::center(eccentricity);
+
+        self.line(format!("Diameter {}, radius {}", diameter, matrix::radius(eccentricity)));
+        if diameter.is_infinite() {
+            self.line(String::from("Some nodes can't reach others, so their eccentricity is infinite"));
+        }
+        self.line(format!("Center: {}", names(net, &center)));
+
+        for &node in center.iter() {
+            self.mark_node(net, node, Mark::new(HIGHLIGHT).with_size(8.0).with_label("center"));
+        }
+    }
+
+    /// Explain there is no answer because of a negative cycle, and show where it is.
+    fn negative_cycle(&mut self, net: &Network, cycle: &NegativeCycle) {
+        let mut nodes = cycle.nodes.clone();
+        nodes.push(cycle.nodes[0]);
+
+        self.line(format!("No shortest paths: negative cycle of cost {}", cycle.cost));
+        self.line(route(net, &nodes));
+        self.mark_route(net, &cycle.nodes, &cycle.links, PROBLEM);
+    }
+}
+
+fn route_overlay(net: &Network, nodes: &[usize], links: &[usize], color: Color) -> Overlay {
+    let mut overlay = Overlay { dim: true, ..Overlay::default() };
+
+    for &node in nodes {
+        overlay.nodes.insert(net.names[node].clone(), Mark::new(color).with_size(7.0));
+    }
+    for &link in links {
+        overlay.edges.insert(net.link_names(link), Mark::new(color).with_size(4.0));
+    }
+
+    overlay
+}
+
+fn route(net: &Network, nodes: &[usize]) -> String {
+    nodes.iter().map(|&node| net.names[node].as_str()).collect::<Vec<_>>().join(" -> ")
+}
+
+fn names(net: &Network, nodes: &[usize]) -> String {
+    listing(nodes.iter().map(|&node| net.names[node].clone()).collect())
+}
+
+fn listing(items: Vec<String>) -> String {
+    match items.len() {
+        0 => String::from("none"),
+        n if n > LISTED => format!("{}, and {} more", items[..LISTED].join(", "), n - LISTED),
+        _ => items.join(", "),
+    }
+}
+
+/// One of the edge attributes for each link, falling back to its weight where it isn't set.
+/// Links come in the order the graph lists its edges, so parallel ones are counted off in order.
+pub fn edge_values(net: &Network, attributes: &Attributes, pick: impl Fn(&EdgeAttributes) -> Option<f32>) -> Vec<f32> {
+    let mut drawn: HashMap<(usize, usize), usize> = HashMap::new();
+
+    net.links
+        .iter()
+        .map(|link| {
+            let nth = drawn.entry((link.from, link.to)).or_default();
+            let (from, to) = (&net.names[link.from], &net.names[link.to]);
+            let value = pick(&attributes.edge(from, to, *nth)).unwrap_or(link.weight);
+            *nth += 1;
+
+            value
+        })
+        .collect()
+}
+
+/// Rounded off, since sums of floats tend to come out a hair away from what they should be.
+fn number(value: f32) -> String {
+    ((value * 1000.0).round() / 1000.0).to_string()
+}
+
+/// Label the two nodes an analysis ran between.
+fn mark_ends(overlay: &mut Overlay, net: &Network, from: usize, to: usize, labels: (&str, &str)) {
+    overlay.nodes.insert(net.names[from].clone(), Mark::new(HIGHLIGHT).with_size(8.0).with_label(labels.0));
+    overlay.nodes.insert(net.names[to].clone(), Mark::new(HIGHLIGHT).with_size(8.0).with_label(labels.1));
+}
+
+/// Costs for a table, left blank where there is no path.
+fn cost_cell(cost: f32) -> String {
+    if cost.is_finite() {
+        cost.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Cheapest path between two nodes by the chosen method. Dijkstra and A* can't cope with
+/// negative weights, so Bellman-Ford is used instead whenever there are any.
+pub fn shortest_path(net: &Network, positions: &[Point], from: usize, to: usize, method: Method) -> Analysis {
+    let mut analysis = Analysis::new(format!("Shortest path from {} to {}", net.names[from], net.names[to]));
+
+    let method = if net.has_negative() && method != Method::BellmanFord {
+        analysis.line(format!("Negative weights, so Bellman-Ford was used instead of {}", method));
+        Method::BellmanFord
+    } else {
+        method
+    };
+
+    let found = match method {
+        Method::Dijkstra => Ok(path::shortest_path(net, from, to)),
+        Method::AStar => Ok(path::a_star(net, from, to, path::euclidean(net, positions, to))),
+        Method::BellmanFord => path::bellman_ford(net, from).map(|tree| tree.path_to(to)),
+    };
+
+    match found {
+        Ok(Some(path)) => {
+            analysis.line(format!("{}: cost {} over {} edges", method, path.cost, path.links.len()));
+            analysis.line(route(net, &path.nodes));
+            analysis.mark_route(net, &path.nodes, &path.links, HIGHLIGHT);
+        }
+        Ok(None) => analysis.line(format!("{}: no path", method)),
+        Err(cycle) => analysis.negative_cycle(net, &cycle),
+    }
+
+    analysis
+}
+
+/// Up to k cheapest paths which don't visit any node twice.
+pub fn k_shortest(net: &Network, from: usize, to: usize, k: usize) -> Analysis {
+    let title = format!("{} shortest paths from {} to {}", k, net.names[from], net.names[to]);
+
+    paths(net, title, |net| multipath::k_shortest(net, from, to, k))
+}
+
+/// Every path as cheap as the cheapest, the routes traffic is shared over by equal cost multipath.
+pub fn equal_cost(net: &Network, from: usize, to: usize) -> Analysis {
+    let title = format!("Equal cost paths from {} to {}", net.names[from], net.names[to]);
+
+    let mut analysis = paths(net, title, |net| multipath::equal_cost(net, from, to, EQUAL_COST_LIMIT));
+
+    if analysis.steps.len() == EQUAL_COST_LIMIT {
+        analysis.line(format!("Stopped after the first {}", EQUAL_COST_LIMIT));
+    }
+
+    analysis
+}
+
+/// Several paths, each in a colour of its own and a step of its own.
+fn paths(net: &Network, title: String, find: impl FnOnce(&Network) -> Vec<Path>) -> Analysis {
+    let mut analysis = Analysis::new(title);
+
+    if net.has_negative() {
+        analysis.line(String::from("Only works with weights of zero or more"));
+        return analysis;
+    }
+
+    let paths = find(net);
+
+    if paths.is_empty() {
+        analysis.line(String::from("No path"));
+    }
+
+    for (i, path) in paths.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+
+        // Where paths overlap the cheaper one's colour shows
+        analysis.mark_route(net, &path.nodes, &path.links, color);
+        analysis.steps.push(Step {
+            label: format!("{}: cost {}, {}", i + 1, path.cost, route(net, &path.nodes)),
+            overlay: route_overlay(net, &path.nodes, &path.links, color),
+        });
+    }
+
+    analysis
+}
+
+/// Lightest (or heaviest) set of edges keeping everything joined that was joined. Prim's
+/// algorithm is used on dense networks and Kruskal's otherwise.
+pub fn spanning_forest(net: &Network, maximum: bool) -> Analysis {
+    let (forest, method) = if net.is_dense() {
+        (spanning::prim(net, maximum), "Prim")
+    } else {
+        (spanning::kruskal(net, maximum), "Kruskal")
+    };
+
+    let mut analysis = Analysis::new(format!(
+        "{} spanning {}",
+        if maximum { "Maximum" } else { "Minimum" },
+        if forest.trees > 1 { "forest" } else { "tree" },
+    ));
+
+    analysis.line(format!("{}: total weight {} over {} edges", method, forest.weight, forest.links.len()));
+    if forest.trees > 1 {
+        analysis.line(format!("{} separate trees, as not everything is joined", forest.trees));
+    }
+
+    for &link in forest.links.iter() {
+        let (from, to) = net.link_names(link);
+
+        analysis.mark_link(net, link, Mark::new(HIGHLIGHT).with_size(4.0));
+        analysis.subgraph.push((from, to, net.links[link].weight));
+    }
+    analysis.overlay.dim = true;
+
+    analysis
+}
+
+/// Each component in a colour of its own, strongly connected ones for directed networks,
+/// along with the links and nodes whose loss would split one up.
+pub fn connectivity(net: &Network) -> Analysis {
+    let mut analysis = Analysis::new(String::from("Connectivity"));
+
+    let joined = connectivity::components(net);
+    let groups = if net.directed {
+        let strong = connectivity::strongly_connected(net);
+        analysis.line(format!("{} strongly connected components, {} weakly connected", strong.len(), joined.len()));
+        strong
+    } else {
+        analysis.line(format!("{} connected components", joined.len()));
+        joined
+    };
+
+    let mut component = vec![0; net.len()];
+    let mut components = Overlay::default();
+
+    for (i, group) in groups.iter().enumerate() {
+        for &node in group.iter() {
+            component[node] = i;
+            components.nodes.insert(net.names[node].clone(), Mark::new(PALETTE[i % PALETTE.len()]));
+        }
+    }
+    // Links between components are left as they are
+    for (link, ends) in net.links.iter().enumerate() {
+        if component[ends.from] == component[ends.to] {
+            let color = PALETTE[component[ends.from] % PALETTE.len()];
+            components.edges.insert(net.link_names(link), Mark::new(color));
+        }
+    }
+
+    let weak = connectivity::weak_points(net);
+    let mut failures = Overlay { dim: true, ..Overlay::default() };
+
+    for &link in weak.bridges.iter() {
+        failures.edges.insert(net.link_names(link), Mark::new(PROBLEM).with_size(4.0));
+    }
+    for &node in weak.cut_nodes.iter() {
+        failures.nodes.insert(net.names[node].clone(), Mark::new(PROBLEM).with_size(8.0).with_label("cut"));
+    }
+
+    let bridges = weak.bridges.iter().map(|&link| {
+        let (from, to) = net.link_names(link);
+        format!("{}-{}", from, to)
+    });
+
+    analysis.line(format!("Bridges: {}", listing(bridges.collect())));
+    analysis.line(format!("Cut nodes: {}", names(net, &weak.cut_nodes)));
+
+    analysis.overlay = failures.clone();
+    analysis.overlay.extend_under(components.clone());
+    analysis.steps = vec![
+        Step { label: String::from("Components"), overlay: components },
+        Step { label: String::from("Single points of failure"), overlay: failures },
+    ];
+
+    analysis
+}
+
+/// Most that can be sent from one node to another, with the weights of `net` as capacities.
+/// Each edge is drawn thicker the more it carries, and the minimum cut which holds the flow
+/// back is shown along with its capacity, which is the same as the flow.
+pub fn max_flow(net: &Network, from: usize, to: usize) -> Analysis {
+    let mut analysis = Analysis::new(format!("Maximum flow from {} to {}", net.names[from], net.names[to]));
+
+    if from == to {
+        analysis.line(String::from("Source and sink are the same node"));
+        return analysis;
+    }
+
+    let flow = flow::max_flow(net, from, to);
+    let capacity = |link: usize| net.links[link].weight.max(0.0);
+    let cut_capacity: f32 = flow.cut.iter().map(|&link| capacity(link)).sum();
+
+    analysis.line(format!("Maximum flow {}", number(flow.value)));
+    analysis.line(format!("Minimum cut of {} edges with capacity {}", flow.cut.len(), number(cut_capacity)));
+
+    let busiest = flow.flows.iter().fold(0.0, |busiest: f32, flow| busiest.max(flow.abs()));
+    let mut flows = Overlay { dim: true, ..Overlay::default() };
+    let mut rows = vec![];
+
+    for (link, &sent) in flow.flows.iter().enumerate().filter(|(_, sent)| sent.abs() > 1e-6) {
+        let label = format!("{}/{}", number(sent.abs()), number(capacity(link)));
+        let mark = Mark::new(HIGHLIGHT).with_size(1.5 + 4.5 * sent.abs() / busiest).with_label(label);
+        flows.edges.insert(net.link_names(link), mark);
+
+        // Listed the way the flow goes, which for undirected links may not be how they were drawn
+        let (start, end) = net.link_names(link);
+        let (start, end) = if sent < 0.0 { (end, start) } else { (start, end) };
+        rows.push(vec![start, end, number(capacity(link)), number(sent.abs())]);
+    }
+
+    let mut cut = Overlay { dim: true, ..Overlay::default() };
+
+    for &link in flow.cut.iter() {
+        let mark = Mark::new(PROBLEM).with_size(4.0).with_label(format!("cut {}", number(capacity(link))));
+        cut.edges.insert(net.link_names(link), mark);
+    }
+
+    mark_ends(&mut flows, net, from, to, ("source", "sink"));
+    mark_ends(&mut cut, net, from, to, ("source", "sink"));
+
+    analysis.overlay = cut.clone();
+    analysis.overlay.extend_under(flows.clone());
+    analysis.steps = vec![
+        Step { label: String::from("Flow"), overlay: flows },
+        Step { label: String::from("Minimum cut"), overlay: cut },
+    ];
+    analysis.table = Some(Table {
+        header: ["from", "to", "capacity", "flow"].map(String::from).to_vec(),
+        rows,
+    });
+
+    analysis
+}
+
+/// Route a demand from one node to another as cheaply as possible, the weights of `net` being
+/// costs per unit and `capacities` how much each edge carries. Edges are drawn thicker the
+/// more they carry.
+pub fn min_cost_flow(net: &Network, capacities: &[f32], from: usize, to: usize, demand: f32) -> Analysis {
+    let mut analysis = Analysis::new(format!("Routing {} from {} to {}", number(demand), net.names[from], net.names[to]));
+
+    if from == to {
+        analysis.line(String::from("Source and sink are the same node"));
+        return analysis;
+    }
+
+    let flow = match mincost::min_cost_flow(net, capacities, from, to, demand) {
+        Ok(flow) => flow,
+        Err(cycle) => {
+            analysis.negative_cycle(net, &cycle);
+            return analysis;
+        }
+    };
+
+    analysis.line(format!("Sent {} at a total cost of {}", number(flow.sent), number(flow.cost)));
+    if demand - flow.sent > 1e-6 {
+        analysis.line(format!("Not enough capacity, {} short of the demand", number(demand - flow.sent)));
+    }
+    analysis.line(String::from("Capacities and costs not set on an edge are its weight"));
+
+    let busiest = flow.flows.iter().fold(0.0, |busiest: f32, flow| busiest.max(flow.abs()));
+    let mut rows = vec![];
+
+    for (link, &sent) in flow.flows.iter().enumerate().filter(|(_, sent)| sent.abs() > 1e-6) {
+        let unit = net.links[link].weight;
+        let label = format!("{} x {}", number(sent.abs()), number(unit));
+        analysis.mark_link(net, link, Mark::new(HIGHLIGHT).with_size(1.5 + 4.5 * sent.abs() / busiest).with_label(label));
+
+        let (start, end) = net.link_names(link);
+        let (start, end) = if sent < 0.0 { (end, start) } else { (start, end) };
+        rows.push(vec![
+            start,
+            end,
+            number(sent.abs()),
+            number(capacities[link]),
+            number(unit),
+            number(sent.abs() * unit),
+        ]);
+    }
+
+    mark_ends(&mut analysis.overlay, net, from, to, ("source", "sink"));
+    analysis.overlay.dim = true;
+    analysis.table = Some(Table {
+        header: ["from", "to", "flow", "capacity", "unit cost", "cost"].map(String::from).to_vec(),
+        rows,
+    });
+
+    analysis
+}
+
+/// The route round a cycle, back to where it started.
+fn cycle_route(net: &Network, cycle: &Cycle) -> String {
+    let mut nodes = cycle.nodes.clone();
+    nodes.push(cycle.nodes[0]);
+
+    route(net, &nodes)
+}
+
+/// Whether there are any cycles, showing one if there are. For dependencies, any cycle means
+/// something which in the end depends on itself.
+pub fn find_cycle(net: &Network) -> Analysis {
+    let mut analysis = Analysis::new(String::from("Cycle check"));
+
+    match cycles::find_cycle(net) {
+        Some(cycle) => {
+            analysis.line(format!("Cycle of {} nodes: {}", cycle.nodes.len(), cycle_route(net, &cycle)));
+            analysis.mark_route(net, &cycle.nodes, &cycle.links, PROBLEM);
+        }
+        None if net.directed => analysis.line(String::from("No cycles, so there is a topological order")),
+        None => analysis.line(String::from("No cycles, it is a forest")),
+    }
+
+    analysis
+}
+
+/// Number the nodes so every edge leads from a lower number to a higher one, as an order to
+/// do dependent tasks in. Where a cycle makes that impossible, the cycle is shown instead.
+pub fn topological_order(net: &Network) -> Analysis {
+    let mut analysis = Analysis::new(String::from("Topological order"));
+
+    if !net.directed {
+        analysis.line(String::from("Only directed graphs have a topological order"));
+        return analysis;
+    }
+
+    let order = match cycles::topological_order(net) {
+        Ok(order) => order,
+        Err(cycle) => {
+            analysis.line(format!("No order, because of the cycle {}", cycle_route(net, &cycle)));
+            analysis.mark_route(net, &cycle.nodes, &cycle.links, PROBLEM);
+            return analysis;
+        }
+    };
+
+    analysis.line(format!("Order: {}", names(net, &order)));
+
+    let mut rows = vec![];
+    for (i, &node) in order.iter().enumerate() {
+        analysis.mark_node(net, node, Mark::new(HIGHLIGHT).with_label((i + 1).to_string()));
+        rows.push(vec![(i + 1).to_string(), net.names[node].clone()]);
+    }
+
+    analysis.table = Some(Table { header: ["position", "node"].map(String::from).to_vec(), rows });
+
+    analysis
+}
+
+/// Every simple cycle, each in a colour of its own and a step of its own. There can be
+/// exponentially many, so this is for small graphs. Gives None if cancelled.
+pub fn simple_cycles(net: &Network, progress: &Progress) -> Option<Analysis> {
+    let mut analysis = Analysis::new(String::from("Simple cycles"));
+
+    if !net.directed {
+        analysis.line(String::from("Only for directed graphs"));
+        return Some(analysis);
+    }
+
+    let found = cycles::simple_cycles(net, CYCLE_LIMIT, progress)?;
+
+    match found.len() {
+        0 => analysis.line(String::from("No cycles")),
+        CYCLE_LIMIT => analysis.line(format!("Stopped after the first {}", CYCLE_LIMIT)),
+        n => analysis.line(format!("{} cycles", n)),
+    }
+
+    let mut rows = vec![];
+    for (i, cycle) in found.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+
+        analysis.mark_route(net, &cycle.nodes, &cycle.links, color);
+        analysis.steps.push(Step {
+            label: format!("{}: {}", i + 1, cycle_route(net, cycle)),
+            overlay: route_overlay(net, &cycle.nodes, &cycle.links, color),
+        });
+        rows.push(vec![(i + 1).to_string(), cycle.nodes.len().to_string(), cycle_route(net, cycle)]);
+    }
+
+    analysis.table = Some(Table { header: ["cycle", "length", "route"].map(String::from).to_vec(), rows });
+
+    Some(analysis)
+}
+
+/// How important each node is by several measures, for picking out critical routers and
+/// hubs. Each measure is a step which sizes and colours the nodes by it, betweenness being
+/// shown to begin with. Gives None if cancelled.
+pub fn centrality(net: &Network, progress: &Progress) -> Option<Analysis> {
+    let mut analysis = Analysis::new(String::from("Centrality"));
+    let found = centrality::centrality(net, progress)?;
+
+    if net.is_empty() {
+        analysis.line(String::from("No nodes"));
+        return Some(analysis);
+    }
+    if net.has_negative() {
+        analysis.line(String::from("Closeness and betweenness count hops, as some weights are negative"));
+    }
+
+    for (i, measure) in Measure::ALL.into_iter().enumerate() {
+        let values = found.get(measure);
+        let most = values.iter().fold(0.0f32, |most, &value| most.max(value));
+        let top = (0..net.len()).max_by(|&a, &b| values[a].total_cmp(&values[b]).then(b.cmp(&a))).unwrap();
+
+        analysis.line(format!("Most central by {}: {} ({})", measure, net.names[top], number(values[top])));
+
+        let mut overlay = Overlay::default();
+        for (node, &value) in values.iter().enumerate() {
+            let share = if most > 0.0 { value / most } else { 0.0 };
+            let color = Color {
+                r: UNIMPORTANT.r + (HIGHLIGHT.r - UNIMPORTANT.r) * share,
+                g: UNIMPORTANT.g + (HIGHLIGHT.g - UNIMPORTANT.g) * share,
+                b: UNIMPORTANT.b + (HIGHLIGHT.b - UNIMPORTANT.b) * share,
+                a: 1.0,
+            };
+            overlay.nodes.insert(net.names[node].clone(), Mark::new(color).with_size(4.0 + 12.0 * share));
+        }
+
+        analysis.steps.push(Step { label: format!("Size by {}", measure), overlay });
+        if measure == Measure::Betweenness {
+            analysis.step = Some(i);
+        }
+    }
+
+    let rows = (0..net.len())
+        .map(|node| {
+            std::iter::once(net.names[node].clone())
+                .chain(Measure::ALL.iter().map(|&measure| number(found.get(measure)[node])))
+                .collect()
+        })
+        .collect();
+    let header = std::iter::once(String::from("node")).chain(Measure::ALL.iter().map(|measure| measure.to_string().to_lowercase()));
+
+    analysis.table = Some(Table { header: header.collect(), rows });
+    analysis.sort_table(1 + Measure::ALL.iter().position(|&measure| measure == Measure::Betweenness).unwrap());
+
+    Some(analysis)
+}
+
+/// Colour the nodes so that no two joined ones share a colour, as when giving neighbouring
+/// transmitters different channels, with bounds on the fewest colours there could be. Gives
+/// None if cancelled.
+pub fn node_coloring(net: &Network, strategy: Strategy, progress: &Progress) -> Option<Analysis> {
+    let mut analysis = Analysis::new(format!("Colouring by {}", strategy));
+
+    let (found, exact) = match strategy {
+        Strategy::WelshPowell => (coloring::welsh_powell(net), false),
+        Strategy::DSatur => (coloring::dsatur(net), false),
+        Strategy::Exact if net.len() > EXACT_COLORING_LIMIT => {
+            analysis.line(format!("Over {} nodes is too many to colour exactly, so DSatur is used", EXACT_COLORING_LIMIT));
+            (coloring::dsatur(net), false)
+        }
+        Strategy::Exact => (coloring::exact(net, progress)?, true),
+    };
+    let clique = coloring::clique(net);
+    let bounds = coloring::bounds(net);
+    let upper = bounds.upper.min(found.count);
+
+    analysis.line(format!("{} colours used", found.count));
+    if exact || bounds.lower == upper {
+        analysis.line(format!("Chromatic number {}", found.count));
+    } else {
+        analysis.line(format!("Chromatic number between {} and {}", bounds.lower, upper));
+    }
+    analysis.line(format!("Clique needing a colour each: {}", names(net, &clique)));
+
+    let mut rows = vec![];
+    for (color, class) in found.classes().iter().enumerate() {
+        for &node in class.iter() {
+            let mark = Mark::new(PALETTE[color % PALETTE.len()]).with_label((color + 1).to_string());
+            analysis.mark_node(net, node, mark);
+            rows.push(vec![net.names[node].clone(), (color + 1).to_string()]);
+        }
+    }
+
+    analysis.steps = vec![Step { label: String::from("Clique"), overlay: route_overlay(net, &clique, &[], HIGHLIGHT) }];
+    analysis.table = Some(Table { header: ["node", "colour"].map(String::from).to_vec(), rows });
+
+    Some(analysis)
+}
+
+/// Colour the edges so that no two meeting at a node share a colour. Each colour is a
+/// maintenance window: all its links can be taken down together without any node losing more
+/// than one of its links at a time.
+pub fn edge_coloring(net: &Network) -> Analysis {
+    let mut analysis = Analysis::new(String::from("Edge colouring"));
+
+    let found = coloring::edge_coloring(net);
+    let bounds = coloring::edge_bounds(net);
+
+    analysis.line(format!("{} maintenance windows", found.count));
+    if bounds.lower == found.count {
+        analysis.line(String::from("As few as there can be, one per link at the busiest node"));
+    } else {
+        analysis.line(format!("At least {} needed, one per link at the busiest node", bounds.lower));
+    }
+
+    let mut rows = vec![];
+    for (color, class) in found.classes().iter().enumerate() {
+        let mut window = Overlay { dim: true, ..Overlay::default() };
+
+        for &link in class.iter() {
+            let mark = Mark::new(PALETTE[color % PALETTE.len()]).with_size(3.0).with_label(format!("w{}", color + 1));
+            window.edges.insert(net.link_names(link), mark.clone());
+            analysis.mark_link(net, link, mark);
+
+            let (from, to) = net.link_names(link);
+            rows.push(vec![from, to, (color + 1).to_string()]);
+        }
+
+        analysis.steps.push(Step { label: format!("Window {}", color + 1), overlay: window });
+    }
+
+    analysis.table = Some(Table { header: ["from", "to", "window"].map(String::from).to_vec(), rows });
+
+    analysis
+}
+
+/// Shortest distances between every pair of nodes as a table, along with how far out each
+/// node is from the rest. The center, the nodes nearest to everything, is picked out. Past
+/// DISTANCE_TABLE_LIMIT nodes the table only has each node's eccentricity.
+pub fn distances(net: &Network, progress: &Progress) -> Option<Analysis> {
+    let mut analysis = Analysis::new(String::from("Distances"));
+
+    if net.is_empty() {
+        analysis.line(String::from("No nodes to measure"));
+        return Some(analysis);
+    }
+
+    if net.len() > DISTANCE_TABLE_LIMIT {
+        let eccentricity = match matrix::eccentricities(net, progress)? {
+            Ok(eccentricity) => eccentricity,
+            Err(cycle) => {
+                analysis.negative_cycle(net, &cycle);
+                return Some(analysis);
+            }
+        };
+
+        analysis.line(format!("Over {} nodes is too many to list every distance, so only eccentricity is given", DISTANCE_TABLE_LIMIT));
+        analysis.eccentricity(net, &eccentricity);
+
+        let rows = eccentricity
+            .iter()
+            .enumerate()
+            .map(|(node, &eccentricity)| vec![net.names[node].clone(), cost_cell(eccentricity)])
+            .collect();
+
+        analysis.table = Some(Table { header: ["node", "eccentricity"].map(String::from).to_vec(), rows });
+
+        return Some(analysis);
+    }
+
+    let matrix = match matrix::all_pairs(net, progress)? {
+        Ok(matrix) => matrix,
+        Err(cycle) => {
+            analysis.negative_cycle(net, &cycle);
+            return Some(analysis);
+        }
+    };
+
+    let eccentricity = matrix.eccentricity();
+    analysis.eccentricity(net, &eccentricity);
+
+    let header = std::iter::once(String::new())
+        .chain(net.names.iter().cloned())
+        .chain(std::iter::once(String::from("eccentricity")))
+        .collect();
+
+    let rows = matrix
+        .cost
+        .iter()
+        .zip(eccentricity)
+        .enumerate()
+        .map(|(node, (row, eccentricity))| {
+            std::iter::once(net.names[node].clone())
+                .chain(row.iter().map(|&cost| cost_cell(cost)))
+                .chain(std::iter::once(cost_cell(eccentricity)))
+                .collect()
+        })
+        .collect();
+
+    analysis.table = Some(Table { header, rows });
+
+    Some(analysis)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+    use crate::netwk::format::{EdgeData, NodeData};
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table {
+            header: vec![String::from("Node"), String::from("Distance")],
+            rows: rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect(),
+        }
+    }
+
+    /// Numbers sort as numbers, text as text, and blank cells go last whichever way round.
+    #[test]
+    pub fn test_sort_table () {
+        let mut analysis = Analysis::new(String::from("Distances"));
+        analysis.table = Some(table(&[&["a", "10"], &["b", ""], &["c", "9"], &["d", "-1.5"]]));
+
+        let column = |analysis: &Analysis, i: usize| analysis.table.as_ref().unwrap().rows.iter().map(|row| row[i].clone()).collect::<Vec<_>>();
+
+        analysis.sort_table(1);
+        assert_eq!(column(&analysis, 1), vec!["10", "9", "-1.5", ""]);
+
+        analysis.sort_table(1);
+        assert_eq!(column(&analysis, 1), vec!["-1.5", "9", "10", ""]);
+
+        analysis.sort_table(0);
+        assert_eq!(column(&analysis, 0), vec!["d", "c", "b", "a"]);
+    }
+
+    /// Cells with commas, quotes or line breaks are quoted, and the rest left as they are.
+    #[test]
+    pub fn test_to_csv () {
+        let table = table(&[&["a, b", "1"], &["say \"hi\"", ""], &["two\nlines", "2.5"]]);
+
+        assert_eq!(table.to_csv(), "Node,Distance\n\"a, b\",1\n\"say \"\"hi\"\"\",\n\"two\nlines\",2.5\n");
+    }
+
+    /// Extraction keeps just the subgraph's edges, telling parallel ones apart by weight.
+    #[test]
+    pub fn test_extract () {
+        let net = network(3, &[(0, 1, 5.0), (0, 1, 2.0), (1, 2, 1.0), (0, 2, 4.0)], false);
+        let analysis = spanning_forest(&net, false);
+
+        let edge = |from: &str, to: &str, weight: f32| EdgeData { from: from.to_string(), to: to.to_string(), weight: Some(weight), control: None };
+        let mut data = GraphData {
+            directed: false,
+            nodes: (0..3).map(|i| NodeData { name: i.to_string(), x: 0.0, y: 0.0 }).collect(),
+            edges: vec![edge("0", "1", 5.0), edge("0", "1", 2.0), edge("1", "2", 1.0), edge("0", "2", 4.0)],
+            groups: vec![],
+        };
+
+        analysis.extract(&mut data);
+        assert_eq!(data.edges, vec![edge("0", "1", 2.0), edge("1", "2", 1.0)]);
+    }
+
+    /// Attributes are matched to parallel links in the order they were drawn, falling back to the weight.
+    #[test]
+    pub fn test_edge_values () {
+        let net = network(2, &[(0, 1, 3.0), (0, 1, 4.0)], true);
+        let mut attributes = Attributes::default();
+        attributes.set_edge("0", "1", 1, EdgeAttributes { capacity: Some(10.0), cost: None });
+
+        assert_eq!(edge_values(&net, &attributes, |edge| edge.capacity), vec![3.0, 10.0]);
+        assert_eq!(edge_values(&net, &attributes, |edge| edge.cost), vec![3.0, 4.0]);
+    }
+}
@@ -0,0 +1,88 @@
+use iced::canvas::Text;
+use iced::pure::widget::canvas::{Frame, Path, Stroke};
+use iced::{Color, Point, Vector};
+
+use crate::netwk::annotation::{Annotation, AnnotationStyle, Shape};
+
+// Draws annotations in world coordinates, so the frame should already have the camera applied.
+
+pub fn draw_annotation(frame: &mut Frame, annotation: &Annotation) {
+    draw_shape(frame, &annotation.shape, &annotation.style);
+}
+
+pub fn draw_shape(frame: &mut Frame, shape: &Shape, style: &AnnotationStyle) {
+    let color = Color::from(style.color);
+    let stroke = Stroke::default().with_width(style.width).with_color(color);
+
+    match shape {
+        Shape::Text { position, content, size } => {
+            frame.fill_text(Text {
+                content: content.clone(),
+                position: (*position).into(),
+                color,
+                size: *size,
+                ..Text::default()
+            });
+        }
+        Shape::Rectangle { .. } => {
+            let bounds = shape.bounds();
+            let path = Path::rectangle(bounds.position().into(), bounds.size().into());
+
+            outline_or_fill(frame, &path, style.filled, color, stroke);
+        }
+        Shape::Ellipse { .. } => {
+            const STEPS: usize = 48;
+
+            let bounds = shape.bounds();
+            let centre = bounds.center();
+            let (rx, ry) = (bounds.width / 2.0, bounds.height / 2.0);
+
+            let path = Path::new(|f| {
+                for i in 0..STEPS {
+                    let angle = i as f32 / STEPS as f32 * std::f32::consts::TAU;
+                    let point = Point::new(centre.x + rx * angle.cos(), centre.y + ry * angle.sin());
+
+                    if i == 0 {
+                        f.move_to(point);
+                    } else {
+                        f.line_to(point);
+                    }
+                }
+                f.close();
+            });
+
+            outline_or_fill(frame, &path, style.filled, color, stroke);
+        }
+        Shape::Arrow { from, to } => {
+            let (from, to) = (Point::from(*from), Point::from(*to));
+            frame.stroke(&Path::line(from, to), stroke);
+
+            let length = from.distance(to);
+
+            if length > 0.0 {
+                let dir = Vector::new((to.x - from.x) / length, (to.y - from.y) / length);
+                let normal = Vector::new(-dir.y, dir.x);
+                let base = to - dir * 10.0;
+
+                frame.fill(
+                    &Path::new(|f| {
+                        f.move_to(to);
+                        f.line_to(base + normal * 5.0);
+                        f.line_to(base - normal * 5.0);
+                        f.close();
+                    }),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+fn outline_or_fill(frame: &mut Frame, path: &Path, filled: bool, color: Color, stroke: Stroke) {
+    if filled {
+        // Fills are kept light so anything drawn over them stays readable
+        frame.fill(path, Color { a: 0.25, ..color });
+    }
+
+    frame.stroke(path, stroke);
+}
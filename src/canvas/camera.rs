@@ -0,0 +1,128 @@
+use iced::pure::widget::canvas::Frame;
+use iced::{Point, Rectangle, Size, Vector};
+
+/// Maps between the graph's world coordinates and the canvas' screen coordinates.
+/// screen = world * scale + translation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub translation: Vector,
+    pub scale: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            translation: Vector::new(0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    pub const MIN_SCALE: f32 = 0.02;
+    pub const MAX_SCALE: f32 = 4.0;
+
+    pub fn to_world(&self, screen: Point) -> Point {
+        Point::new(
+            (screen.x - self.translation.x) / self.scale,
+            (screen.y - self.translation.y) / self.scale,
+        )
+    }
+
+    pub fn to_screen(&self, world: Point) -> Point {
+        Point::new(
+            world.x * self.scale + self.translation.x,
+            world.y * self.scale + self.translation.y,
+        )
+    }
+
+    /// Area of the world visible in a canvas of the given size.
+    pub fn visible_region(&self, size: Size) -> Rectangle {
+        let top_left = self.to_world(Point::ORIGIN);
+
+        Rectangle::new(top_left, Size::new(size.width / self.scale, size.height / self.scale))
+    }
+
+    pub fn pan(&self, delta: Vector) -> Camera {
+        Camera {
+            translation: self.translation + delta,
+            ..*self
+        }
+    }
+
+    /// Zoom by some factor while keeping the world point under `screen` fixed.
+    pub fn zoom_at(&self, screen: Point, factor: f32) -> Camera {
+        let scale = (self.scale * factor).clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+        let world = self.to_world(screen);
+
+        Camera {
+            translation: Vector::new(screen.x - world.x * scale, screen.y - world.y * scale),
+            scale,
+        }
+    }
+
+    /// Same zoom, moved so the world point sits in the middle of a canvas of the given size.
+    pub fn centre_on(&self, world: Point, size: Size) -> Camera {
+        Camera {
+            translation: Vector::new(size.width / 2.0 - world.x * self.scale, size.height / 2.0 - world.y * self.scale),
+            ..*self
+        }
+    }
+
+    /// Camera which centres the world rectangle inside a canvas of the given size.
+    pub fn fit(region: Rectangle, size: Size, padding: f32) -> Camera {
+        let width = f32::max(region.width, 1.0) + 2.0 * padding;
+        let height = f32::max(region.height, 1.0) + 2.0 * padding;
+
+        let scale = f32::min(size.width / width, size.height / height)
+            .clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+        let centre = region.center();
+
+        Camera {
+            translation: Vector::new(
+                size.width / 2.0 - centre.x * scale,
+                size.height / 2.0 - centre.y * scale,
+            ),
+            scale,
+        }
+    }
+
+    /// Transform the frame so that drawing in world coordinates lands in the right place.
+    pub fn apply(&self, frame: &mut Frame) {
+        frame.translate(self.translation);
+        frame.scale(self.scale);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Screen and world coordinates map back and forth, and zooming keeps the point under the cursor still.
+    #[test]
+    pub fn test_zoom_at () {
+        let camera = Camera::default().pan(Vector::new(30.0, -20.0));
+        let cursor = Point::new(100.0, 50.0);
+        let world = camera.to_world(cursor);
+
+        assert_eq!(camera.to_screen(world), cursor);
+
+        let zoomed = camera.zoom_at(cursor, 2.0);
+        assert_eq!(zoomed.scale, 2.0);
+        assert_eq!(zoomed.to_world(cursor), world);
+
+        assert_eq!(camera.zoom_at(cursor, 1000.0).scale, Camera::MAX_SCALE);
+    }
+
+    /// Fitting a region centres it and scales it to the tighter of the two sides.
+    #[test]
+    pub fn test_fit () {
+        let region = Rectangle::new(Point::new(0.0, 0.0), Size::new(200.0, 100.0));
+        let camera = Camera::fit(region, Size::new(400.0, 400.0), 0.0);
+
+        assert_eq!(camera.scale, 2.0);
+        assert_eq!(camera.to_screen(region.center()), Point::new(200.0, 200.0));
+        assert_eq!(camera.visible_region(Size::new(400.0, 400.0)).width, 200.0);
+    }
+}
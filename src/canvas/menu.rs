@@ -0,0 +1,104 @@
+use iced::alignment::Vertical;
+use iced::canvas::Text;
+use iced::pure::widget::canvas::{Frame, Path, Stroke};
+use iced::{Color, Point, Rectangle, Size};
+
+use crate::ui::Message;
+
+/// What was under the cursor when the context menu was opened.
+#[derive(Debug, Clone)]
+pub enum ContextTarget {
+    Node(String),
+    /// Ends of the edge, and which of the edges drawn that way it is
+    Edge(String, String, usize),
+    Annotation(usize),
+    Group(String),
+    Canvas(Point),
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    label: String,
+    message: Message,
+}
+
+impl MenuItem {
+    pub fn new(label: &str, message: Message) -> Self {
+        MenuItem {
+            label: String::from(label),
+            message,
+        }
+    }
+}
+
+/// A popup list of actions drawn on top of the canvas at the position it was opened.
+/// Positions here are in screen space, not world space.
+#[derive(Debug, Clone)]
+pub struct ContextMenu {
+    position: Point,
+    items: Vec<MenuItem>,
+}
+
+impl ContextMenu {
+    const ITEM_WIDTH: f32 = 150.0;
+    const ITEM_HEIGHT: f32 = 22.0;
+
+    /// Opens the menu at `position`, nudged so it stays inside the canvas bounds.
+    pub fn new(position: Point, items: Vec<MenuItem>, bounds: Size) -> Self {
+        let height = Self::ITEM_HEIGHT * items.len() as f32;
+
+        let position = Point::new(
+            f32::max(0.0, f32::min(position.x, bounds.width - Self::ITEM_WIDTH)),
+            f32::max(0.0, f32::min(position.y, bounds.height - height)),
+        );
+
+        ContextMenu { position, items }
+    }
+
+    fn item_bounds(&self, index: usize) -> Rectangle {
+        Rectangle::new(
+            Point::new(self.position.x, self.position.y + Self::ITEM_HEIGHT * index as f32),
+            Size::new(Self::ITEM_WIDTH, Self::ITEM_HEIGHT),
+        )
+    }
+
+    /// Message for the item under the cursor, if any.
+    pub fn item_at(&self, cursor: Point) -> Option<Message> {
+        (0..self.items.len())
+            .find(|i| self.item_bounds(*i).contains(cursor))
+            .map(|i| self.items[i].message.clone())
+    }
+
+    pub fn draw(&self, frame: &mut Frame, cursor: Option<Point>) {
+        for (i, item) in self.items.iter().enumerate() {
+            let bounds = self.item_bounds(i);
+            let hovered = cursor.map(|cursor| bounds.contains(cursor)).unwrap_or(false);
+
+            frame.fill_rectangle(
+                bounds.position(),
+                bounds.size(),
+                if hovered {
+                    Color::from_rgb(0.85, 0.9, 1.0)
+                } else {
+                    Color::WHITE
+                },
+            );
+
+            frame.fill_text(Text {
+                content: item.label.clone(),
+                position: Point::new(bounds.x + 8.0, bounds.center_y()),
+                vertical_alignment: Vertical::Center,
+                size: 16.0,
+                ..Text::default()
+            });
+        }
+
+        frame.stroke(
+            &Path::rectangle(
+                self.position,
+                Size::new(Self::ITEM_WIDTH, Self::ITEM_HEIGHT * self.items.len() as f32),
+            ),
+            Stroke::default(),
+        );
+    }
+}
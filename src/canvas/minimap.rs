@@ -0,0 +1,144 @@
+use iced::pure::widget::canvas::{Frame, Path, Stroke};
+use iced::{Color, Point, Rectangle, Size, Vector};
+
+use super::camera::Camera;
+use crate::netwk::geometry;
+use crate::netwk::layer::Layers;
+use crate::netwk::layout::LayoutGraph;
+
+/// Small overview of the whole diagram drawn in the bottom right corner of the canvas,
+/// with a box showing the part currently in view. Positions are in screen space.
+#[derive(Debug, Clone)]
+pub struct Minimap {
+    bounds: Rectangle,
+    region: Rectangle,
+    scale: f32,
+}
+
+impl Minimap {
+    const WIDTH: f32 = 160.0;
+    const HEIGHT: f32 = 120.0;
+    const MARGIN: f32 = 10.0;
+
+    /// Past this many nodes the minimap shades how crowded each part of the diagram is instead
+    /// of drawing every node and edge.
+    const DETAIL_LIMIT: usize = 5000;
+    /// Side of a shaded square, in pixels.
+    const CELL: f32 = 4.0;
+
+    /// Lay out the minimap for a canvas of the given size, around the extent of the graph as
+    /// the spatial index has it. There is nothing to show for an empty graph.
+    pub fn new(extent: Option<geometry::Rectangle>, camera: &Camera, canvas: Size) -> Option<Minimap> {
+        let graph_bounds = extent?;
+        let view = camera.visible_region(canvas);
+
+        // Cover the graph and the viewport so the viewport box never falls off the edge
+        let left = graph_bounds.x.min(view.x) - 20.0;
+        let top = graph_bounds.y.min(view.y) - 20.0;
+        let right = (graph_bounds.x + graph_bounds.width).max(view.x + view.width) + 20.0;
+        let bottom = (graph_bounds.y + graph_bounds.height).max(view.y + view.height) + 20.0;
+        let region = Rectangle::new(Point::new(left, top), Size::new(right - left, bottom - top));
+
+        let bounds = Rectangle::new(
+            Point::new(canvas.width - Self::WIDTH - Self::MARGIN, canvas.height - Self::HEIGHT - Self::MARGIN),
+            Size::new(Self::WIDTH, Self::HEIGHT),
+        );
+        let scale = f32::min(Self::WIDTH / region.width, Self::HEIGHT / region.height);
+
+        Some(Minimap { bounds, region, scale })
+    }
+
+    pub fn contains(&self, screen: Point) -> bool {
+        self.bounds.contains(screen)
+    }
+
+    /// Offset which centres the scaled region inside the minimap box.
+    fn inset(&self) -> Vector {
+        Vector::new(
+            (self.bounds.width - self.region.width * self.scale) / 2.0,
+            (self.bounds.height - self.region.height * self.scale) / 2.0,
+        )
+    }
+
+    fn to_minimap(&self, world: impl Into<Point>) -> Point {
+        let world: Point = world.into();
+        let inset = self.inset();
+
+        Point::new(
+            self.bounds.x + inset.x + (world.x - self.region.x) * self.scale,
+            self.bounds.y + inset.y + (world.y - self.region.y) * self.scale,
+        )
+    }
+
+    pub fn to_world(&self, screen: Point) -> Point {
+        let inset = self.inset();
+
+        Point::new(
+            self.region.x + (screen.x - self.bounds.x - inset.x) / self.scale,
+            self.region.y + (screen.y - self.bounds.y - inset.y) / self.scale,
+        )
+    }
+
+    pub fn draw(&self, frame: &mut Frame, graph: &LayoutGraph, layers: &Layers, camera: &Camera, canvas: Size) {
+        frame.fill_rectangle(self.bounds.position(), self.bounds.size(), Color::from_rgba(1.0, 1.0, 1.0, 0.9));
+
+        if graph.node_list().len() > Self::DETAIL_LIMIT {
+            self.draw_density(frame, graph, layers);
+        } else {
+            let edge_stroke = || Stroke::default().with_width(0.5).with_color(Color::from_rgb(0.5, 0.5, 0.5));
+
+            // Always straight at this size
+            for edge in graph.edges().filter(|edge| layers.edge_visible(edge.from().name(), edge.to().name())) {
+                frame.stroke(
+                    &Path::line(self.to_minimap(graph.position(edge.from().name())), self.to_minimap(graph.position(edge.to().name()))),
+                    edge_stroke(),
+                );
+            }
+
+            for node in graph.nodes().filter(|node| layers.node_visible(node.name())) {
+                let pos = self.to_minimap(graph.position(node.name()));
+                frame.fill_rectangle(pos - Vector::new(1.0, 1.0), Size::new(2.0, 2.0), Color::BLACK);
+            }
+        }
+
+        let view = camera.visible_region(canvas);
+        let top_left = self.to_minimap(view.position());
+
+        frame.stroke(
+            &Path::rectangle(top_left, Size::new(view.width * self.scale, view.height * self.scale)),
+            Stroke::default().with_width(1.5).with_color(Color::from_rgb(0.2, 0.5, 1.0)),
+        );
+        frame.stroke(&Path::rectangle(self.bounds.position(), self.bounds.size()), Stroke::default());
+    }
+
+    fn draw_density(&self, frame: &mut Frame, graph: &LayoutGraph, layers: &Layers) {
+        let columns = (self.bounds.width / Self::CELL) as usize;
+        let rows = (self.bounds.height / Self::CELL) as usize;
+        let mut counts = vec![0usize; columns * rows];
+
+        for node in graph.nodes().filter(|node| layers.node_visible(node.name())) {
+            let pos = self.to_minimap(graph.position(node.name()));
+            let column = ((pos.x - self.bounds.x) / Self::CELL) as usize;
+            let row = ((pos.y - self.bounds.y) / Self::CELL) as usize;
+
+            if column < columns && row < rows {
+                counts[row * columns + column] += 1;
+            }
+        }
+
+        let busiest = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+
+        for (i, count) in counts.into_iter().enumerate().filter(|(_, count)| *count > 0) {
+            let top_left = Point::new(
+                self.bounds.x + (i % columns) as f32 * Self::CELL,
+                self.bounds.y + (i / columns) as f32 * Self::CELL,
+            );
+
+            frame.fill_rectangle(
+                top_left,
+                Size::new(Self::CELL, Self::CELL),
+                Color::from_rgba(0.0, 0.0, 0.0, 0.2 + 0.8 * count as f32 / busiest),
+            );
+        }
+    }
+}
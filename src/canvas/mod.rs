@@ -1,34 +1,285 @@
-use crate::netwk::NetworkGraph;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::netwk::annotation::{Annotation, Annotations, Shape, ShapeKind};
+use crate::netwk::document::Document;
+use crate::netwk::group::Group;
+use crate::netwk::layer::Layers;
+use crate::netwk::layout::{LayoutGraph, LayoutNode};
+use crate::netwk::spatial::{IndexedEdge, SpatialIndex};
 use crate::ui::Message;
 use iced::{
-    canvas::Event,
+    canvas::{Event, Text},
     pure::widget::canvas::{self, Cursor, Fill, FillRule, Frame, Path, Program, Stroke, event::{self, Status}},
-    Color,
+    Color, Vector,
 };
-use iced::{mouse, Point};
+use iced::{keyboard, mouse, Point, Rectangle};
 
+use self::annotation::draw_annotation;
+use self::camera::Camera;
+use self::menu::{ContextMenu, ContextTarget, MenuItem};
+use self::minimap::Minimap;
 use self::mode::{PlaceEdgeProgress, PlaceCurveProgress};
+use self::overlay::Overlay;
+use self::style::NodeStyle;
+use self::tiles::{Sketch, Tile, Tiles};
 
+pub mod annotation;
+pub mod camera;
+pub mod menu;
+pub mod minimap;
 pub mod mode;
+pub mod overlay;
+pub mod style;
+pub mod tiles;
 
-/// This controls the actual canvas which is rendered for the user.
-/// NetworkCanvas struct should hold reference to any external data needed.
-/// while State holds the internal state which can't be modified from outside.
+// This controls the actual canvas which is rendered for the user.
+// NetworkCanvas struct should hold reference to any external data needed.
+// while State holds the internal state which can't be modified from outside.
 
 #[derive(Default)]
 pub struct State {
     mode: mode::Mode,
+    menu: Option<ContextMenu>,
+    panning: Option<(Point, Camera)>,
+    dragging: Option<(Drag, Point)>,
+    /// Layout of the minimap when a drag on it started, held so it doesn't shift under the cursor
+    minimap_drag: Option<Minimap>,
+    modifiers: keyboard::Modifiers,
+}
+
+/// Whatever is being dragged around in view mode, with the last cursor position seen.
+#[derive(Debug, Clone)]
+enum Drag {
+    Node(String),
+    Group(String),
+    Annotation(usize),
+}
+
+/// One viewport onto a document. A document can be shown in several side by side,
+/// each panned and zoomed separately.
+#[derive(Default)]
+pub struct View {
+    pub camera: Camera,
+    /// Group boxes and the annotations under the graph
+    pub under: canvas::Cache,
+    /// The graph itself
+    pub tiles: Tiles,
+    /// Selection rings, the annotations over the graph and the border
+    pub over: canvas::Cache,
+    pub minimap: canvas::Cache,
+}
+
+impl View {
+    /// Throw away everything drawn, for when the camera zooms or the whole document changes.
+    pub fn clear(&mut self) {
+        self.under.clear();
+        self.tiles.clear();
+        self.over.clear();
+        self.minimap.clear();
+    }
+
+    /// The camera moved without zooming, so the tiles are kept and only placed again.
+    pub fn moved(&mut self) {
+        self.under.clear();
+        self.tiles.moved();
+        self.over.clear();
+        self.minimap.clear();
+    }
 }
 
 pub struct NetworkCanvas<'a> {
-    graph: &'a NetworkGraph,
-    cache: &'a canvas::Cache,
-    pen_mode: &'a Option<mode::Mode>
+    /// Which of the document's views this is, so camera changes go back to the right one
+    view: usize,
+    graph: &'a LayoutGraph,
+    index: &'a SpatialIndex,
+    annotations: &'a Annotations,
+    layers: &'a Layers,
+    caches: &'a View,
+    pen_mode: &'a Option<mode::Mode>,
+    camera: &'a Camera,
+    selection: &'a HashSet<String>,
+    styles: &'a HashMap<String, NodeStyle>,
+    /// Results of the last analysis, drawn over the graph
+    overlay: Option<&'a Overlay>,
+    /// Show how long each frame took to build, for benchmarking
+    frame_times: bool,
 }
 
 impl<'a> NetworkCanvas<'a> {
-    pub fn new(graph: &'a NetworkGraph, cache: &'a canvas::Cache, pen_mode: &'a Option<mode::Mode>) -> Self {
-        NetworkCanvas { graph, cache, pen_mode }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        document: &'a Document,
+        view: &'a View,
+        index: usize,
+        pen_mode: &'a Option<mode::Mode>,
+        selection: &'a HashSet<String>,
+        styles: &'a HashMap<String, NodeStyle>,
+        overlay: Option<&'a Overlay>,
+        frame_times: bool,
+    ) -> Self {
+        NetworkCanvas {
+            view: index,
+            graph: &document.graph,
+            index: document.index(),
+            annotations: document.annotations(),
+            layers: document.layers(),
+            caches: view,
+            pen_mode,
+            camera: &view.camera,
+            selection,
+            styles,
+            overlay,
+            frame_times,
+        }
+    }
+
+    // Hit-testing only ever finds things on visible, unlocked layers.
+
+    fn pick_node(&self, pos: Point) -> Option<Arc<LayoutNode>> {
+        let name = self.index.nearest_node(pos.into(), NODE_RADIUS, |name| self.layers.node_interactive(name))?;
+
+        self.graph.get_node(name)
+    }
+
+    fn pick_edge(&self, pos: Point) -> Option<&'a IndexedEdge> {
+        self.index.nearest_edge(pos.into(), 4.0 / self.camera.scale, |from, to| {
+            self.layers.edge_interactive(from, to)
+        })
+    }
+
+    fn pick_annotation(&self, pos: Point) -> Option<&'a Annotation> {
+        let layers = self.layers;
+
+        self.annotations
+            .get_at_where(pos.into(), 4.0 / self.camera.scale, |annotation| layers.annotation_interactive(annotation.id()))
+    }
+
+    fn pick_group(&self, pos: Point) -> Option<&'a Group> {
+        let layers = self.layers;
+
+        self.graph
+            .get_group_at(pos.into(), GROUP_PADDING)
+            .filter(|group| group.members().iter().all(|member| layers.node_interactive(member)))
+    }
+
+    /// Work out what a right click at this world position was aimed at.
+    fn context_target(&self, pos: Point) -> ContextTarget {
+        if let Some(node) = self.pick_node(pos) {
+            ContextTarget::Node(node.name().clone())
+        } else if let Some(edge) = self.pick_edge(pos) {
+            ContextTarget::Edge(edge.from.clone(), edge.to.clone(), edge.nth)
+        } else if let Some(annotation) = self.pick_annotation(pos) {
+            ContextTarget::Annotation(annotation.id())
+        } else if let Some(group) = self.pick_group(pos) {
+            ContextTarget::Group(group.name().clone())
+        } else {
+            ContextTarget::Canvas(pos)
+        }
+    }
+
+    fn context_items(&self, target: ContextTarget, bounds: Rectangle) -> Vec<MenuItem> {
+        match target {
+            ContextTarget::Node(name) => {
+                let pos = Point::from(self.graph.position(&name));
+
+                let mut items = vec![
+                    MenuItem::new("Rename", Message::EditNode(name.clone())),
+                    MenuItem::new("Delete", Message::RemoveNode(name.clone())),
+                    MenuItem::new("Connect to...", Message::ChangePenMode(
+                        mode::Mode::PlaceEdge(PlaceEdgeProgress::From { from: pos }),
+                    )),
+                    MenuItem::new("Shortest path to...", Message::ChangePenMode(
+                        mode::Mode::PickPair(mode::PairTool::ShortestPath, Some(pos)),
+                    )),
+                    MenuItem::new("Select neighbours", Message::SelectNeighbours(name.clone())),
+                    MenuItem::new("Cycle style", Message::CycleNodeStyle(name.clone())),
+                ];
+
+                match self.graph.group_of(&name) {
+                    Some(group) if group.is_collapsed() => {
+                        items.push(MenuItem::new("Expand group", Message::ExpandGroup(group.name().clone())))
+                    }
+                    Some(group) => {
+                        items.push(MenuItem::new("Collapse group", Message::CollapseGroup(group.name().clone())))
+                    }
+                    None => {}
+                }
+
+                items
+            }
+            ContextTarget::Group(name) => vec![
+                MenuItem::new("Collapse", Message::CollapseGroup(name.clone())),
+                MenuItem::new("Ungroup", Message::Ungroup(name)),
+            ],
+            ContextTarget::Edge(from, to, nth) => {
+                let curved = self.graph.curved_between(&from, &to);
+
+                let shape = if curved {
+                    MenuItem::new("Make straight", Message::SetEdgeControl(from.clone(), to.clone(), None))
+                } else {
+                    MenuItem::new("Make curve", Message::SetEdgeControl(
+                        from.clone(),
+                        to.clone(),
+                        self.default_control(&from, &to),
+                    ))
+                };
+
+                vec![
+                    MenuItem::new("Set weight", Message::EditEdge(from.clone(), to.clone())),
+                    MenuItem::new("Capacity and cost", Message::EditEdgeAttributes(from.clone(), to.clone(), nth)),
+                    MenuItem::new("Reverse", Message::ReverseEdge(from.clone(), to.clone())),
+                    shape,
+                    MenuItem::new("Delete", Message::RemoveEdge(from, to)),
+                ]
+            }
+            ContextTarget::Annotation(id) => {
+                let mut items = vec![];
+
+                if let Some(Shape::Text { .. }) = self.annotations.get(id).map(|annotation| &annotation.shape) {
+                    items.push(MenuItem::new("Edit text", Message::EditAnnotationText(id)));
+                }
+
+                items.extend(vec![
+                    MenuItem::new("Cycle colour", Message::CycleAnnotationColor(id)),
+                    MenuItem::new("Toggle fill", Message::ToggleAnnotationFill(id)),
+                    MenuItem::new("Above/below graph", Message::ToggleAnnotationAbove(id)),
+                    MenuItem::new("Delete", Message::RemoveAnnotation(id)),
+                ]);
+
+                items
+            }
+            ContextTarget::Canvas(pos) => {
+                let fitted = match self.graph.bounds() {
+                    Some(region) => Camera::fit(region.into(), bounds.size(), 20.0),
+                    None => Camera::default(),
+                };
+
+                vec![
+                    MenuItem::new("Add node here", Message::AddNode(pos)),
+                    MenuItem::new("Paste", Message::Paste(pos)),
+                    MenuItem::new("Copy selection", Message::Copy),
+                    MenuItem::new("Cut selection", Message::Cut),
+                    MenuItem::new("Duplicate selection", Message::Duplicate),
+                    MenuItem::new("Group selection", Message::GroupSelection),
+                    MenuItem::new("Copy as DOT", Message::CopyAsDot),
+                    MenuItem::new("Select all", Message::SelectAll),
+                    MenuItem::new("Fit view", Message::SetCamera(self.view, fitted)),
+                ]
+            }
+        }
+    }
+
+    /// Control point bowing a straight edge out to one side, used when converting it to a curve.
+    fn default_control(&self, from: &str, to: &str) -> Option<Point> {
+        let (from, to) = (self.graph.get_node(from)?, self.graph.get_node(to)?);
+        let (from, to) = (Point::from(self.graph.position(from.name())), Point::from(self.graph.position(to.name())));
+
+        let mid = Point::new((from.x + to.x) / 2.0, (from.y + to.y) / 2.0);
+        let normal = Vector::new(from.y - to.y, to.x - from.x);
+
+        Some(mid + normal * 0.25)
     }
 }
 
@@ -42,11 +293,16 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
         bounds: iced::Rectangle,
         cursor: iced::canvas::Cursor,
     ) -> (Status, Option<Message>) {
-        let cursor_pos = if let Some(position) = cursor.position_in(&bounds) {
+        if let Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
+            state.modifiers = modifiers;
+        }
+
+        let screen_pos = if let Some(position) = cursor.position_in(&bounds) {
             position
         } else {
             return (Status::Ignored, None);
         };
+        let cursor_pos = self.camera.to_world(screen_pos);
 
         if let Some(new_mode) = self.pen_mode {
             state.mode = *new_mode;
@@ -54,9 +310,65 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
 
         match event {
             Event::Mouse(mouse_event) => {
+                // An open menu swallows the next click, whether or not it lands on an item
+                if let (Some(menu), mouse::Event::ButtonPressed(_)) = (&state.menu, mouse_event) {
+                    let message = menu.item_at(screen_pos);
+                    state.menu = None;
+
+                    return (event::Status::Captured, message);
+                }
+
+                // The minimap sits over everything else, so it gets first go at the mouse
+                if let Some(minimap) = state.minimap_drag.clone() {
+                    return match mouse_event {
+                        mouse::Event::CursorMoved { .. } => {
+                            let camera = self.camera.centre_on(minimap.to_world(screen_pos), bounds.size());
+
+                            (event::Status::Captured, Some(Message::SetCamera(self.view, camera)))
+                        }
+                        // A release outside the canvas is never seen, so any press also ends the drag
+                        mouse::Event::ButtonReleased(mouse::Button::Left) | mouse::Event::ButtonPressed(_) => {
+                            state.minimap_drag = None;
+
+                            (event::Status::Captured, None)
+                        }
+                        _ => (event::Status::Captured, None),
+                    };
+                }
+
+                if let Some(minimap) = Minimap::new(self.index.extent(), self.camera, bounds.size()).filter(|m| m.contains(screen_pos)) {
+                    if let mouse::Event::ButtonPressed(mouse::Button::Left) = mouse_event {
+                        let camera = self.camera.centre_on(minimap.to_world(screen_pos), bounds.size());
+                        state.minimap_drag = Some(minimap);
+
+                        return (event::Status::Captured, Some(Message::SetCamera(self.view, camera)));
+                    }
+
+                    if let mouse::Event::ButtonPressed(_) = mouse_event {
+                        return (event::Status::Captured, None);
+                    }
+                }
+
                 let message = match mouse_event {
                     mouse::Event::ButtonPressed(mouse::Button::Left) => match &state.mode {
-                        mode::Mode::View => None,
+                        mode::Mode::View => match self.pick_node(cursor_pos) {
+                            Some(node) => {
+                                state.dragging = Some((Drag::Node(node.name().clone()), cursor_pos));
+
+                                Some(Message::SelectNode(node.name().clone(), state.modifiers.shift()))
+                            },
+                            None => {
+                                // Annotations and whole groups can be picked up and dragged about
+                                let drag = if let Some(annotation) = self.pick_annotation(cursor_pos) {
+                                    Some(Drag::Annotation(annotation.id()))
+                                } else {
+                                    self.pick_group(cursor_pos).map(|group| Drag::Group(group.name().clone()))
+                                };
+                                state.dragging = drag.map(|drag| (drag, cursor_pos));
+
+                                Some(Message::ClearSelection)
+                            }
+                        },
                         mode::Mode::PlaceNode => {
                             state.mode = mode::Mode::View;
 
@@ -65,18 +377,18 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
                         mode::Mode::PlaceEdge(progress) => {
                             match progress {
                                 PlaceEdgeProgress::None => {
-                                    state.mode = mode::Mode::PlaceEdge( match self.graph.get_near_point(cursor_pos) {
-                                        Some(from) => (mode::PlaceEdgeProgress::From { from: *from.data() }),
+                                    state.mode = mode::Mode::PlaceEdge( match self.pick_node(cursor_pos) {
+                                        Some(from) => (mode::PlaceEdgeProgress::From { from: Point::from(self.graph.position(from.name())) }),
                                         None => mode::PlaceEdgeProgress::None
                                     });
-                                    
+
                                     Some(Message::Ack) // ack so we dont reset progress
                                 },
                                 PlaceEdgeProgress::From { from } => {
-                                    match self.graph.get_near_point(cursor_pos) {
+                                    match self.pick_node(cursor_pos) {
                                         Some(to) => {
                                             let from = from.clone();
-                                            let to = *to.data();
+                                            let to = Point::from(self.graph.position(to.name()));
                                             state.mode = mode::Mode::View;
 
                                             Some(Message::AddEdge(from, to))
@@ -90,8 +402,8 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
                         mode::Mode::PlaceCurve(progress) => {
                             match progress {
                                 PlaceCurveProgress::None => {
-                                    state.mode = mode::Mode::PlaceCurve(match self.graph.get_near_point(cursor_pos) {
-                                        Some(from) => (mode::PlaceCurveProgress::From { from: *from.data() }),
+                                    state.mode = mode::Mode::PlaceCurve(match self.pick_node(cursor_pos) {
+                                        Some(from) => (mode::PlaceCurveProgress::From { from: Point::from(self.graph.position(from.name())) }),
                                         None => mode::PlaceCurveProgress::None
                                     });
 
@@ -99,8 +411,8 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
                                 },
                                 PlaceCurveProgress::From { from } => {
 
-                                    state.mode = mode::Mode::PlaceCurve(match self.graph.get_near_point(cursor_pos) {
-                                        Some(to) => (mode::PlaceCurveProgress::To { from: *from, to: *to.data() }),
+                                    state.mode = mode::Mode::PlaceCurve(match self.pick_node(cursor_pos) {
+                                        Some(to) => (mode::PlaceCurveProgress::To { from: *from, to: Point::from(self.graph.position(to.name())) }),
                                         None => mode::PlaceCurveProgress::From { from: *from }
                                     });
 
@@ -117,16 +429,116 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
                             }
                         } // end place curve mode
 
-                        // Other modes no action     
+                        mode::Mode::PickPair(tool, from) => {
+                            let (tool, from) = (*tool, *from);
+
+                            match (from, self.pick_node(cursor_pos)) {
+                                (None, picked) => {
+                                    state.mode = mode::Mode::PickPair(tool, picked.map(|node| Point::from(self.graph.position(node.name()))));
+
+                                    Some(Message::Ack)
+                                },
+                                (Some(from), Some(to)) => {
+                                    state.mode = mode::Mode::View;
+
+                                    Some(Message::PickedPair(tool, from, Point::from(self.graph.position(to.name()))))
+                                },
+                                (Some(_), None) => None
+                            }
+                        }
+
+                        mode::Mode::PlaceAnnotation(kind, from) => {
+                            match (kind, from) {
+                                (ShapeKind::Text, _) | (_, Some(_)) => {
+                                    let shape = Shape::new(*kind, from.unwrap_or(cursor_pos).into(), cursor_pos.into());
+                                    state.mode = mode::Mode::View;
+
+                                    Some(Message::AddAnnotation(shape))
+                                },
+                                (_, None) => {
+                                    state.mode = mode::Mode::PlaceAnnotation(*kind, Some(cursor_pos));
+
+                                    Some(Message::Ack)
+                                }
+                            }
+                        }
+
+                        // Other modes no action
                         _ => None
                     },
+                    mouse::Event::ButtonPressed(mouse::Button::Right) => {
+                        let target = self.context_target(cursor_pos);
+                        let items = self.context_items(target, bounds);
+
+                        state.menu = Some(ContextMenu::new(screen_pos, items, bounds.size()));
+
+                        None
+                    },
+                    mouse::Event::ButtonPressed(mouse::Button::Middle) => {
+                        state.panning = Some((screen_pos, *self.camera));
+
+                        None
+                    },
+                    mouse::Event::ButtonReleased(mouse::Button::Middle) => {
+                        state.panning = None;
+
+                        None
+                    },
+                    mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                        state.dragging = None;
+
+                        None
+                    },
+                    mouse::Event::CursorMoved { .. } => {
+                        if let Some((start, camera)) = state.panning {
+                            Some(Message::SetCamera(self.view, camera.pan(screen_pos - start)))
+                        } else if let Some((drag, last)) = state.dragging.take() {
+                            let delta = cursor_pos - last;
+                            let message = match &drag {
+                                Drag::Node(name) => Message::MoveNode(name.clone(), delta),
+                                Drag::Group(name) => Message::MoveGroup(name.clone(), delta),
+                                Drag::Annotation(id) => Message::MoveAnnotation(*id, delta),
+                            };
+                            state.dragging = Some((drag, cursor_pos));
+
+                            Some(message)
+                        } else {
+                            None
+                        }
+                    },
+                    mouse::Event::WheelScrolled { delta } => {
+                        let lines = match delta {
+                            mouse::ScrollDelta::Lines { y, .. } => y,
+                            mouse::ScrollDelta::Pixels { y, .. } => y / 20.0,
+                        };
+
+                        Some(Message::SetCamera(self.view, self.camera.zoom_at(screen_pos, 1.1_f32.powf(lines))))
+                    },
                     //  other mouse events need no message response
                     _ => None,
                 };
 
                 (event::Status::Captured, message) // send back message (if generated)
             }
-            // dont care about non-mouse events
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) if modifiers.command() => {
+                let message = match key_code {
+                    keyboard::KeyCode::C => Some(Message::Copy),
+                    keyboard::KeyCode::X => Some(Message::Cut),
+                    keyboard::KeyCode::V => Some(Message::Paste(cursor_pos)),
+                    keyboard::KeyCode::D => Some(Message::Duplicate),
+                    keyboard::KeyCode::A => Some(Message::SelectAll),
+                    keyboard::KeyCode::Z if modifiers.shift() => Some(Message::Redo),
+                    keyboard::KeyCode::Z => Some(Message::Undo),
+                    keyboard::KeyCode::Y => Some(Message::Redo),
+                    _ => None,
+                };
+
+                match message {
+                    Some(message) => (event::Status::Captured, Some(message)),
+                    None => (event::Status::Ignored, None),
+                }
+            }
+            // dont care about other events
             _ => return (event::Status::Ignored, None),
         }
     }
@@ -137,42 +549,330 @@ impl<'a> Program<Message> for NetworkCanvas<'a> {
         bounds: iced::Rectangle,
         cursor: Cursor,
     ) -> Vec<iced::canvas::Geometry> {
-        let content = self.cache.draw(bounds.size(), |frame: &mut Frame| {
-            for node in self.graph.node_list() {
-                frame.fill(
-                    &Path::circle(*node.data(), 5.0),
-                    Fill {
-                        color: Color::BLACK,
-                        rule: FillRule::EvenOdd,
-                    },
-                );
+        let started = Instant::now();
 
-                for conn in node.edges().borrow().iter() {
+        // Only what is on screen gets drawn, padded so rings and labels at the edges still show
+        let region = self.camera.visible_region(bounds.size());
+        let region = Rectangle {
+            x: region.x - CULL_PADDING,
+            y: region.y - CULL_PADDING,
+            width: region.width + 2.0 * CULL_PADDING,
+            height: region.height + 2.0 * CULL_PADDING,
+        };
+        let annotations = self.annotations.iter().filter(|annotation| self.layers.annotation_visible(annotation.id()));
 
-                    let point = conn.destination();
+        let under = self.caches.under.draw(bounds.size(), |frame: &mut Frame| {
+            frame.with_save(|frame| {
+                self.camera.apply(frame);
 
-                    let path = match conn.control() {
-                        None => Path::line(*node.data(), *point.data()),
-                        Some(cpoint) => Path::new(|f| {
-                            f.move_to(*node.data());
-                            f.quadratic_curve_to(*cpoint, *point.data())
-                        }),
-                    };
+                for annotation in annotations.clone().filter(|annotation| !annotation.above) {
+                    draw_annotation(frame, annotation);
+                }
 
-                    frame.stroke(&path, Stroke::default().with_width(2.0));
+                for group in self.graph.groups().iter().filter(|group| {
+                    group.members().iter().any(|member| self.layers.node_visible(member))
+                }) {
+                    if let Some(bounds) = self.graph.group_bounds(group.name()) {
+                        draw_group_box(frame, group.name(), bounds.into(), self.camera.scale >= LABELS_BELOW);
+                    }
+                }
+            });
+        });
+
+        let mut geometry = vec![under];
+
+        // Each tile draws what it owns, see sketch_tile
+        let first = Tiles::tile_at(self.camera, region.position());
+        let tiles = Tiles::covering(self.camera, region);
+        self.caches.tiles.retain(&tiles);
+
+        for &tile in tiles.iter() {
+            // An edge can cross many tiles, so it belongs to the top left one of those on screen;
+            // that way it is drawn exactly once however far the camera has moved since the sketch
+            let owns = |span: Rectangle| {
+                let (x, y) = Tiles::tile_at(self.camera, span.position());
+                (x.max(first.0), y.max(first.1)) == tile
+            };
+
+            geometry.push(self.caches.tiles.draw(
+                tile,
+                bounds.size(),
+                || self.sketch_tile(tile),
+                |frame: &mut Frame, sketch: &Sketch| {
+                    frame.with_save(|frame| {
+                        self.camera.apply(frame);
+                        sketch.play(frame, owns);
+                    });
+                },
+            ));
+        }
+
+        geometry.push(self.caches.over.draw(bounds.size(), |frame: &mut Frame| {
+            frame.with_save(|frame| {
+                self.camera.apply(frame);
+
+                for pos in self.selection.iter().filter_map(|name| self.index.position(name)).map(Point::from) {
+                    if region.contains(pos) {
+                        frame.stroke(
+                            &Path::circle(pos, 8.0),
+                            Stroke::default().with_width(2.0).with_color(Color::from_rgb(0.2, 0.5, 1.0)),
+                        );
+                    }
                 }
-            }
 
+                for annotation in annotations.clone().filter(|annotation| annotation.above) {
+                    draw_annotation(frame, annotation);
+                }
+            });
 
             // Draw outline over canvas
             frame.stroke(
                 &Path::rectangle(Point::ORIGIN, frame.size()),
                 Stroke::default()
             );
+        }));
+
+        geometry.push(state.mode.draw(bounds, cursor, self.camera));
+
+        if let Some(minimap) = Minimap::new(self.index.extent(), self.camera, bounds.size()) {
+            geometry.push(self.caches.minimap.draw(bounds.size(), |frame: &mut Frame| {
+                minimap.draw(frame, self.graph, self.layers, self.camera, bounds.size());
+            }));
+        }
+
+        if let Some(menu) = &state.menu {
+            let mut frame = Frame::new(bounds.size());
+            menu.draw(&mut frame, cursor.position_in(&bounds));
+
+            geometry.push(frame.into_geometry());
+        }
+
+        if self.frame_times {
+            let mut frame = Frame::new(bounds.size());
+            frame.fill_text(Text {
+                content: format!(
+                    "{:.1} ms, {} tiles, {} nodes",
+                    started.elapsed().as_secs_f32() * 1000.0,
+                    tiles.len(),
+                    self.graph.node_list().len(),
+                ),
+                position: Point::new(8.0, 8.0),
+                ..Text::default()
+            });
+
+            geometry.push(frame.into_geometry());
+        }
+
+        geometry
+    }
+}
+
+impl<'a> NetworkCanvas<'a> {
+    /// Colour for something the overlay, if any, doesn't mark.
+    fn unmarked(&self, color: Color) -> Color {
+        self.overlay.map_or(color, |overlay| overlay.unmarked(color))
+    }
+
+    /// Sketch the nodes sitting in one tile and every edge crossing it. Which tile draws an
+    /// edge depends on where the camera is, so that is left until playback; either way a
+    /// change to an edge only touches the tiles it crosses.
+    fn sketch_tile(&self, tile: Tile) -> Sketch {
+        let mut sketch = Sketch::default();
+        let region = Tiles::bounds(self.camera, tile);
+
+        let edges = self.index.edges_in(region.into()).into_iter().filter(|edge| {
+            self.layers.edge_visible(&edge.from, &edge.to)
         });
 
-        let pen = state.mode.draw(bounds, cursor);
+        if self.camera.scale < AGGREGATE_BELOW {
+            // Too far out to tell edges apart, so those running between the same two patches
+            // of screen are drawn as one line, thicker the more of them there are
+            let cell = AGGREGATE_CELL / self.camera.scale;
+            let cell_of = |pos: Point| -> Cell { ((pos.x / cell).floor() as i32, (pos.y / cell).floor() as i32) };
+            let centre = |(x, y): Cell| Point::new((x as f32 + 0.5) * cell, (y as f32 + 0.5) * cell);
+
+            let mut bundles: HashMap<(Cell, Cell), (Rectangle, usize)> = HashMap::new();
+
+            for edge in edges {
+                let (a, b) = (cell_of(edge.from_pos.into()), cell_of(edge.to_pos.into()));
+                let key = if a <= b { (a, b) } else { (b, a) };
+                let (from, to) = (centre(key.0), centre(key.1));
+                let span = Rectangle::new(
+                    Point::new(from.x.min(to.x), from.y.min(to.y)),
+                    iced::Size::new((from.x - to.x).abs(), (from.y - to.y).abs()),
+                );
+
+                if a != b {
+                    bundles.entry(key).or_insert((span, 0)).1 += 1;
+                }
+            }
+
+            for ((a, b), (span, count)) in bundles {
+                let width = (1.0 + (count as f32).log2() * 0.5) / self.camera.scale;
 
-        vec![content, pen]
+                sketch.stroke_across(span, &Path::line(centre(a), centre(b)), width, Color::from_rgba(0.0, 0.0, 0.0, 0.6));
+            }
+        } else {
+            for edge in edges {
+                let span: Rectangle = edge.bounds().into();
+                let (from, to) = (Point::from(edge.from_pos), Point::from(edge.to_pos));
+                let control = edge.control.map(Point::from);
+
+                let path = match control {
+                    None => Path::line(from, to),
+                    Some(cpoint) => Path::new(|f| {
+                        f.move_to(from);
+                        f.quadratic_curve_to(cpoint, to)
+                    }),
+                };
+
+                let mark = self.overlay.and_then(|overlay| overlay.edge(&edge.from, &edge.to));
+                let (color, width) = match mark {
+                    Some(mark) => (mark.color, mark.size.unwrap_or(2.0)),
+                    None => (self.unmarked(Color::BLACK), 2.0),
+                };
+
+                sketch.stroke_across(span, &path, width, color);
+
+                if self.graph.is_directed() {
+                    draw_arrow_head(&mut sketch, span, control.unwrap_or(from), to, color);
+                }
+
+                if let Some(label) = mark.and_then(|mark| mark.label.as_ref()) {
+                    if self.camera.scale >= LABELS_BELOW {
+                        // Halfway along a curve is halfway between the middle of the ends and the control point
+                        let middle = Point::new((from.x + to.x) / 2.0, (from.y + to.y) / 2.0);
+                        let middle = control.map_or(middle, |c| Point::new((middle.x + c.x) / 2.0, (middle.y + c.y) / 2.0));
+
+                        sketch.fill_text_across(span, Text {
+                            content: label.clone(),
+                            position: middle + Vector::new(4.0, 4.0),
+                            color,
+                            ..Text::default()
+                        });
+                    }
+                }
+            }
+        }
+
+        let nodes = self.index.nodes_in(region.into()).into_iter().filter(|(name, pos)| {
+            Tiles::tile_at(self.camera, (*pos).into()) == tile && self.layers.node_visible(name)
+        });
+
+        for (name, pos) in nodes {
+            let pos = Point::from(pos);
+            let style = self.styles.get(name).copied().unwrap_or_default();
+            let mark = self.overlay.and_then(|overlay| overlay.node(name));
+            let (color, radius) = match mark {
+                Some(mark) => (mark.color, mark.size.unwrap_or(NODE_RADIUS)),
+                None => (self.unmarked(style.color()), NODE_RADIUS),
+            };
+
+            if self.camera.scale < DOTS_BELOW {
+                // A square is far cheaper to build than a circle and looks the same this small
+                let half = Vector::new(radius, radius);
+                sketch.fill_rectangle(pos - half, iced::Size::new(2.0 * radius, 2.0 * radius), color);
+            } else {
+                sketch.fill(
+                    &Path::circle(pos, radius),
+                    Fill {
+                        color,
+                        rule: FillRule::EvenOdd,
+                    },
+                );
+            }
+
+            if let Some(label) = mark.and_then(|mark| mark.label.as_ref()) {
+                if self.camera.scale >= LABELS_BELOW {
+                    sketch.fill_text(Text {
+                        content: label.clone(),
+                        position: pos + Vector::new(radius + 3.0, -radius - 12.0),
+                        color,
+                        ..Text::default()
+                    });
+                }
+            }
+
+            if let Some(group) = self.graph.groups().iter().find(|group| group.summary() == Some(name)) {
+                // Collapsed groups get a ring and their name so they stand out from plain nodes
+                sketch.stroke(&Path::circle(pos, 9.0), 2.0, style.color());
+
+                if self.camera.scale >= LABELS_BELOW {
+                    sketch.fill_text(Text {
+                        content: format!("{} ({})", group.name(), group.members().len()),
+                        position: pos + Vector::new(12.0, -8.0),
+                        ..Text::default()
+                    });
+                }
+            }
+        }
+
+        sketch
+    }
+}
+
+/// Space left between a group's nodes and the edge of its box.
+const GROUP_PADDING: f32 = 15.0;
+
+const NODE_RADIUS: f32 = 5.0;
+
+/// How far past the edge of the view things are still drawn, enough for a collapsed group's label.
+const CULL_PADDING: f32 = 120.0;
+
+// Levels of detail, by camera scale. Below each the canvas drops something which would be too
+// small to make out anyway.
+const LABELS_BELOW: f32 = 0.5;
+const DOTS_BELOW: f32 = 0.35;
+const AGGREGATE_BELOW: f32 = 0.2;
+
+/// Side, in screen pixels, of the patches edges are bundled between when aggregated.
+/// Divides Tiles::SIZE so every patch sits inside one tile.
+const AGGREGATE_CELL: f32 = 16.0;
+
+/// A patch of screen edges are bundled by when zoomed far out.
+type Cell = (i32, i32);
+
+fn draw_group_box(frame: &mut Frame, name: &str, bounds: Rectangle, label: bool) {
+    let top_left = Point::new(bounds.x - GROUP_PADDING, bounds.y - GROUP_PADDING);
+    let size = iced::Size::new(bounds.width + 2.0 * GROUP_PADDING, bounds.height + 2.0 * GROUP_PADDING);
+    let color = Color::from_rgb(0.3, 0.5, 0.7);
+
+    frame.fill_rectangle(top_left, size, Color { a: 0.1, ..color });
+    frame.stroke(&Path::rectangle(top_left, size), Stroke::default().with_color(color));
+
+    if label {
+        frame.fill_text(Text {
+            content: name.to_string(),
+            position: top_left + Vector::new(0.0, -18.0),
+            color,
+            ..Text::default()
+        });
+    }
+}
+
+/// Arrow head touching the edge of the node circle at `tip`, pointing away from `from`.
+/// Drawn by whichever tile draws the edge spanning `span`.
+fn draw_arrow_head(sketch: &mut Sketch, span: Rectangle, from: Point, tip: Point, color: Color) {
+    let length = from.distance(tip);
+
+    if length == 0.0 {
+        return;
     }
+
+    let dir = Vector::new((tip.x - from.x) / length, (tip.y - from.y) / length);
+    let normal = Vector::new(-dir.y, dir.x);
+
+    let tip = tip - dir * 5.0;
+    let base = tip - dir * 8.0;
+
+    sketch.fill_across(
+        span,
+        &Path::new(|f| {
+            f.move_to(tip);
+            f.line_to(base + normal * 4.0);
+            f.line_to(base - normal * 4.0);
+            f.close();
+        }),
+        color,
+    );
 }
@@ -2,6 +2,10 @@ use iced::canvas::FillRule;
 use iced::pure::widget::canvas::{Cursor, Fill, Frame, Geometry, Path, Stroke};
 use iced::{Color, Point, Rectangle};
 
+use super::annotation::draw_shape;
+use super::camera::Camera;
+use crate::netwk::annotation::{AnnotationStyle, Shape, ShapeKind};
+
 /// Defines the different Pen modes available for the canvas.
 #[derive(Debug, Clone, Copy)]
 pub enum Mode {
@@ -9,8 +13,22 @@ pub enum Mode {
     PlaceNode,
     PlaceEdge(PlaceEdgeProgress),
     PlaceCurve(PlaceCurveProgress),
+    /// Annotations are placed with a click at each corner (text needs only the first)
+    PlaceAnnotation(ShapeKind, Option<Point>),
     RemoveNode,
     RemoveEdge,
+    /// Two nodes are picked in turn for an analysis which runs between them
+    PickPair(PairTool, Option<Point>),
+}
+
+/// Analyses run between a pair of nodes picked on the canvas, the first picked being the source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PairTool {
+    ShortestPath,
+    KShortest,
+    EqualCost,
+    MaxFlow,
+    MinCostFlow,
 }
 
 /// Defines progress through the Edge creation process (click startpoint, click endpoint)
@@ -34,10 +52,11 @@ impl Default for Mode {
 }
 
 impl Mode {
-    pub fn draw(&self, bounds: Rectangle, cursor: Cursor) -> Geometry {
+    pub fn draw(&self, bounds: Rectangle, cursor: Cursor, camera: &Camera) -> Geometry {
         let mut frame = Frame::new(bounds.size());
+        camera.apply(&mut frame);
 
-        if let Some(cursor_pos) = cursor.position_in(&bounds) {
+        if let Some(cursor_pos) = cursor.position_in(&bounds).map(|pos| camera.to_world(pos)) {
             match self {
                 Mode::PlaceNode => {
                     frame.fill(
@@ -77,6 +96,19 @@ impl Mode {
                         )
                     }
                 },
+
+                Mode::PickPair(_, Some(from)) => {
+                    frame.stroke(
+                        &Path::line(*from, cursor_pos),
+                        Stroke::default().with_width(2.0).with_color(Color::from_rgb(0.95, 0.5, 0.1)),
+                    );
+                },
+
+                Mode::PlaceAnnotation(kind, from) => {
+                    let from = from.unwrap_or(cursor_pos);
+
+                    draw_shape(&mut frame, &Shape::new(*kind, from.into(), cursor_pos.into()), &AnnotationStyle::default());
+                },
                 _ => {}
             }
         }
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use iced::Color;
+
+/// Results drawn over the graph: marked nodes and edges get their own colour, size and label,
+/// and everything else can be faded back so they stand out.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    pub nodes: HashMap<String, Mark>,
+    /// Keyed by the names at either end, the way the edge was drawn
+    pub edges: HashMap<(String, String), Mark>,
+    pub dim: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mark {
+    pub color: Color,
+    /// Radius for nodes and line width for edges, or None for the usual
+    pub size: Option<f32>,
+    pub label: Option<String>,
+}
+
+impl Mark {
+    pub fn new(color: Color) -> Self {
+        Mark { color, size: None, label: None }
+    }
+
+    pub fn with_size(self, size: f32) -> Self {
+        Mark { size: Some(size), ..self }
+    }
+
+    pub fn with_label(self, label: impl Into<String>) -> Self {
+        Mark { label: Some(label.into()), ..self }
+    }
+}
+
+impl Overlay {
+    /// How much of their colour unmarked things keep when dimmed.
+    const FADE: f32 = 0.15;
+
+    pub fn node(&self, name: &str) -> Option<&Mark> {
+        self.nodes.get(name)
+    }
+
+    pub fn edge(&self, from: &str, to: &str) -> Option<&Mark> {
+        self.edges.get(&(from.to_string(), to.to_string()))
+    }
+
+    /// Add the other's marks for whatever this doesn't already mark.
+    pub fn extend_under(&mut self, other: Overlay) {
+        for (name, mark) in other.nodes {
+            self.nodes.entry(name).or_insert(mark);
+        }
+        for (ends, mark) in other.edges {
+            self.edges.entry(ends).or_insert(mark);
+        }
+
+        self.dim |= other.dim;
+    }
+
+    /// Colour to draw something unmarked in.
+    pub fn unmarked(&self, color: Color) -> Color {
+        if self.dim {
+            Color { a: color.a * Self::FADE, ..color }
+        } else {
+            color
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use iced::Color;
+
+/// Fill colours a node can be drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NodeStyle {
+    #[default]
+    Default,
+    Red,
+    Green,
+    Blue,
+    Orange,
+}
+
+impl NodeStyle {
+    pub fn color(&self) -> Color {
+        match self {
+            NodeStyle::Default => Color::BLACK,
+            NodeStyle::Red => Color::from_rgb(0.85, 0.2, 0.2),
+            NodeStyle::Green => Color::from_rgb(0.2, 0.65, 0.3),
+            NodeStyle::Blue => Color::from_rgb(0.2, 0.4, 0.85),
+            NodeStyle::Orange => Color::from_rgb(0.95, 0.6, 0.1),
+        }
+    }
+
+    /// The style after this one, wrapping back round to the default.
+    pub fn next(&self) -> NodeStyle {
+        match self {
+            NodeStyle::Default => NodeStyle::Red,
+            NodeStyle::Red => NodeStyle::Green,
+            NodeStyle::Green => NodeStyle::Blue,
+            NodeStyle::Blue => NodeStyle::Orange,
+            NodeStyle::Orange => NodeStyle::Default,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use iced::pure::widget::canvas::{Cache, Fill, Frame, Geometry, Path, Stroke, Text};
+use iced::{Color, Point, Rectangle, Size};
+
+use super::camera::Camera;
+
+/// Column and row of a tile, counted in tiles from the world origin.
+pub type Tile = (i32, i32);
+
+/// The graph drawn in square tiles, each cached on its own so a change only costs redrawing
+/// the tiles it touches. Tiles are a fixed size on screen, so they only hold for the scale they
+/// were drawn at. Each keeps a sketch of its contents in world space, so when the camera only
+/// moves the sketch is played back at the new position instead of going through the graph again.
+#[derive(Default)]
+pub struct Tiles {
+    entries: RefCell<HashMap<Tile, Entry>>,
+}
+
+struct Entry {
+    sketch: Sketch,
+    cache: Cache,
+}
+
+impl Tiles {
+    /// Side of a tile in screen pixels.
+    pub const SIZE: f32 = 256.0;
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    /// The camera moved without zooming: keep every sketch but play them back again.
+    pub fn moved(&self) {
+        for entry in self.entries.borrow_mut().values_mut() {
+            entry.cache.clear();
+        }
+    }
+
+    /// Forget every tile overlapping a world region.
+    pub fn invalidate(&self, camera: &Camera, region: Rectangle) {
+        let (left, top) = Self::tile_at(camera, region.position());
+        let (right, bottom) = Self::tile_at(camera, Point::new(region.x + region.width, region.y + region.height));
+
+        self.entries
+            .borrow_mut()
+            .retain(|(x, y), _| *x < left || *x > right || *y < top || *y > bottom);
+    }
+
+    /// Forget the tiles which have gone off screen, so panning around doesn't pile them up.
+    pub fn retain(&self, tiles: &[Tile]) {
+        let keep: HashSet<&Tile> = tiles.iter().collect();
+
+        self.entries.borrow_mut().retain(|tile, _| keep.contains(tile));
+    }
+
+    /// The tile's geometry. The sketch is only made again if the tile has been invalidated,
+    /// and played back if the camera moved or the canvas resized.
+    pub fn draw(
+        &self,
+        tile: Tile,
+        size: Size,
+        sketch: impl FnOnce() -> Sketch,
+        play: impl Fn(&mut Frame, &Sketch),
+    ) -> Geometry {
+        let mut entries = self.entries.borrow_mut();
+        let entry = entries.entry(tile).or_insert_with(|| Entry {
+            sketch: sketch(),
+            cache: Cache::default(),
+        });
+
+        entry.cache.draw(size, |frame| play(frame, &entry.sketch))
+    }
+
+    pub fn tile_at(camera: &Camera, world: Point) -> Tile {
+        let side = Self::SIZE / camera.scale;
+
+        ((world.x / side).floor() as i32, (world.y / side).floor() as i32)
+    }
+
+    /// World area a tile covers.
+    pub fn bounds(camera: &Camera, tile: Tile) -> Rectangle {
+        let side = Self::SIZE / camera.scale;
+
+        Rectangle::new(Point::new(tile.0 as f32 * side, tile.1 as f32 * side), Size::new(side, side))
+    }
+
+    /// Every tile overlapping a world region, row by row.
+    pub fn covering(camera: &Camera, region: Rectangle) -> Vec<Tile> {
+        let (left, top) = Self::tile_at(camera, region.position());
+        let (right, bottom) = Self::tile_at(camera, Point::new(region.x + region.width, region.y + region.height));
+
+        (top..=bottom).flat_map(|y| (left..=right).map(move |x| (x, y))).collect()
+    }
+}
+
+/// Drawing recorded in world space, to be played back onto a frame wherever the camera is.
+/// Anything which may cross into other tiles is recorded with the world area it spans, so
+/// which tile draws it can be settled on playback.
+#[derive(Default)]
+pub struct Sketch {
+    marks: Vec<(Option<Rectangle>, Mark)>,
+}
+
+enum Mark {
+    Stroke(Path, f32, Color),
+    Fill(Path, Fill),
+    Rectangle(Point, Size, Color),
+    Text(Text),
+}
+
+impl Sketch {
+    pub fn stroke(&mut self, path: &Path, width: f32, color: Color) {
+        self.marks.push((None, Mark::Stroke(path.clone(), width, color)));
+    }
+
+    /// Like stroke, for something spanning an area which may reach other tiles.
+    pub fn stroke_across(&mut self, span: Rectangle, path: &Path, width: f32, color: Color) {
+        self.marks.push((Some(span), Mark::Stroke(path.clone(), width, color)));
+    }
+
+    pub fn fill(&mut self, path: &Path, fill: impl Into<Fill>) {
+        self.marks.push((None, Mark::Fill(path.clone(), fill.into())));
+    }
+
+    pub fn fill_across(&mut self, span: Rectangle, path: &Path, fill: impl Into<Fill>) {
+        self.marks.push((Some(span), Mark::Fill(path.clone(), fill.into())));
+    }
+
+    pub fn fill_rectangle(&mut self, top_left: Point, size: Size, color: Color) {
+        self.marks.push((None, Mark::Rectangle(top_left, size, color)));
+    }
+
+    pub fn fill_text(&mut self, text: Text) {
+        self.marks.push((None, Mark::Text(text)));
+    }
+
+    pub fn fill_text_across(&mut self, span: Rectangle, text: Text) {
+        self.marks.push((Some(span), Mark::Text(text)));
+    }
+
+    /// Draw everything recorded, leaving out what spans other tiles unless `owns` says
+    /// this tile is the one to draw it.
+    pub fn play(&self, frame: &mut Frame, owns: impl Fn(Rectangle) -> bool) {
+        for (span, mark) in self.marks.iter() {
+            if span.is_some_and(|span| !owns(span)) {
+                continue;
+            }
+
+            match mark {
+                Mark::Stroke(path, width, color) => {
+                    frame.stroke(path, Stroke::default().with_width(*width).with_color(*color))
+                }
+                Mark::Fill(path, fill) => frame.fill(path, *fill),
+                Mark::Rectangle(top_left, size, color) => frame.fill_rectangle(*top_left, *size, *color),
+                Mark::Text(text) => frame.fill_text(text.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Tiles cover a fixed amount of screen, so more of the world the further out the camera is.
+    #[test]
+    pub fn test_covering () {
+        let camera = Camera::default();
+        let region = Rectangle::new(Point::new(-1.0, 0.0), Size::new(Tiles::SIZE, 1.0));
+
+        assert_eq!(Tiles::covering(&camera, region), vec![(-1, 0), (0, 0)]);
+        assert_eq!(Tiles::bounds(&camera, (1, 0)).x, Tiles::SIZE);
+
+        let far = Camera { scale: 0.5, ..camera };
+        assert_eq!(Tiles::tile_at(&far, Point::new(Tiles::SIZE * 1.5, 0.0)), (0, 0));
+    }
+}
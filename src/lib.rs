@@ -0,0 +1,17 @@
+//! Network diagrams as a library. The graph model in `netwk` has no dependencies of its own;
+//! the rest is switched on by features:
+//!
+//! - `gui`: the iced editor, `canvas`, `ui` and its `analysis` tools (on by default)
+//! - `serde`: JSON save files and the clipboard format
+//! - `algorithms`: path finding, flows and the like over `NetworkGraph` (on by default)
+//!
+//! Using just the model is `ntwk = { default-features = false }`.
+
+pub mod netwk;
+
+#[cfg(feature = "gui")]
+pub mod analysis;
+#[cfg(feature = "gui")]
+pub mod canvas;
+#[cfg(feature = "gui")]
+pub mod ui;
@@ -1,34 +1,45 @@
 use std::env;
-use iced::pure::Sandbox;
+use iced::pure::Application;
 
 use iced::Settings;
 use iced::window;
 use iced::window::Position;
-
-pub mod netwk;
-pub mod canvas;
-pub mod ui;
+use ntwk::netwk::format::DocumentData;
+use ntwk::netwk::generate::benchmark_graph;
+use ntwk::ui;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     match args.get(1).map(|x| x.as_str()).unwrap_or("ui") {
-        "ui" => {
-
-            let default_settings = Settings::default();
-            let default_window = window::Settings::default();
+        "ui" => run(ui::Flags::default()),
+        // Open a large generated graph with frame times shown, e.g. `ntwk bench 100000`
+        "bench" => {
+            let nodes = args.get(2).and_then(|n| n.parse().ok()).unwrap_or(100_000);
+            let graph = benchmark_graph(nodes, 1);
 
-            ui::NetworkUI::run(Settings {
-                window: window::Settings {
-                    size: (500, 600),
-                    position: Position::Centered,
-                    ..default_window
-                },
-                ..default_settings
-            }).expect("Application exited with error")
+            run(ui::Flags {
+                document: Some(DocumentData { graph, ..DocumentData::default() }),
+                frame_times: true,
+            })
         }
         _ => {
             println!("Hello World!");
         }
     }
 }
+
+fn run(flags: ui::Flags) {
+    let default_settings = Settings::default();
+    let default_window = window::Settings::default();
+
+    ui::NetworkUI::run(Settings {
+        window: window::Settings {
+            size: (800, 600),
+            position: Position::Centered,
+            ..default_window
+        },
+        flags,
+        ..default_settings
+    }).expect("Application exited with error")
+}
@@ -0,0 +1,289 @@
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use super::{multipath, Network, Visit};
+use crate::netwk::job::Progress;
+
+/// Chance PageRank's random walker follows a link rather than jumping anywhere.
+const DAMPING: f32 = 0.85;
+/// Iterative measures stop once no value moves by more than this, or after so many rounds.
+const TOLERANCE: f32 = 1e-6;
+const ROUNDS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Degree,
+    Closeness,
+    Betweenness,
+    Eigenvector,
+    PageRank,
+}
+
+impl Measure {
+    pub const ALL: [Measure; 5] =
+        [Measure::Degree, Measure::Closeness, Measure::Betweenness, Measure::Eigenvector, Measure::PageRank];
+}
+
+impl fmt::Display for Measure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Measure::Degree => write!(f, "Degree"),
+            Measure::Closeness => write!(f, "Closeness"),
+            Measure::Betweenness => write!(f, "Betweenness"),
+            Measure::Eigenvector => write!(f, "Eigenvector"),
+            Measure::PageRank => write!(f, "PageRank"),
+        }
+    }
+}
+
+/// How important each node is, by several measures.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Centrality {
+    /// Links at the node, whichever way they go. Links to itself don't count
+    pub degree: Vec<f32>,
+    /// How near the node is to everything it reaches, scaled down by how little it reaches
+    pub closeness: Vec<f32>,
+    /// Share of shortest paths between other nodes which pass through the node
+    pub betweenness: Vec<f32>,
+    /// Importance from being joined to other important nodes, the most important being 1
+    pub eigenvector: Vec<f32>,
+    /// Chance of a random walk along links being at the node, adding up to 1
+    pub pagerank: Vec<f32>,
+}
+
+impl Centrality {
+    pub fn get(&self, measure: Measure) -> &[f32] {
+        match measure {
+            Measure::Degree => &self.degree,
+            Measure::Closeness => &self.closeness,
+            Measure::Betweenness => &self.betweenness,
+            Measure::Eigenvector => &self.eigenvector,
+            Measure::PageRank => &self.pagerank,
+        }
+    }
+}
+
+/// Every measure for every node. Closeness and betweenness go by weight, or by hops if any
+/// weights are negative. Gives None if cancelled.
+pub fn centrality(net: &Network, progress: &Progress) -> Option<Centrality> {
+    let hops;
+    let measured = if net.has_negative() {
+        hops = net.with_weights(std::iter::repeat(1.0));
+        &hops
+    } else {
+        net
+    };
+    let (closeness, betweenness) = brandes(measured, progress)?;
+
+    Some(Centrality {
+        degree: degree(net),
+        closeness,
+        betweenness,
+        eigenvector: eigenvector(net),
+        pagerank: pagerank(net),
+    })
+}
+
+/// Links at each node, whichever way they go.
+pub fn degree(net: &Network) -> Vec<f32> {
+    let mut degree = vec![0.0; net.len()];
+
+    for link in net.links.iter().filter(|link| link.from != link.to) {
+        degree[link.from] += 1.0;
+        degree[link.to] += 1.0;
+    }
+
+    degree
+}
+
+/// Closeness and betweenness together, as both come from a shortest path search from every
+/// node (Brandes' algorithm). Paths of equal cost share the credit for the nodes they pass
+/// through. Directed networks measure closeness going out from each node.
+pub fn brandes(net: &Network, progress: &Progress) -> Option<(Vec<f32>, Vec<f32>)> {
+    let n = net.len();
+    let mut closeness = vec![0.0; n];
+    let mut betweenness = vec![0.0f64; n];
+
+    for source in 0..n {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(source as f32 / n as f32, "Measuring centrality");
+
+        let mut cost = vec![f32::INFINITY; n];
+        // How many shortest paths reach each node, and the nodes just before it on them
+        let mut paths = vec![0.0f64; n];
+        let mut before: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut settled = vec![false; n];
+        let mut order = vec![];
+        let mut queue = BinaryHeap::new();
+
+        cost[source] = 0.0;
+        paths[source] = 1.0;
+        queue.push(Visit { cost: 0.0, node: source });
+
+        while let Some(Visit { cost: reached, node }) = queue.pop() {
+            if settled[node] {
+                continue;
+            }
+            settled[node] = true;
+            order.push(node);
+
+            for &(link, other) in net.adjacent(node) {
+                let through = reached + net.links[link].weight;
+
+                // Equal paths can add up to slightly different sums, and still share the credit
+                if cost[other].is_finite() && multipath::same_cost(through, cost[other]) {
+                    if !settled[other] {
+                        paths[other] += paths[node];
+                        before[other].push(node);
+                    }
+                } else if through < cost[other] {
+                    cost[other] = through;
+                    paths[other] = paths[node];
+                    before[other] = vec![node];
+                    queue.push(Visit { cost: through, node: other });
+                }
+            }
+        }
+
+        // Wasserman and Faust's closeness, which copes with nodes not reaching everything
+        let total: f32 = order.iter().map(|&node| cost[node]).sum();
+        if total > 0.0 && n > 1 {
+            let reached = (order.len() - 1) as f32;
+            closeness[source] = (reached / (n - 1) as f32) * (reached / total);
+        }
+
+        // Credit flows back from the furthest nodes
+        let mut dependency = vec![0.0f64; n];
+        for &node in order.iter().rev() {
+            for &previous in before[node].iter() {
+                dependency[previous] += paths[previous] / paths[node] * (1.0 + dependency[node]);
+            }
+            if node != source {
+                betweenness[node] += dependency[node];
+            }
+        }
+    }
+
+    // Every ordered pair of other nodes is counted, which for undirected networks is each pair twice
+    let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 } else { 1.0 };
+    let betweenness = betweenness.into_iter().map(|value| (value / pairs) as f32).collect();
+
+    Some((closeness, betweenness))
+}
+
+/// Importance as the sum of the importance of the nodes linking in, by power iteration.
+/// Weights are ignored. Each round also keeps some of what the node had, which stops the
+/// values swinging back and forth forever on networks made of two halves.
+pub fn eigenvector(net: &Network) -> Vec<f32> {
+    let mut values = vec![1.0; net.len()];
+
+    for _ in 0..ROUNDS {
+        let mut next = values.clone();
+        for (node, &value) in values.iter().enumerate() {
+            for &(_, other) in net.adjacent(node) {
+                next[other] += value;
+            }
+        }
+
+        let most = next.iter().fold(0.0f32, |most, &value| most.max(value));
+        if most == 0.0 {
+            break;
+        }
+        next.iter_mut().for_each(|value| *value /= most);
+
+        let moved = values.iter().zip(next.iter()).fold(0.0f32, |moved, (a, b)| moved.max((a - b).abs()));
+        values = next;
+        if moved < TOLERANCE {
+            break;
+        }
+    }
+
+    values
+}
+
+/// Google's PageRank: how often a walker following random links, sometimes jumping to a
+/// random node instead, would be at each node. Nodes with no way on send the walker anywhere.
+pub fn pagerank(net: &Network) -> Vec<f32> {
+    let n = net.len();
+    if n == 0 {
+        return vec![];
+    }
+
+    let mut ranks = vec![1.0 / n as f32; n];
+
+    for _ in 0..ROUNDS {
+        let stuck: f32 = (0..n).filter(|&node| net.adjacent(node).is_empty()).map(|node| ranks[node]).sum();
+        let mut next = vec![(1.0 - DAMPING + DAMPING * stuck) / n as f32; n];
+
+        for (node, &rank) in ranks.iter().enumerate() {
+            let leaving = net.adjacent(node);
+            for &(_, other) in leaving {
+                next[other] += DAMPING * rank / leaving.len() as f32;
+            }
+        }
+
+        let moved: f32 = ranks.iter().zip(next.iter()).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if moved < TOLERANCE {
+            break;
+        }
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-3)
+    }
+
+    /// On a line of five nodes the middle one is the most central by every measure but
+    /// PageRank, where the ends hand everything to their one neighbour.
+    #[test]
+    pub fn test_centrality () {
+        let net = network(5, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 4, 1.0)], false);
+        let centrality = centrality(&net, &Progress::default()).unwrap();
+
+        assert_eq!(centrality.degree, vec![1.0, 2.0, 2.0, 2.0, 1.0]);
+        assert!(close(&centrality.betweenness, &[0.0, 0.5, 4.0 / 6.0, 0.5, 0.0]));
+        assert!(close(&centrality.closeness, &[0.4, 4.0 / 7.0, 4.0 / 6.0, 4.0 / 7.0, 0.4]));
+        assert!((centrality.pagerank.iter().sum::<f32>() - 1.0).abs() < 1e-3);
+        assert!(centrality.pagerank[1] > centrality.pagerank[2] && centrality.pagerank[2] > centrality.pagerank[0]);
+
+        for measure in Measure::ALL.into_iter().filter(|&measure| measure != Measure::PageRank) {
+            let values = centrality.get(measure);
+            assert!(values[2] >= values[1] && values[1] > values[0], "{}", measure);
+        }
+    }
+
+    /// Equal cost paths split the credit, and weights lengthen paths.
+    #[test]
+    pub fn test_betweenness () {
+        // A square: each of the pairs across it has two shortest paths
+        let square = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 0, 1.0)], false);
+        let (_, betweenness) = brandes(&square, &Progress::default()).unwrap();
+        assert!(close(&betweenness, &[1.0 / 6.0; 4]));
+
+        // Unless one way round is dearer
+        let square = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 5.0), (3, 0, 1.0)], false);
+        let (_, betweenness) = brandes(&square, &Progress::default()).unwrap();
+        assert!(close(&betweenness, &[4.0 / 6.0, 4.0 / 6.0, 0.0, 0.0]));
+
+        // 0.3 + 0.6 comes out a hair over 0.4 + 0.5, but the two ways round still tie
+        let square = network(4, &[(0, 1, 0.3), (1, 2, 0.6), (2, 3, 0.5), (3, 0, 0.4)], false);
+        let (_, betweenness) = brandes(&square, &Progress::default()).unwrap();
+        assert!(close(&betweenness, &[2.0 / 6.0, 1.0 / 6.0, 0.0, 1.0 / 6.0]));
+
+        // The star's hub gets the walkers that have nowhere else to go
+        let star = network(4, &[(1, 0, 1.0), (2, 0, 1.0), (3, 0, 1.0)], true);
+        let ranks = pagerank(&star);
+        assert!(ranks[0] > ranks[1] && close(&ranks[1..], &[ranks[1]; 3]));
+    }
+}
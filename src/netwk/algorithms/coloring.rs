@@ -0,0 +1,390 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use super::Network;
+use crate::netwk::job::Progress;
+
+// Colourings ignore direction, and links from a node to itself, which no colouring of nodes
+// could satisfy.
+
+/// A colour, numbered from 0, for each node (or each link, for edge colourings) such that no
+/// two which meet share one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coloring {
+    pub colors: Vec<usize>,
+    /// How many colours are used
+    pub count: usize,
+}
+
+/// The fewest colours a colouring could use lies somewhere between these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub lower: usize,
+    pub upper: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Greedy, most joined nodes first
+    WelshPowell,
+    /// Greedy, always taking the node whose neighbours already have the most different colours
+    DSatur,
+    /// Backtracking for the fewest colours there can be, only practical on small networks
+    Exact,
+}
+
+impl Strategy {
+    pub fn next(self) -> Self {
+        match self {
+            Strategy::WelshPowell => Strategy::DSatur,
+            Strategy::DSatur => Strategy::Exact,
+            Strategy::Exact => Strategy::WelshPowell,
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::WelshPowell => write!(f, "Welsh-Powell"),
+            Strategy::DSatur => write!(f, "DSatur"),
+            Strategy::Exact => write!(f, "Exact"),
+        }
+    }
+}
+
+impl Coloring {
+    fn new(colors: Vec<usize>) -> Self {
+        let count = colors.iter().map(|&color| color + 1).max().unwrap_or(0);
+
+        Coloring { colors, count }
+    }
+
+    /// What was given each colour, colour by colour.
+    pub fn classes(&self) -> Vec<Vec<usize>> {
+        let mut classes = vec![vec![]; self.count];
+
+        for (item, &color) in self.colors.iter().enumerate() {
+            classes[color].push(item);
+        }
+
+        classes
+    }
+}
+
+/// Nodes joined to each node, each once.
+fn neighbours(net: &Network) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![vec![]; net.len()];
+
+    for link in net.links.iter().filter(|link| link.from != link.to) {
+        neighbours[link.from].push(link.to);
+        neighbours[link.to].push(link.from);
+    }
+    for list in neighbours.iter_mut() {
+        list.sort_unstable();
+        list.dedup();
+    }
+
+    neighbours
+}
+
+/// Lowest colour not among those taken.
+fn first_free(taken: impl Iterator<Item = usize>) -> usize {
+    let mut taken: Vec<usize> = taken.collect();
+    taken.sort_unstable();
+    taken.dedup();
+
+    taken.iter().enumerate().position(|(i, &color)| i != color).unwrap_or(taken.len())
+}
+
+/// Give each node in turn the lowest colour its neighbours don't have.
+fn greedy(neighbours: &[Vec<usize>], order: impl Iterator<Item = usize>) -> Coloring {
+    let mut colors: Vec<Option<usize>> = vec![None; neighbours.len()];
+
+    for node in order {
+        colors[node] = Some(first_free(neighbours[node].iter().filter_map(|&other| colors[other])));
+    }
+
+    Coloring::new(colors.into_iter().map(|color| color.unwrap_or(0)).collect())
+}
+
+/// Greedy colouring taking the most joined nodes first (Welsh-Powell).
+pub fn welsh_powell(net: &Network) -> Coloring {
+    let neighbours = neighbours(net);
+    let mut order: Vec<usize> = (0..net.len()).collect();
+    order.sort_by_key(|&node| Reverse(neighbours[node].len()));
+
+    greedy(&neighbours, order.into_iter())
+}
+
+/// Greedy colouring taking next whichever node has the most differently coloured neighbours,
+/// the most joined breaking ties (DSatur). Usually needs fewer colours than Welsh-Powell.
+pub fn dsatur(net: &Network) -> Coloring {
+    let neighbours = neighbours(net);
+    greedy(&neighbours, dsatur_order(&neighbours).into_iter())
+}
+
+fn dsatur_order(neighbours: &[Vec<usize>]) -> Vec<usize> {
+    let n = neighbours.len();
+    let mut colors: Vec<Option<usize>> = vec![None; n];
+    // Colours seen so far among each node's neighbours
+    let mut seen: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut order = Vec::with_capacity(n);
+
+    for _ in 0..n {
+        let node = (0..n)
+            .filter(|&node| colors[node].is_none())
+            .max_by_key(|&node| (seen[node].len(), neighbours[node].len(), Reverse(node)))
+            .unwrap();
+
+        let color = first_free(seen[node].iter().copied());
+        colors[node] = Some(color);
+        order.push(node);
+
+        for &other in neighbours[node].iter() {
+            if !seen[other].contains(&color) {
+                seen[other].push(color);
+            }
+        }
+    }
+
+    order
+}
+
+/// A set of nodes all joined to each other, found greedily from each node in turn, so not
+/// always the largest there is. Every one of them needs a colour of its own.
+pub fn clique(net: &Network) -> Vec<usize> {
+    let neighbours = neighbours(net);
+    let mut best: Vec<usize> = vec![];
+
+    for start in 0..net.len() {
+        if neighbours[start].len() < best.len() {
+            continue;
+        }
+
+        let mut candidates = neighbours[start].clone();
+        candidates.sort_by_key(|&node| Reverse(neighbours[node].len()));
+
+        let mut clique = vec![start];
+        for node in candidates {
+            if clique.iter().all(|member| neighbours[node].binary_search(member).is_ok()) {
+                clique.push(node);
+            }
+        }
+
+        if clique.len() > best.len() {
+            best = clique;
+        }
+    }
+
+    best
+}
+
+/// Bounds on the chromatic number, the fewest colours the nodes can be given: at least a
+/// clique's worth, and at most what the greedy colourings manage.
+pub fn bounds(net: &Network) -> Bounds {
+    let lower = clique(net).len();
+    let upper = welsh_powell(net).count.min(dsatur(net).count);
+
+    Bounds { lower, upper }
+}
+
+/// Colouring with the fewest colours there can be, by backtracking: nodes are tried in the
+/// order DSatur takes them with each colour already used or one new one, giving up on any
+/// branch which can't beat the best found. Gives None if cancelled.
+pub fn exact(net: &Network, progress: &Progress) -> Option<Coloring> {
+    let neighbours = neighbours(net);
+    let order = dsatur_order(&neighbours);
+    let best = greedy(&neighbours, order.iter().copied());
+    let lower = clique(net).len();
+
+    if best.count <= lower {
+        return Some(best);
+    }
+
+    let mut search = Search {
+        order,
+        colors: vec![None; net.len()],
+        neighbours,
+        best,
+        lower,
+        steps: 0,
+        progress,
+    };
+    search.branch(0, 0)?;
+
+    Some(search.best)
+}
+
+struct Search<'a> {
+    neighbours: Vec<Vec<usize>>,
+    order: Vec<usize>,
+    colors: Vec<Option<usize>>,
+    best: Coloring,
+    lower: usize,
+    steps: usize,
+    progress: &'a Progress,
+}
+
+impl Search<'_> {
+    /// Try colours for the node at `depth` in the order, `used` being how many colours the
+    /// nodes before it have. Recursion only goes as deep as there are nodes, and this is only
+    /// for small networks.
+    fn branch(&mut self, depth: usize, used: usize) -> Option<()> {
+        self.steps += 1;
+        if self.steps.is_multiple_of(4096) && self.progress.is_cancelled() {
+            return None;
+        }
+
+        let node = match self.order.get(depth) {
+            Some(&node) => node,
+            None => {
+                self.best = Coloring::new(self.colors.iter().map(|color| color.unwrap_or(0)).collect());
+                return Some(());
+            }
+        };
+
+        let mut color = 0;
+        // Anything reaching as many colours as the best can't improve on it
+        while color <= used && color + 1 < self.best.count {
+            if self.neighbours[node].iter().all(|&other| self.colors[other] != Some(color)) {
+                self.colors[node] = Some(color);
+                self.branch(depth + 1, used.max(color + 1))?;
+                self.colors[node] = None;
+
+                if self.best.count <= self.lower {
+                    break;
+                }
+            }
+
+            color += 1;
+        }
+
+        Some(())
+    }
+}
+
+/// Colour the links so that no two meeting at a node share a colour, greedily with links
+/// between the busiest nodes first. Links of one colour can all be worked on at once.
+pub fn edge_coloring(net: &Network) -> Coloring {
+    let mut degree = vec![0; net.len()];
+    for link in net.links.iter() {
+        degree[link.from] += 1;
+        if link.to != link.from {
+            degree[link.to] += 1;
+        }
+    }
+
+    let mut order: Vec<usize> = (0..net.links.len()).collect();
+    order.sort_by_key(|&link| Reverse(degree[net.links[link].from] + degree[net.links[link].to]));
+
+    // Colours already on the links at each node
+    let mut used: Vec<Vec<usize>> = vec![vec![]; net.len()];
+    let mut colors = vec![0; net.links.len()];
+
+    for link in order {
+        let (from, to) = (net.links[link].from, net.links[link].to);
+        let color = first_free(used[from].iter().chain(used[to].iter()).copied());
+
+        colors[link] = color;
+        used[from].push(color);
+        used[to].push(color);
+    }
+
+    Coloring::new(colors)
+}
+
+/// Bounds on the fewest colours the links can be given: at least as many as there are links
+/// at the busiest node, and at most what the greedy colouring manages. Vizing's theorem puts
+/// it at no more than one over the lower bound when there are no parallel links.
+pub fn edge_bounds(net: &Network) -> Bounds {
+    let mut degree = vec![0; net.len()];
+    for link in net.links.iter() {
+        degree[link.from] += 1;
+        if link.to != link.from {
+            degree[link.to] += 1;
+        }
+    }
+
+    Bounds {
+        lower: degree.into_iter().max().unwrap_or(0),
+        upper: edge_coloring(net).count,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    fn proper(net: &Network, coloring: &Coloring) -> bool {
+        net.links.iter().all(|link| link.from == link.to || coloring.colors[link.from] != coloring.colors[link.to])
+    }
+
+    /// Every strategy gives a proper colouring, and the exact one finds the fewest colours
+    /// where greedy ones go over.
+    #[test]
+    pub fn test_coloring () {
+        // A five cycle needs three colours, a four cycle two
+        let odd = network(5, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 4, 1.0), (4, 0, 1.0)], true);
+        let even = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 0, 1.0), (2, 2, 1.0)], false);
+        let progress = Progress::default();
+
+        for net in [&odd, &even] {
+            for coloring in [welsh_powell(net), dsatur(net), exact(net, &progress).unwrap()] {
+                assert!(proper(net, &coloring));
+            }
+        }
+        assert_eq!(exact(&odd, &progress).unwrap().count, 3);
+        assert_eq!(exact(&even, &progress).unwrap().count, 2);
+        assert_eq!(bounds(&odd), Bounds { lower: 2, upper: 3 });
+
+        // A crown graph, which greedy colouring in the wrong order colours badly
+        let mut links = vec![];
+        for i in 0..4 {
+            for j in 0..4 {
+                if i != j {
+                    links.push((2 * i, 2 * j + 1, 1.0));
+                }
+            }
+        }
+        let crown = network(8, &links, false);
+        let coloring = exact(&crown, &progress).unwrap();
+
+        assert!(proper(&crown, &coloring));
+        assert_eq!(coloring.count, 2);
+        assert_eq!(coloring.classes().len(), 2);
+    }
+
+    /// Cliques give the lower bound, and links meeting at a node get different colours.
+    #[test]
+    pub fn test_clique_and_edges () {
+        let mut links = vec![];
+        for i in 0..4 {
+            for j in (i + 1)..4 {
+                links.push((i, j, 1.0));
+            }
+        }
+        links.push((3, 4, 1.0));
+        let net = network(5, &links, false);
+
+        let mut found = clique(&net);
+        found.sort_unstable();
+        assert_eq!(found, vec![0, 1, 2, 3]);
+        assert_eq!(exact(&net, &Progress::default()).unwrap().count, 4);
+
+        let edges = edge_coloring(&net);
+        for (i, a) in net.links.iter().enumerate() {
+            for (j, b) in net.links.iter().enumerate().skip(i + 1) {
+                if a.from == b.from || a.from == b.to || a.to == b.from || a.to == b.to {
+                    assert_ne!(edges.colors[i], edges.colors[j]);
+                }
+            }
+        }
+
+        let bounds = edge_bounds(&net);
+        assert_eq!(bounds.lower, 4);
+        assert!(bounds.upper >= 4 && bounds.upper == edges.count);
+    }
+}
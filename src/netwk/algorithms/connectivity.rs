@@ -0,0 +1,219 @@
+use super::spanning::DisjointSet;
+use super::Network;
+
+// Searches here keep their own stack rather than recursing, since a long chain of nodes in a
+// large network would otherwise run out of real stack.
+
+/// Groups of nodes joined to each other, ignoring direction, largest first.
+pub fn components(net: &Network) -> Vec<Vec<usize>> {
+    let mut sets = DisjointSet::new(net.len());
+
+    for link in net.links.iter() {
+        sets.union(link.from, link.to);
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![vec![]; net.len()];
+    for node in 0..net.len() {
+        groups[sets.find(node)].push(node);
+    }
+
+    largest_first(groups)
+}
+
+/// Groups of nodes which can each reach all the others following direction, largest first
+/// (Tarjan's algorithm). Undirected networks give the same as components.
+pub fn strongly_connected(net: &Network) -> Vec<Vec<usize>> {
+    let n = net.len();
+    let mut index: Vec<Option<usize>> = vec![None; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut groups = vec![];
+    let mut counter = 0;
+
+    for root in 0..n {
+        if index[root].is_some() {
+            continue;
+        }
+
+        index[root] = Some(counter);
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        // Each node being searched from, with how far through its links the search has got
+        let mut calls = vec![(root, 0)];
+
+        while let Some(&(node, next)) = calls.last() {
+            if let Some(&(_, other)) = net.adjacent(node).get(next) {
+                calls.last_mut().expect("just looked").1 += 1;
+
+                match index[other] {
+                    None => {
+                        index[other] = Some(counter);
+                        low[other] = counter;
+                        counter += 1;
+                        stack.push(other);
+                        on_stack[other] = true;
+                        calls.push((other, 0));
+                    }
+                    Some(other_index) if on_stack[other] => low[node] = low[node].min(other_index),
+                    Some(_) => (),
+                }
+
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent] = low[parent].min(low[node]);
+            }
+
+            if Some(low[node]) == index[node] {
+                let mut group = vec![];
+
+                loop {
+                    let member = stack.pop().expect("node is on the stack");
+                    on_stack[member] = false;
+                    group.push(member);
+
+                    if member == node {
+                        break;
+                    }
+                }
+
+                groups.push(group);
+            }
+        }
+    }
+
+    largest_first(groups)
+}
+
+fn largest_first(mut groups: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+    groups.retain(|group| !group.is_empty());
+    for group in groups.iter_mut() {
+        group.sort_unstable();
+    }
+    groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+
+    groups
+}
+
+/// Single points of failure, ignoring direction: links and nodes whose loss would split up
+/// the part of the network they are in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeakPoints {
+    pub bridges: Vec<usize>,
+    /// Articulation points
+    pub cut_nodes: Vec<usize>,
+}
+
+/// Find bridges and cut nodes by how far back each part of a depth first search can reach
+/// without using the link it was found by. Parallel links are told apart, so a doubled link
+/// is never a bridge.
+pub fn weak_points(net: &Network) -> WeakPoints {
+    let net = net.undirected();
+    let n = net.len();
+    let mut found: Vec<Option<usize>> = vec![None; n];
+    let mut low = vec![0; n];
+    let mut cut = vec![false; n];
+    let mut bridges = vec![];
+    let mut counter = 0;
+
+    for root in 0..n {
+        if found[root].is_some() {
+            continue;
+        }
+
+        found[root] = Some(counter);
+        low[root] = counter;
+        counter += 1;
+
+        let mut children = 0;
+        // Node, link it was reached by, and how far through its links the search has got
+        let mut calls: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+
+        while let Some(&(node, via, next)) = calls.last() {
+            if let Some(&(link, other)) = net.adjacent(node).get(next) {
+                calls.last_mut().expect("just looked").2 += 1;
+
+                if Some(link) == via {
+                    continue;
+                }
+
+                match found[other] {
+                    None => {
+                        found[other] = Some(counter);
+                        low[other] = counter;
+                        counter += 1;
+                        calls.push((other, Some(link), 0));
+
+                        if node == root {
+                            children += 1;
+                        }
+                    }
+                    Some(other_found) => low[node] = low[node].min(other_found),
+                }
+
+                continue;
+            }
+
+            calls.pop();
+
+            if let (Some(&(parent, _, _)), Some(via)) = (calls.last(), via) {
+                low[parent] = low[parent].min(low[node]);
+                let parent_found = found[parent].expect("parents were found first");
+
+                // Nothing under this node reaches back above its parent without this link
+                if low[node] > parent_found {
+                    bridges.push(via);
+                }
+                if parent != root && low[node] >= parent_found {
+                    cut[parent] = true;
+                }
+            }
+        }
+
+        // The root is only a cut node if the search had to leave it more than once
+        cut[root] = children > 1;
+    }
+
+    bridges.sort_unstable();
+
+    WeakPoints { bridges, cut_nodes: (0..n).filter(|&node| cut[node]).collect() }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// Direction only matters for strong components.
+    #[test]
+    pub fn test_components () {
+        let net = network(6, &[(0, 1, 1.0), (1, 2, 1.0), (2, 0, 1.0), (2, 3, 1.0), (4, 5, 1.0)], true);
+
+        assert_eq!(components(&net), vec![vec![0, 1, 2, 3], vec![4, 5]]);
+        assert_eq!(strongly_connected(&net), vec![vec![0, 1, 2], vec![3], vec![4], vec![5]]);
+        assert_eq!(strongly_connected(&net.undirected()), components(&net));
+    }
+
+    /// Two triangles joined by a single link, which is a bridge until it is doubled up. Its
+    /// ends stay cut nodes either way.
+    #[test]
+    pub fn test_weak_points () {
+        let mut links = vec![(0, 1, 1.0), (1, 2, 1.0), (2, 0, 1.0), (3, 4, 1.0), (4, 5, 1.0), (5, 3, 1.0), (2, 3, 1.0)];
+        let net = network(7, &links, false);
+
+        assert_eq!(weak_points(&net), WeakPoints { bridges: vec![6], cut_nodes: vec![2, 3] });
+
+        links.push((3, 2, 1.0));
+        links.push((5, 6, 1.0));
+        let net = network(7, &links, true);
+
+        assert_eq!(weak_points(&net), WeakPoints { bridges: vec![8], cut_nodes: vec![2, 3, 5] });
+    }
+}
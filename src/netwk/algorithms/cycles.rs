@@ -0,0 +1,430 @@
+use std::collections::VecDeque;
+
+use super::Network;
+use crate::netwk::job::Progress;
+
+// Topological order and simple cycles follow links the way they were drawn, so they only mean
+// something for directed networks. Finding a cycle works on either.
+
+/// A closed route: each link goes from the node at the same place in `nodes` to the next one,
+/// the last link leading back to the first node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub nodes: Vec<usize>,
+    pub links: Vec<usize>,
+}
+
+/// Links leaving each node the way they were drawn.
+fn outgoing(net: &Network) -> Vec<Vec<usize>> {
+    let mut outgoing = vec![vec![]; net.len()];
+
+    for (i, link) in net.links.iter().enumerate() {
+        outgoing[link.from].push(i);
+    }
+
+    outgoing
+}
+
+/// Links arriving at each node the way they were drawn.
+fn arriving(net: &Network) -> Vec<Vec<usize>> {
+    let mut arriving = vec![vec![]; net.len()];
+
+    for (i, link) in net.links.iter().enumerate() {
+        arriving[link.to].push(i);
+    }
+
+    arriving
+}
+
+/// Any one cycle, by depth first search: the first link found leading back to a node still
+/// being searched from closes it. In undirected networks a link isn't followed straight back.
+pub fn find_cycle(net: &Network) -> Option<Cycle> {
+    // 0 not reached yet, 1 on the current search path, 2 done with
+    let mut state = vec![0u8; net.len()];
+    // Link each node was reached by, and the node it was reached from
+    let mut via: Vec<Option<(usize, usize)>> = vec![None; net.len()];
+
+    for root in 0..net.len() {
+        if state[root] != 0 {
+            continue;
+        }
+
+        state[root] = 1;
+        let mut stack = vec![(root, 0)];
+
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+
+            match net.adjacent(node).get(*next) {
+                Some(&(link, other)) => {
+                    *next += 1;
+
+                    if via[node].is_some_and(|(arrived, _)| arrived == link) {
+                        continue;
+                    }
+
+                    match state[other] {
+                        0 => {
+                            state[other] = 1;
+                            via[other] = Some((link, node));
+                            stack.push((other, 0));
+                        }
+                        1 => return Some(close(&via, other, node, link)),
+                        _ => {}
+                    }
+                }
+                None => {
+                    state[node] = 2;
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// The cycle made by following `link` from `end` back to `start`, which it was reached from.
+fn close(via: &[Option<(usize, usize)>], start: usize, end: usize, link: usize) -> Cycle {
+    let mut nodes = vec![end];
+    let mut links = vec![link];
+    let mut at = end;
+
+    while at != start {
+        let (link, from) = via[at].expect("searched from the start of the cycle");
+        nodes.push(from);
+        links.push(link);
+        at = from;
+    }
+
+    // Walked backwards, so the other way round
+    nodes.reverse();
+    links.reverse();
+
+    Cycle { nodes, links }
+}
+
+/// Nodes in an order where every link goes forwards, such as tasks ahead of whatever depends on
+/// them (Kahn's algorithm). Nodes free to go first are taken lowest numbered first. If there is
+/// no such order, a cycle showing why is given instead.
+pub fn topological_order(net: &Network) -> Result<Vec<usize>, Cycle> {
+    let outgoing = outgoing(net);
+    let mut incoming = vec![0; net.len()];
+    for link in net.links.iter() {
+        incoming[link.to] += 1;
+    }
+
+    let mut ready: VecDeque<usize> = (0..net.len()).filter(|&node| incoming[node] == 0).collect();
+    let mut placed = vec![false; net.len()];
+    let mut order = Vec::with_capacity(net.len());
+
+    while let Some(node) = ready.pop_front() {
+        placed[node] = true;
+        order.push(node);
+
+        for &link in outgoing[node].iter() {
+            let to = net.links[link].to;
+            incoming[to] -= 1;
+            if incoming[to] == 0 {
+                ready.push_back(to);
+            }
+        }
+    }
+
+    if order.len() == net.len() {
+        return Ok(order);
+    }
+
+    // Everything left has a link in from something else left, so walking those links backwards
+    // has to come round to a node already passed
+    let arriving = arriving(net);
+    let mut walked: Vec<Option<usize>> = vec![None; net.len()];
+    let mut nodes = vec![];
+    let mut links = vec![];
+    let mut at = (0..net.len()).find(|&node| !placed[node]).expect("some node is left");
+
+    while walked[at].is_none() {
+        walked[at] = Some(nodes.len());
+        nodes.push(at);
+
+        let link = *arriving[at].iter().find(|&&link| !placed[net.links[link].from]).expect("left by a link");
+        links.push(link);
+        at = net.links[link].from;
+    }
+
+    let start = walked[at].unwrap();
+    let mut cycle = Cycle { nodes: nodes.split_off(start), links: links.split_off(start) };
+    cycle.nodes[1..].reverse();
+    cycle.links.reverse();
+
+    Err(cycle)
+}
+
+/// Every simple cycle, one that doesn't pass through any node twice, by Johnson's algorithm.
+/// There can be exponentially many, so at most `limit` are found. Gives None if cancelled.
+pub fn simple_cycles(net: &Network, limit: usize, progress: &Progress) -> Option<Vec<Cycle>> {
+    let mut search = Johnson {
+        outgoing: outgoing(net),
+        arriving: arriving(net),
+        net,
+        start: 0,
+        within: vec![false; net.len()],
+        blocked: vec![false; net.len()],
+        blocking: vec![vec![]; net.len()],
+        nodes: vec![],
+        links: vec![],
+        cycles: vec![],
+        limit,
+        steps: 0,
+        progress,
+        cancelled: false,
+    };
+
+    for start in 0..net.len() {
+        if search.cycles.len() >= limit {
+            break;
+        }
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(start as f32 / net.len() as f32, "Finding cycles");
+
+        // No cycle can come back to the start without a link in from no lower a node
+        if !search.arriving[start].iter().any(|&link| net.links[link].from >= start) {
+            continue;
+        }
+
+        // Cycles through the start among the nodes numbered after it, each found once
+        search.start = start;
+        search.within = search.strongly_joined(start);
+        for node in 0..net.len() {
+            search.blocked[node] = false;
+            search.blocking[node].clear();
+        }
+
+        search.circuit();
+        if search.cancelled {
+            return None;
+        }
+    }
+
+    Some(search.cycles)
+}
+
+struct Johnson<'a> {
+    net: &'a Network,
+    outgoing: Vec<Vec<usize>>,
+    arriving: Vec<Vec<usize>>,
+    start: usize,
+    /// Nodes in the same strongly connected component as the start, numbered no lower
+    within: Vec<bool>,
+    blocked: Vec<bool>,
+    /// Nodes to unblock once each node is
+    blocking: Vec<Vec<usize>>,
+    nodes: Vec<usize>,
+    links: Vec<usize>,
+    cycles: Vec<Cycle>,
+    limit: usize,
+    steps: usize,
+    progress: &'a Progress,
+    cancelled: bool,
+}
+
+impl Johnson<'_> {
+    /// Nodes numbered no lower than `start` which can both be reached from it and reach it
+    /// back, without going through anything lower.
+    fn strongly_joined(&self, start: usize) -> Vec<bool> {
+        let forwards = self.reach(start, &self.outgoing, |link| self.net.links[link].to, &vec![true; self.net.len()]);
+
+        self.reach(start, &self.arriving, |link| self.net.links[link].from, &forwards)
+    }
+
+    /// Nodes reachable from `start` over the links given for each node, staying among those
+    /// allowed and numbered no lower than it.
+    fn reach(&self, start: usize, links: &[Vec<usize>], far_end: impl Fn(usize) -> usize, allowed: &[bool]) -> Vec<bool> {
+        let mut reached = vec![false; self.net.len()];
+        let mut pending = vec![start];
+        reached[start] = true;
+
+        while let Some(node) = pending.pop() {
+            for &link in links[node].iter() {
+                let other = far_end(link);
+
+                if other > start && allowed[other] && !reached[other] {
+                    reached[other] = true;
+                    pending.push(other);
+                }
+            }
+        }
+
+        reached
+    }
+
+    /// Follow every path from the start which doesn't go through a node twice, noting the
+    /// cycles made by those leading back to it. The path is kept on a stack of its own rather
+    /// than by recursing, as it can be as long as the network is large.
+    fn circuit(&mut self) {
+        // Node at each step of the path, the next of its links to try, and whether any cycle
+        // has been found going on from it
+        let mut path = vec![(self.start, 0, false)];
+        self.nodes.push(self.start);
+        self.blocked[self.start] = true;
+
+        while let Some((node, next, found)) = path.last_mut() {
+            self.steps += 1;
+            if self.steps.is_multiple_of(4096) && self.progress.is_cancelled() {
+                self.cancelled = true;
+            }
+            if self.cancelled || self.cycles.len() >= self.limit {
+                return;
+            }
+
+            let node = *node;
+
+            match self.outgoing[node].get(*next) {
+                Some(&link) => {
+                    *next += 1;
+                    let to = self.net.links[link].to;
+
+                    if !self.within[to] {
+                        continue;
+                    }
+
+                    if to == self.start {
+                        *found = true;
+
+                        let mut links = self.links.clone();
+                        links.push(link);
+                        self.cycles.push(Cycle { nodes: self.nodes.clone(), links });
+                    } else if !self.blocked[to] {
+                        path.push((to, 0, false));
+                        self.nodes.push(to);
+                        self.links.push(link);
+                        self.blocked[to] = true;
+                    }
+                }
+                None => {
+                    let found = *found;
+
+                    if found {
+                        self.unblock(node);
+                    } else {
+                        for &link in self.outgoing[node].iter() {
+                            let to = self.net.links[link].to;
+
+                            if self.within[to] && !self.blocking[to].contains(&node) {
+                                self.blocking[to].push(node);
+                            }
+                        }
+                    }
+
+                    path.pop();
+                    self.nodes.pop();
+                    if let Some((_, _, before)) = path.last_mut() {
+                        self.links.pop();
+                        *before |= found;
+                    }
+                }
+            }
+        }
+    }
+
+    fn unblock(&mut self, node: usize) {
+        let mut pending = vec![node];
+
+        while let Some(node) = pending.pop() {
+            if self.blocked[node] {
+                self.blocked[node] = false;
+                pending.append(&mut self.blocking[node]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    fn is_cycle(net: &Network, cycle: &Cycle) -> bool {
+        let n = cycle.nodes.len();
+
+        n == cycle.links.len()
+            && (0..n).all(|i| {
+                let link = net.links[cycle.links[i]];
+                let (from, to) = (cycle.nodes[i], cycle.nodes[(i + 1) % n]);
+                (link.from == from && link.to == to) || (!net.directed && link.from == to && link.to == from)
+            })
+    }
+
+    /// Cycles are found the right way round, and trees, however drawn, have none.
+    #[test]
+    pub fn test_find_cycle () {
+        let net = network(5, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 1, 1.0), (3, 4, 1.0)], true);
+        let cycle = find_cycle(&net).unwrap();
+        assert!(is_cycle(&net, &cycle));
+        assert_eq!(cycle.nodes.len(), 3);
+
+        let tree = network(4, &[(0, 1, 1.0), (2, 1, 1.0), (1, 3, 1.0)], false);
+        assert_eq!(find_cycle(&tree), None);
+        // Both ways along one link isn't a cycle, but two parallel links are
+        let pair = network(2, &[(0, 1, 1.0), (1, 0, 1.0)], false);
+        assert!(is_cycle(&pair, &find_cycle(&pair).unwrap()));
+
+        let looped = network(2, &[(0, 1, 1.0), (1, 1, 1.0)], true);
+        assert_eq!(find_cycle(&looped).unwrap(), Cycle { nodes: vec![1], links: vec![1] });
+    }
+
+    /// Every link goes forwards in the order, and a cycle stops there being one.
+    #[test]
+    pub fn test_topological_order () {
+        let net = network(5, &[(3, 1, 1.0), (1, 0, 1.0), (3, 4, 1.0), (4, 0, 1.0), (2, 4, 1.0)], true);
+        let order = topological_order(&net).unwrap();
+        assert_eq!(order, vec![2, 3, 1, 4, 0]);
+
+        let net = network(5, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 1, 1.0), (4, 0, 1.0)], true);
+        let cycle = topological_order(&net).unwrap_err();
+        assert!(is_cycle(&net, &cycle));
+        assert_eq!(cycle.nodes.len(), 3);
+    }
+
+    /// Johnson's algorithm finds each simple cycle once, parallel links making cycles of
+    /// their own, and stops at the limit.
+    #[test]
+    pub fn test_simple_cycles () {
+        let progress = Progress::default();
+
+        // Every ordering of three nodes joined both ways: three pairs and two triangles
+        let mut links = vec![];
+        for i in 0..3 {
+            for j in 0..3 {
+                if i != j {
+                    links.push((i, j, 1.0));
+                }
+            }
+        }
+        let net = network(3, &links, true);
+        let cycles = simple_cycles(&net, 100, &progress).unwrap();
+
+        assert_eq!(cycles.len(), 5);
+        assert!(cycles.iter().all(|cycle| is_cycle(&net, cycle)));
+        assert_eq!(cycles.iter().filter(|cycle| cycle.nodes.len() == 3).count(), 2);
+        assert_eq!(simple_cycles(&net, 2, &progress).unwrap().len(), 2);
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, 1.0), (1, 2, 1.0), (2, 0, 1.0), (2, 2, 1.0)], true);
+        assert_eq!(simple_cycles(&net, 100, &progress).unwrap().len(), 3);
+        assert_eq!(simple_cycles(&network(3, &[(0, 1, 1.0), (1, 2, 1.0)], true), 100, &progress).unwrap(), vec![]);
+    }
+
+    /// A cycle through every node of a large network doesn't run out of stack.
+    #[test]
+    pub fn test_long_cycle () {
+        let n = 20_000;
+        let ring: Vec<(usize, usize, f32)> = (0..n).map(|i| (i, (i + 1) % n, 1.0)).collect();
+        let cycles = simple_cycles(&network(n, &ring, true), 10, &Progress::default()).unwrap();
+
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), n);
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+
+use super::Network;
+
+/// Residual capacities under this count as used up, so rounding can't keep a search going.
+const EPSILON: f32 = 1e-6;
+
+/// Most that can be sent from a source to a sink, with link weights as capacities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flow {
+    pub value: f32,
+    /// Sent along each link, negative where it runs against the way an undirected link was drawn
+    pub flows: Vec<f32>,
+    /// Links of a minimum cut: full links separating the nodes the source can still reach
+    pub cut: Vec<usize>,
+    pub source_side: Vec<bool>,
+}
+
+/// Capacities left on each arc of the flow network. Arcs come in pairs, 2i and 2i + 1 for
+/// link i, each being the other's way back. An undirected link can carry its capacity either
+/// way, so both its arcs start with it; a directed link's way back starts empty.
+struct Residual {
+    capacity: Vec<f32>,
+    residual: Vec<f32>,
+    /// Arcs leaving each node, and the node each goes to
+    arcs: Vec<Vec<(usize, usize)>>,
+    /// Node each arc leaves from
+    tails: Vec<usize>,
+}
+
+impl Residual {
+    fn new(net: &Network) -> Self {
+        let mut arcs = vec![vec![]; net.len()];
+        let mut capacity = Vec::with_capacity(2 * net.links.len());
+        let mut tails = Vec::with_capacity(2 * net.links.len());
+
+        for (i, link) in net.links.iter().enumerate() {
+            // Negative capacity makes no sense, so counts as none
+            let weight = link.weight.max(0.0);
+
+            capacity.push(weight);
+            capacity.push(if net.directed { 0.0 } else { weight });
+            arcs[link.from].push((2 * i, link.to));
+            arcs[link.to].push((2 * i + 1, link.from));
+            tails.extend([link.from, link.to]);
+        }
+
+        Residual { residual: capacity.clone(), capacity, arcs, tails }
+    }
+
+    /// Levels by number of hops from the source over arcs with room left, None if unreached.
+    fn levels(&self, source: usize) -> Vec<Option<usize>> {
+        let mut levels = vec![None; self.arcs.len()];
+        let mut queue = VecDeque::from([source]);
+        levels[source] = Some(0);
+
+        while let Some(node) = queue.pop_front() {
+            for &(arc, next) in self.arcs[node].iter() {
+                if levels[next].is_none() && self.residual[arc] > EPSILON {
+                    levels[next] = levels[node].map(|level| level + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        levels
+    }
+
+    /// Push flow along paths which go one level further each hop until none are left,
+    /// returning how much was sent.
+    fn blocking_flow(&mut self, source: usize, sink: usize, levels: &[Option<usize>]) -> f32 {
+        let mut sent = 0.0;
+        // How far through its arcs each node has got; arcs before this lead nowhere useful
+        let mut next = vec![0; self.arcs.len()];
+        let mut path: Vec<usize> = vec![];
+        let mut node = source;
+
+        loop {
+            if node == sink {
+                let amount = path.iter().map(|&arc| self.residual[arc]).fold(f32::INFINITY, f32::min);
+
+                for &arc in path.iter() {
+                    self.residual[arc] -= amount;
+                    self.residual[arc ^ 1] += amount;
+                }
+                sent += amount;
+
+                // Start again from the source; the full arcs are skipped from now on
+                path.clear();
+                node = source;
+                continue;
+            }
+
+            let step = self.arcs[node][next[node]..].iter().position(|&(arc, to)| {
+                self.residual[arc] > EPSILON && levels[to].is_some() && levels[to] == levels[node].map(|level| level + 1)
+            });
+
+            match step {
+                Some(offset) => {
+                    next[node] += offset;
+                    let (arc, to) = self.arcs[node][next[node]];
+                    path.push(arc);
+                    node = to;
+                }
+                None => {
+                    // Dead end, so back up and make sure nothing comes this way again
+                    next[node] = self.arcs[node].len();
+
+                    let arc = match path.pop() {
+                        Some(arc) => arc,
+                        None => return sent,
+                    };
+                    node = self.tails[arc];
+                    next[node] += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Maximum flow from source to sink by Dinic's algorithm, and the minimum cut which limits it.
+pub fn max_flow(net: &Network, source: usize, sink: usize) -> Flow {
+    let mut residual = Residual::new(net);
+    let mut value = 0.0;
+
+    if source != sink {
+        loop {
+            let levels = residual.levels(source);
+
+            if levels[sink].is_none() {
+                break;
+            }
+
+            value += residual.blocking_flow(source, sink, &levels);
+        }
+    }
+
+    let flows = (0..net.links.len()).map(|i| residual.capacity[2 * i] - residual.residual[2 * i]).collect();
+
+    // Whatever the source can still reach is its side of the cut
+    let source_side: Vec<bool> = residual.levels(source).iter().map(Option::is_some).collect();
+    let cut = (0..net.links.len())
+        .filter(|&i| {
+            let link = net.links[i];
+            let (from, to) = (source_side[link.from], source_side[link.to]);
+
+            if net.directed { from && !to } else { from != to }
+        })
+        .collect();
+
+    Flow { value, flows, cut, source_side }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// The textbook example, where the cut and the flow come out the same.
+    #[test]
+    pub fn test_max_flow () {
+        let net = network(
+            6,
+            &[
+                (0, 1, 16.0),
+                (0, 2, 13.0),
+                (1, 3, 12.0),
+                (2, 1, 4.0),
+                (2, 4, 14.0),
+                (3, 2, 9.0),
+                (3, 5, 20.0),
+                (4, 3, 7.0),
+                (4, 5, 4.0),
+            ],
+            true,
+        );
+
+        let flow = max_flow(&net, 0, 5);
+        let cut: f32 = flow.cut.iter().map(|&link| net.links[link].weight).sum();
+
+        assert_eq!(flow.value, 23.0);
+        assert_eq!(cut, 23.0);
+
+        // Whatever goes into a node in the middle comes out again
+        for node in 1..5 {
+            let balance: f32 = net
+                .links
+                .iter()
+                .zip(flow.flows.iter())
+                .map(|(link, flow)| if link.to == node { *flow } else if link.from == node { -flow } else { 0.0 })
+                .sum();
+            assert_eq!(balance, 0.0);
+        }
+
+        assert_eq!(max_flow(&net, 5, 0).value, 0.0);
+    }
+
+    /// Undirected links carry flow against the way they were drawn when they need to.
+    #[test]
+    pub fn test_undirected_flow () {
+        let net = network(4, &[(1, 0, 3.0), (1, 3, 2.0), (2, 0, 2.0), (3, 2, 5.0), (2, 1, 1.0)], false);
+        let flow = max_flow(&net, 0, 3);
+
+        assert_eq!(flow.value, 5.0);
+        assert_eq!(flow.flows[0], -3.0);
+        assert_eq!(flow.cut, vec![0, 2]);
+    }
+}
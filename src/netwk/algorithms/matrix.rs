@@ -0,0 +1,227 @@
+use super::multipath;
+use super::path::{self, NegativeCycle};
+use super::Network;
+use crate::netwk::job::Progress;
+
+/// Shortest distances between every pair of nodes: `cost[from][to]`, infinity where there is no path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub cost: Vec<Vec<f32>>,
+}
+
+/// Shortest distances between all pairs, or the negative cycle which rules them out. Dense
+/// graphs, and any with negative weights, use Floyd-Warshall; sparse ones run Dijkstra from
+/// every node, which is quicker when there are far fewer links than pairs of nodes. Returns
+/// None if cancelled part way.
+pub fn all_pairs(net: &Network, progress: &Progress) -> Option<Result<Matrix, NegativeCycle>> {
+    if !net.has_negative() && !net.is_dense() {
+        return repeated_dijkstra(net, progress).map(Ok);
+    }
+
+    let matrix = floyd_warshall(net, progress)?;
+
+    // A node which can get back to itself for less than nothing may sit on a negative cycle,
+    // but a cycle summing to zero can come out a hair below it, so Bellman-Ford has the last word
+    let below_zero = |node: usize| matrix.cost[node][node] < 0.0 && !multipath::same_cost(matrix.cost[node][node], 0.0);
+
+    match (0..net.len()).find(|&node| below_zero(node)) {
+        Some(node) => match path::bellman_ford(net, node) {
+            Err(cycle) => Some(Err(cycle)),
+            Ok(_) => Some(Ok(matrix)),
+        },
+        None => Some(Ok(matrix)),
+    }
+}
+
+pub fn floyd_warshall(net: &Network, progress: &Progress) -> Option<Matrix> {
+    let n = net.len();
+    let mut cost = vec![vec![f32::INFINITY; n]; n];
+
+    for (node, row) in cost.iter_mut().enumerate() {
+        row[node] = 0.0;
+    }
+
+    for link in net.links.iter() {
+        cost[link.from][link.to] = cost[link.from][link.to].min(link.weight);
+
+        if !net.directed {
+            cost[link.to][link.from] = cost[link.to][link.from].min(link.weight);
+        }
+    }
+
+    for via in 0..n {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(via as f32 / n as f32, "Measuring distances");
+
+        let through = cost[via].clone();
+
+        for row in cost.iter_mut() {
+            let to_via = row[via];
+
+            if !to_via.is_finite() {
+                continue;
+            }
+
+            for (to, cell) in row.iter_mut().enumerate() {
+                let cost = to_via + through[to];
+
+                if cost < *cell {
+                    *cell = cost;
+                }
+            }
+        }
+    }
+
+    Some(Matrix { cost })
+}
+
+/// All pairs by running Dijkstra from each node in turn. Weights must not be negative.
+pub fn repeated_dijkstra(net: &Network, progress: &Progress) -> Option<Matrix> {
+    let mut cost = Vec::with_capacity(net.len());
+
+    for source in 0..net.len() {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(source as f32 / net.len() as f32, "Measuring distances");
+
+        cost.push(path::dijkstra(net, source).cost);
+    }
+
+    Some(Matrix { cost })
+}
+
+/// Eccentricity of every node without keeping the whole matrix, one search at a time, for
+/// networks too big for n by n distances. Returns None if cancelled part way.
+pub fn eccentricities(net: &Network, progress: &Progress) -> Option<Result<Vec<f32>, NegativeCycle>> {
+    let negative = net.has_negative();
+    let mut eccentricity = Vec::with_capacity(net.len());
+
+    for source in 0..net.len() {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(source as f32 / net.len() as f32, "Measuring distances");
+
+        let tree = if negative {
+            match path::bellman_ford(net, source) {
+                Ok(tree) => tree,
+                Err(cycle) => return Some(Err(cycle)),
+            }
+        } else {
+            path::dijkstra(net, source)
+        };
+
+        eccentricity.push(furthest(&tree.cost));
+    }
+
+    Some(Ok(eccentricity))
+}
+
+fn furthest(row: &[f32]) -> f32 {
+    row.iter().copied().fold(0.0, f32::max)
+}
+
+/// Largest eccentricity, the longest shortest path.
+pub fn diameter(eccentricity: &[f32]) -> f32 {
+    eccentricity.iter().copied().fold(0.0, f32::max)
+}
+
+/// Smallest eccentricity.
+pub fn radius(eccentricity: &[f32]) -> f32 {
+    eccentricity.iter().copied().fold(f32::INFINITY, f32::min)
+}
+
+/// Nodes with the smallest eccentricity, the ones closest to everything else.
+pub fn center(eccentricity: &[f32]) -> Vec<usize> {
+    let radius = radius(eccentricity);
+
+    eccentricity.iter().enumerate().filter(|&(_, &e)| e == radius).map(|(node, _)| node).collect()
+}
+
+impl Matrix {
+    /// Furthest any other node is from each node; infinite if some node can't be reached.
+    pub fn eccentricity(&self) -> Vec<f32> {
+        self.cost.iter().map(|row| furthest(row)).collect()
+    }
+
+    pub fn diameter(&self) -> f32 {
+        diameter(&self.eccentricity())
+    }
+
+    pub fn radius(&self) -> f32 {
+        radius(&self.eccentricity())
+    }
+
+    pub fn center(&self) -> Vec<usize> {
+        center(&self.eccentricity())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// Both ways of working out the matrix agree, and pick Floyd-Warshall for negative weights.
+    #[test]
+    pub fn test_all_pairs () {
+        let net = network(5, &[(0, 1, 2.0), (1, 2, 1.0), (0, 2, 4.0), (2, 3, 1.5), (4, 0, 1.0)], true);
+        let progress = Progress::default();
+
+        let floyd = floyd_warshall(&net, &progress).unwrap();
+        assert_eq!(floyd, repeated_dijkstra(&net, &progress).unwrap());
+        assert_eq!(floyd.cost[0], vec![0.0, 2.0, 3.0, 4.5, f32::INFINITY]);
+        assert_eq!(floyd.cost[4][3], 5.5);
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, -2.0), (2, 0, 3.0)], true);
+        assert_eq!(all_pairs(&net, &progress).unwrap().unwrap().cost[0][2], -1.0);
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, -2.0), (2, 0, 0.5)], true);
+        assert_eq!(all_pairs(&net, &progress).unwrap().unwrap_err().cost, -0.5);
+    }
+
+    /// A cycle whose weights sum to zero only through rounding isn't taken for a negative one.
+    #[test]
+    pub fn test_all_pairs_zero_cycle () {
+        let net = network(4, &[(0, 1, 0.1), (1, 2, 0.1), (2, 3, 0.1), (3, 0, -(0.1 + 0.1 + 0.1))], true);
+        let progress = Progress::default();
+
+        for row in all_pairs(&net, &progress).unwrap().unwrap().cost {
+            assert!(row.iter().all(|cost| cost.is_finite()));
+        }
+        assert!(eccentricities(&net, &progress).unwrap().is_ok());
+    }
+
+    /// A path of three has the middle as its center.
+    #[test]
+    pub fn test_eccentricity () {
+        let net = network(3, &[(0, 1, 1.0), (1, 2, 1.0)], false);
+        let matrix = all_pairs(&net, &Progress::default()).unwrap().unwrap();
+
+        assert_eq!(matrix.eccentricity(), vec![2.0, 1.0, 2.0]);
+        assert_eq!(matrix.diameter(), 2.0);
+        assert_eq!(matrix.radius(), 1.0);
+        assert_eq!(matrix.center(), vec![1]);
+    }
+
+    /// Going one search at a time gives the same eccentricities as the matrix.
+    #[test]
+    pub fn test_eccentricities () {
+        let progress = Progress::default();
+
+        let net = network(5, &[(0, 1, 2.0), (1, 2, 1.0), (0, 2, 4.0), (2, 3, 1.5), (4, 0, 1.0)], true);
+        let matrix = all_pairs(&net, &progress).unwrap().unwrap();
+        assert_eq!(eccentricities(&net, &progress).unwrap().unwrap(), matrix.eccentricity());
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, -2.0), (2, 0, 3.0)], true);
+        let matrix = all_pairs(&net, &progress).unwrap().unwrap();
+        assert_eq!(eccentricities(&net, &progress).unwrap().unwrap(), matrix.eccentricity());
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, -2.0), (2, 0, 0.5)], true);
+        assert!(eccentricities(&net, &progress).unwrap().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use super::path::{self, NegativeCycle};
+use super::{Link, Network};
+
+/// Residual capacities under this count as used up.
+const EPSILON: f32 = 1e-6;
+
+/// Demand routed from a source to a sink as cheaply as the capacities allow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostFlow {
+    /// As much of the demand as fits
+    pub sent: f32,
+    pub cost: f32,
+    /// Sent along each link, negative where it runs against the way an undirected link was drawn
+    pub flows: Vec<f32>,
+}
+
+/// One way along a link in the residual network. Arcs come in pairs, 2i and 2i + 1, each
+/// being the other's way back, which costs as much less as going forward costs more.
+#[derive(Debug, Clone, Copy)]
+struct Arc {
+    from: usize,
+    to: usize,
+    residual: f32,
+    cost: f32,
+}
+
+/// Send up to `demand` from source to sink at the least total cost, link weights being the
+/// cost per unit and `capacities` how much each link carries. Uses successive shortest paths:
+/// keep sending along the cheapest path with room left, which may undo earlier choices by
+/// going back along arcs already used. Costs can be negative, but not if they make a cycle
+/// which could be gone round for ever, and that cycle is given back instead.
+pub fn min_cost_flow(net: &Network, capacities: &[f32], source: usize, sink: usize, demand: f32) -> Result<CostFlow, NegativeCycle> {
+    // Only links which can carry anything are any use, as are only cycles made from them
+    let usable: Vec<Link> = net.links.iter().zip(capacities).filter(|(_, &capacity)| capacity > EPSILON).map(|(link, _)| *link).collect();
+    let usable = Network::from_links(net.names.clone(), usable, net.directed);
+    path::bellman_ford(&usable, source)?;
+
+    // Undirected links are a pair of directed ones, one each way
+    let mut arcs = vec![];
+    let mut forward: Vec<(usize, Option<usize>)> = vec![];
+
+    for (link, &capacity) in net.links.iter().zip(capacities) {
+        let capacity = capacity.max(0.0);
+        let mut add = |from: usize, to: usize| {
+            arcs.push(Arc { from, to, residual: capacity, cost: link.weight });
+            arcs.push(Arc { from: to, to: from, residual: 0.0, cost: -link.weight });
+            arcs.len() - 2
+        };
+
+        let ahead = add(link.from, link.to);
+        let back = (!net.directed).then(|| add(link.to, link.from));
+        forward.push((ahead, back));
+    }
+
+    let mut leaving = vec![vec![]; net.len()];
+    for (i, arc) in arcs.iter().enumerate() {
+        leaving[arc.from].push(i);
+    }
+
+    let (mut sent, mut cost) = (0.0, 0.0);
+
+    while source != sink && demand - sent > EPSILON {
+        let (price, via) = cheapest(net.len(), &arcs, &leaving, source);
+
+        if via[sink].is_none() {
+            break;
+        }
+
+        let mut path = vec![];
+        let mut node = sink;
+        while let Some(arc) = via[node] {
+            path.push(arc);
+            node = arcs[arc].from;
+        }
+
+        let amount = path.iter().map(|&arc| arcs[arc].residual).fold(demand - sent, f32::min);
+
+        for &arc in path.iter() {
+            arcs[arc].residual -= amount;
+            arcs[arc ^ 1].residual += amount;
+        }
+
+        sent += amount;
+        cost += amount * price[sink];
+    }
+
+    let used = |arc: usize| arcs[arc ^ 1].residual;
+    let flows = forward.iter().map(|&(ahead, back)| used(ahead) - back.map_or(0.0, used)).collect();
+
+    Ok(CostFlow { sent, cost, flows })
+}
+
+/// Cheapest way to every node over arcs with room left, by Bellman-Ford since the way back
+/// along a used arc costs less than nothing. Gives the price of getting to each node and
+/// the arc it was got to by.
+fn cheapest(nodes: usize, arcs: &[Arc], leaving: &[Vec<usize>], source: usize) -> (Vec<f32>, Vec<Option<usize>>) {
+    let mut price = vec![f32::INFINITY; nodes];
+    let mut via = vec![None; nodes];
+    price[source] = 0.0;
+
+    for _ in 0..nodes {
+        let mut changed = false;
+
+        for node in 0..nodes {
+            if !price[node].is_finite() {
+                continue;
+            }
+
+            for &i in leaving[node].iter() {
+                let arc = arcs[i];
+
+                if arc.residual > EPSILON && price[node] + arc.cost < price[arc.to] - EPSILON {
+                    price[arc.to] = price[node] + arc.cost;
+                    via[arc.to] = Some(i);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    (price, via)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// Fills the cheap route first, then overflows into the dearer one, then runs out of room.
+    #[test]
+    pub fn test_min_cost_flow () {
+        let net = network(4, &[(0, 1, 1.0), (1, 3, 1.0), (0, 2, 2.0), (2, 3, 2.0)], true);
+        let capacities = [2.0, 2.0, 3.0, 3.0];
+
+        let flow = min_cost_flow(&net, &capacities, 0, 3, 4.0).unwrap();
+        assert_eq!((flow.sent, flow.cost), (4.0, 12.0));
+        assert_eq!(flow.flows, vec![2.0, 2.0, 2.0, 2.0]);
+
+        let flow = min_cost_flow(&net, &capacities, 0, 3, 10.0).unwrap();
+        assert_eq!((flow.sent, flow.cost), (5.0, 16.0));
+    }
+
+    /// Sending the first lot the cheap way can block the best overall, which sending back
+    /// along the way it came sorts out.
+    #[test]
+    pub fn test_min_cost_reroute () {
+        // Cheapest single path is 0-1-2-3, but two units need 0-1-3 and 0-2-3
+        let net = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (1, 3, 5.0), (0, 2, 5.0)], false);
+        let flow = min_cost_flow(&net, &[1.0; 5], 0, 3, 2.0).unwrap();
+
+        assert_eq!((flow.sent, flow.cost), (2.0, 12.0));
+        assert_eq!(flow.flows[1], 0.0);
+
+        let net = network(3, &[(0, 1, 1.0), (1, 2, -2.0), (2, 1, 1.0)], true);
+        assert!(min_cost_flow(&net, &[1.0; 3], 0, 2, 1.0).is_err());
+    }
+}
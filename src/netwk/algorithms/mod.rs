@@ -0,0 +1,184 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::NetworkGraph;
+
+pub mod centrality;
+pub mod coloring;
+pub mod connectivity;
+pub mod cycles;
+pub mod flow;
+pub mod matrix;
+pub mod mincost;
+pub mod multipath;
+pub mod path;
+pub mod spanning;
+
+// Graph algorithms. They work on a Network, the graph flattened into numbered nodes and links,
+// rather than on the NetworkGraph itself: it is quicker to walk, and being plain data it can be
+// handed to a job thread while the document carries on being edited.
+
+/// One edge, between nodes numbered as in Network::names, pointing the way it was drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    /// Edges without a weight count as 1, so unweighted graphs are measured in hops
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Network {
+    pub names: Vec<String>,
+    pub links: Vec<Link>,
+    pub directed: bool,
+    /// Links which can be followed away from each node, with the node at the far end
+    adjacent: Vec<Vec<(usize, usize)>>,
+    lookup: HashMap<String, usize>,
+}
+
+impl Network {
+    pub fn new<N: Clone, E: Clone>(graph: &NetworkGraph<N, E>) -> Self {
+        let names = graph.nodes().map(|node| node.name().clone()).collect::<Vec<_>>();
+        let lookup: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+
+        let links = graph
+            .edges()
+            .filter_map(|edge| {
+                Some(Link {
+                    from: *lookup.get(edge.from().name().as_str())?,
+                    to: *lookup.get(edge.to().name().as_str())?,
+                    weight: edge.weight().unwrap_or(1.0),
+                })
+            })
+            .collect();
+
+        Network::from_links(names, links, graph.is_directed())
+    }
+
+    pub fn from_links(names: Vec<String>, links: Vec<Link>, directed: bool) -> Self {
+        let mut adjacent = vec![vec![]; names.len()];
+
+        for (i, link) in links.iter().enumerate() {
+            adjacent[link.from].push((i, link.to));
+
+            if !directed && link.from != link.to {
+                adjacent[link.to].push((i, link.from));
+            }
+        }
+
+        let lookup = names.iter().enumerate().map(|(i, name)| (name.clone(), i)).collect();
+
+        Network { names, links, directed, adjacent, lookup }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.lookup.get(name).copied()
+    }
+
+    /// Links which can be followed away from the node, paired with the node each leads to.
+    /// Undirected links can be followed from either end.
+    pub fn adjacent(&self, node: usize) -> &[(usize, usize)] {
+        &self.adjacent[node]
+    }
+
+    /// Names at either end of a link, the way it was drawn.
+    pub fn link_names(&self, link: usize) -> (String, String) {
+        let link = self.links[link];
+
+        (self.names[link.from].clone(), self.names[link.to].clone())
+    }
+
+    /// The same network with every link pointing the other way. Link numbers stay the same.
+    pub fn reversed(&self) -> Network {
+        let links = self.links.iter().map(|link| Link { from: link.to, to: link.from, ..*link }).collect();
+
+        Network::from_links(self.names.clone(), links, self.directed)
+    }
+
+    /// The same network weighed some other way, such as by capacity rather than weight.
+    pub fn with_weights(&self, weights: impl IntoIterator<Item = f32>) -> Network {
+        let links = self.links.iter().zip(weights).map(|(link, weight)| Link { weight, ..*link }).collect();
+
+        Network::from_links(self.names.clone(), links, self.directed)
+    }
+
+    /// The same network with direction ignored.
+    pub fn undirected(&self) -> Network {
+        Network::from_links(self.names.clone(), self.links.clone(), false)
+    }
+
+    /// Links for at least a quarter of the pairs of nodes. Roughly where algorithms costing n^3
+    /// start to beat ones costing n * links * log n.
+    pub fn is_dense(&self) -> bool {
+        self.links.len() * 4 >= self.len() * self.len()
+    }
+
+    pub fn has_negative(&self) -> bool {
+        self.links.iter().any(|link| link.weight < 0.0)
+    }
+}
+
+/// Entry in a priority queue, ordered so that a BinaryHeap pops the lowest cost first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Visit {
+    pub cost: f32,
+    pub node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+
+    use super::*;
+
+    /// Network from (from, to, weight) triples over nodes named by their number.
+    pub fn network(nodes: usize, links: &[(usize, usize, f32)], directed: bool) -> Network {
+        Network::from_links(
+            (0..nodes).map(|i| i.to_string()).collect(),
+            links.iter().map(|&(from, to, weight)| Link { from, to, weight }).collect(),
+            directed,
+        )
+    }
+
+    /// Flattening keeps names, weights and which way edges were drawn.
+    #[test]
+    pub fn test_network () {
+        let mut graph: NetworkGraph<(), ()> = NetworkGraph::new();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        graph.add_edge(&a, &b);
+        graph.add_edge_with(&b, &a, Some(3.5), ());
+
+        let net = Network::new(&graph);
+
+        assert_eq!(net.len(), 2);
+        assert_eq!(net.links, vec![Link { from: 0, to: 1, weight: 1.0 }, Link { from: 1, to: 0, weight: 3.5 }]);
+        assert_eq!(net.adjacent(0).len(), 2);
+        assert_eq!(net.index("1"), Some(1));
+
+        graph.set_directed(true);
+        assert_eq!(Network::new(&graph).adjacent(0), &[(0, 1)]);
+    }
+}
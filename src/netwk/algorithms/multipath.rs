@@ -0,0 +1,165 @@
+use super::path::{self, Path};
+use super::Network;
+
+/// Up to k cheapest paths from source to target which never visit a node twice, cheapest
+/// first (Yen's algorithm). Each is found by branching off one already found at some node,
+/// with the links earlier paths took from there blocked. Weights must not be negative.
+pub fn k_shortest(net: &Network, source: usize, target: usize, k: usize) -> Vec<Path> {
+    let mut found: Vec<Path> = match path::shortest_path(net, source, target) {
+        Some(path) if k > 0 => vec![path],
+        _ => return vec![],
+    };
+    let mut candidates: Vec<Path> = vec![];
+
+    while found.len() < k {
+        let last = found.last().expect("starts with one path");
+
+        for branch in 0..last.links.len() {
+            let spur = last.nodes[branch];
+            let (root_nodes, root_links) = (&last.nodes[..branch], &last.links[..branch]);
+
+            // Links leaving the branch node along paths which share this root are taken already
+            let taken: Vec<usize> = found
+                .iter()
+                .filter(|path| path.links.len() > branch && path.links[..branch] == *root_links)
+                .map(|path| path.links[branch])
+                .collect();
+
+            let skip = |link: usize| {
+                let ends = net.links[link];
+                taken.contains(&link) || root_nodes.contains(&ends.from) || root_nodes.contains(&ends.to)
+            };
+
+            let spur_path = match path::search(net, spur, Some(target), |_| 0.0, skip).path_to(target) {
+                Some(spur_path) => spur_path,
+                None => continue,
+            };
+
+            let mut links = root_links.to_vec();
+            links.extend(spur_path.links);
+            let mut nodes = root_nodes.to_vec();
+            nodes.extend(spur_path.nodes);
+            let cost = links.iter().map(|&link| net.links[link].weight).sum();
+
+            if !candidates.iter().chain(found.iter()).any(|other| other.links == links) {
+                candidates.push(Path { nodes, links, cost });
+            }
+        }
+
+        // Cheapest candidate next, fewest hops breaking ties
+        let next = candidates
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.cost.total_cmp(&b.cost).then(a.links.len().cmp(&b.links.len())))
+            .map(|(i, _)| i);
+
+        match next {
+            Some(next) => found.push(candidates.swap_remove(next)),
+            None => break,
+        }
+    }
+
+    found
+}
+
+/// Whether a path costs the same as a finite `best`. Sums of floats drift depending on the
+/// order they were added in, so near enough counts.
+pub fn same_cost(cost: f32, best: f32) -> bool {
+    (cost - best).abs() <= 1e-4 * best.abs().max(1.0)
+}
+
+/// Every path from source to target costing the same as the cheapest, up to `limit` of them
+/// since there can be exponentially many. Weights must not be negative.
+pub fn equal_cost(net: &Network, source: usize, target: usize, limit: usize) -> Vec<Path> {
+    let from_source = path::dijkstra(net, source).cost;
+    let to_target = path::dijkstra(&net.reversed(), target).cost;
+    let best = from_source[target];
+
+    if !best.is_finite() {
+        return vec![];
+    }
+
+    // A link lies on a cheapest path if going source, along it, then target costs the best
+    let tight = |from: usize, link: usize, to: usize| {
+        same_cost(from_source[from] + net.links[link].weight + to_target[to], best)
+    };
+
+    let mut paths = vec![];
+    let mut current = Path { nodes: vec![source], links: vec![], cost: best };
+
+    equal_cost_from(net, source, target, limit, &tight, &mut current, &mut paths);
+
+    paths
+}
+
+fn equal_cost_from(
+    net: &Network,
+    node: usize,
+    target: usize,
+    limit: usize,
+    tight: &impl Fn(usize, usize, usize) -> bool,
+    current: &mut Path,
+    paths: &mut Vec<Path>,
+) {
+    if node == target {
+        paths.push(current.clone());
+        return;
+    }
+
+    for &(link, next) in net.adjacent(node) {
+        if paths.len() >= limit {
+            return;
+        }
+        // Zero weight links can make cycles of equal cost, which would never finish
+        if !tight(node, link, next) || current.nodes.contains(&next) {
+            continue;
+        }
+
+        current.nodes.push(next);
+        current.links.push(link);
+        equal_cost_from(net, next, target, limit, tight, current, paths);
+        current.nodes.pop();
+        current.links.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// The usual example of Yen's algorithm, C to H.
+    #[test]
+    pub fn test_k_shortest () {
+        let (c, d, e, f, g, h) = (0, 1, 2, 3, 4, 5);
+        let net = network(
+            6,
+            &[(c, d, 3.0), (c, e, 2.0), (d, f, 4.0), (e, d, 1.0), (e, f, 2.0), (e, g, 3.0), (f, g, 2.0), (f, h, 1.0), (g, h, 2.0)],
+            true,
+        );
+
+        let paths = k_shortest(&net, c, h, 3);
+
+        assert_eq!(paths.iter().map(|path| path.cost).collect::<Vec<_>>(), vec![5.0, 7.0, 8.0]);
+        assert_eq!(paths[0].nodes, vec![c, e, f, h]);
+        assert_eq!(paths[1].nodes, vec![c, e, g, h]);
+        assert_eq!(k_shortest(&net, c, h, 10).len(), 7);
+        assert!(k_shortest(&net, h, c, 3).is_empty());
+    }
+
+    /// Every cheapest path is found, however many hops, and no more than asked for.
+    #[test]
+    pub fn test_equal_cost () {
+        let net = network(4, &[(0, 1, 1.0), (1, 3, 1.0), (0, 2, 1.0), (2, 3, 1.0), (0, 3, 3.0)], false);
+        assert_eq!(equal_cost(&net, 0, 3, 10).len(), 2);
+
+        let net = network(4, &[(0, 1, 1.0), (1, 3, 1.0), (0, 2, 1.0), (2, 3, 1.0), (0, 3, 2.0)], true);
+        let paths = equal_cost(&net, 0, 3, 10);
+
+        assert_eq!(paths.len(), 3);
+        assert!(paths.iter().all(|path| path.cost == 2.0));
+        assert_eq!(equal_cost(&net, 0, 3, 2).len(), 2);
+        assert!(equal_cost(&net, 3, 0, 10).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use super::{Network, Visit};
+use crate::netwk::geometry::Point;
+
+/// A route through the network: the nodes visited and the links followed between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub nodes: Vec<usize>,
+    pub links: Vec<usize>,
+    pub cost: f32,
+}
+
+/// A cycle whose links add up to less than nothing, so going round it again always comes out
+/// cheaper and nothing reachable from it has a shortest path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegativeCycle {
+    pub nodes: Vec<usize>,
+    pub links: Vec<usize>,
+    pub cost: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Dijkstra,
+    /// Dijkstra steered towards the target by straight line distance
+    AStar,
+    /// Slower, but copes with negative weights
+    BellmanFord,
+}
+
+impl Method {
+    pub fn next(self) -> Self {
+        match self {
+            Method::Dijkstra => Method::AStar,
+            Method::AStar => Method::BellmanFord,
+            Method::BellmanFord => Method::Dijkstra,
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Dijkstra => write!(f, "Dijkstra"),
+            Method::AStar => write!(f, "A*"),
+            Method::BellmanFord => write!(f, "Bellman-Ford"),
+        }
+    }
+}
+
+/// Cheapest known way to each node from one source. Nodes never reached cost infinity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub source: usize,
+    pub cost: Vec<f32>,
+    /// Link each node was reached by, and the node it was reached from
+    pub via: Vec<Option<(usize, usize)>>,
+}
+
+impl Tree {
+    fn new(net: &Network, source: usize) -> Self {
+        let mut cost = vec![f32::INFINITY; net.len()];
+        cost[source] = 0.0;
+
+        Tree { source, cost, via: vec![None; net.len()] }
+    }
+
+    pub fn path_to(&self, target: usize) -> Option<Path> {
+        if !self.cost[target].is_finite() {
+            return None;
+        }
+
+        let (mut nodes, mut links) = (vec![target], vec![]);
+        let mut node = target;
+
+        while let Some((link, from)) = self.via[node] {
+            if node == self.source {
+                break;
+            }
+
+            links.push(link);
+            nodes.push(from);
+            node = from;
+        }
+
+        nodes.reverse();
+        links.reverse();
+
+        Some(Path { nodes, links, cost: self.cost[target] })
+    }
+}
+
+/// Shortest paths from the source to everywhere it reaches. Weights must not be negative.
+pub fn dijkstra(net: &Network, source: usize) -> Tree {
+    search(net, source, None, |_| 0.0, |_| false)
+}
+
+pub fn shortest_path(net: &Network, source: usize, target: usize) -> Option<Path> {
+    search(net, source, Some(target), |_| 0.0, |_| false).path_to(target)
+}
+
+/// Shortest path found with A*, which looks at fewer nodes than Dijkstra when the heuristic is
+/// good. The heuristic guesses the cost left from a node to the target and must never guess
+/// high, or the path found may not be the shortest.
+pub fn a_star(net: &Network, source: usize, target: usize, heuristic: impl Fn(usize) -> f32) -> Option<Path> {
+    search(net, source, Some(target), heuristic, |_| false).path_to(target)
+}
+
+/// Straight line distance to the target, for A*. Weights needn't be lengths, so the distance is
+/// scaled by the lowest weight per unit length of any link, which keeps it from ever guessing high.
+pub fn euclidean<'a>(net: &Network, positions: &'a [Point], target: usize) -> impl Fn(usize) -> f32 + 'a {
+    let scale = net
+        .links
+        .iter()
+        .filter_map(|link| {
+            let length = positions[link.from].distance(positions[link.to]);
+            (length > 0.0).then(|| link.weight / length)
+        })
+        .fold(f32::INFINITY, f32::min);
+    let scale = if scale.is_finite() { scale.max(0.0) } else { 0.0 };
+
+    move |node| scale * positions[node].distance(positions[target])
+}
+
+/// Dijkstra, or A* given a heuristic, stopping early once the target is settled if there is
+/// one. Links for which `skip` is true are treated as missing.
+pub(crate) fn search(
+    net: &Network,
+    source: usize,
+    target: Option<usize>,
+    heuristic: impl Fn(usize) -> f32,
+    skip: impl Fn(usize) -> bool,
+) -> Tree {
+    let mut tree = Tree::new(net, source);
+    let mut settled = vec![false; net.len()];
+    let mut queue = BinaryHeap::from([Visit { cost: heuristic(source), node: source }]);
+
+    while let Some(Visit { node, .. }) = queue.pop() {
+        if settled[node] {
+            continue;
+        }
+        settled[node] = true;
+
+        if Some(node) == target {
+            break;
+        }
+
+        for &(link, next) in net.adjacent(node) {
+            let cost = tree.cost[node] + net.links[link].weight;
+
+            if settled[next] || skip(link) || cost >= tree.cost[next] {
+                continue;
+            }
+
+            tree.cost[next] = cost;
+            tree.via[next] = Some((link, node));
+            queue.push(Visit { cost: cost + heuristic(next), node: next });
+        }
+    }
+
+    tree
+}
+
+/// Shortest paths from the source allowing negative weights, or the negative cycle that makes
+/// them meaningless. An undirected link with a negative weight is such a cycle on its own, being
+/// walkable back and forth.
+pub fn bellman_ford(net: &Network, source: usize) -> Result<Tree, NegativeCycle> {
+    let mut tree = Tree::new(net, source);
+
+    let relax = |tree: &mut Tree| {
+        let mut relaxed = None;
+
+        for node in 0..net.len() {
+            if !tree.cost[node].is_finite() {
+                continue;
+            }
+
+            for &(link, next) in net.adjacent(node) {
+                let cost = tree.cost[node] + net.links[link].weight;
+
+                if cost < tree.cost[next] {
+                    tree.cost[next] = cost;
+                    tree.via[next] = Some((link, node));
+                    relaxed = Some(next);
+                }
+            }
+        }
+
+        relaxed
+    };
+
+    for _ in 1..net.len() {
+        if relax(&mut tree).is_none() {
+            return Ok(tree);
+        }
+    }
+
+    let mut node = match relax(&mut tree) {
+        Some(node) => node,
+        None => return Ok(tree),
+    };
+
+    // Still improving after every path could have been found means going round in circles.
+    // Stepping back once per node is sure to land on the cycle rather than a tail leading to it.
+    for _ in 0..net.len() {
+        node = tree.via[node].expect("relaxed nodes were reached").1;
+    }
+
+    let (mut nodes, mut links) = (vec![], vec![]);
+    let start = node;
+
+    loop {
+        let (link, from) = tree.via[node].expect("relaxed nodes were reached");
+        nodes.push(node);
+        links.push(link);
+        node = from;
+
+        if node == start {
+            break;
+        }
+    }
+
+    nodes.reverse();
+    links.reverse();
+    let cost = links.iter().map(|&link| net.links[link].weight).sum();
+
+    Err(NegativeCycle { nodes, links, cost })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// Dijkstra takes the long way round when it is cheaper, and counts hops without weights.
+    #[test]
+    pub fn test_dijkstra () {
+        let net = network(4, &[(0, 1, 5.0), (0, 2, 1.0), (2, 3, 1.0), (3, 1, 1.0)], false);
+        let path = shortest_path(&net, 0, 1).unwrap();
+
+        assert_eq!(path.nodes, vec![0, 2, 3, 1]);
+        assert_eq!(path.links, vec![1, 2, 3]);
+        assert_eq!(path.cost, 3.0);
+        assert_eq!(shortest_path(&net, 1, 0).unwrap().cost, 3.0);
+
+        let net = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 0, 1.0)], true);
+        assert_eq!(dijkstra(&net, 0).cost, vec![0.0, 1.0, 2.0, f32::INFINITY]);
+        assert_eq!(shortest_path(&net, 0, 3), None);
+        assert_eq!(shortest_path(&net, 1, 0).unwrap().nodes, vec![1, 2, 0]);
+    }
+
+    /// A* agrees with Dijkstra even when weights are nothing like the drawn lengths.
+    #[test]
+    pub fn test_a_star () {
+        let positions: Vec<Point> = (0..9).map(|i| Point::new((i % 3) as f32 * 100.0, (i / 3) as f32 * 100.0)).collect();
+        let mut links = vec![];
+
+        for i in 0..9 {
+            if i % 3 < 2 {
+                links.push((i, i + 1, 1.0 + (i % 4) as f32));
+            }
+            if i < 6 {
+                links.push((i, i + 3, 2.0));
+            }
+        }
+
+        let net = network(9, &links, false);
+
+        for target in 0..9 {
+            let path = a_star(&net, 0, target, euclidean(&net, &positions, target)).unwrap();
+            assert_eq!(path.cost, shortest_path(&net, 0, target).unwrap().cost);
+        }
+    }
+
+    /// Negative weights are fine until they make a cycle, which is then given back.
+    #[test]
+    pub fn test_bellman_ford () {
+        let net = network(3, &[(0, 1, 4.0), (0, 2, 5.0), (2, 1, -3.0)], true);
+        let tree = bellman_ford(&net, 0).unwrap();
+
+        assert_eq!(tree.cost, vec![0.0, 2.0, 5.0]);
+        assert_eq!(tree.path_to(1).unwrap().nodes, vec![0, 2, 1]);
+
+        let net = network(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, -1.0), (3, 1, -1.0)], true);
+        let cycle = bellman_ford(&net, 0).unwrap_err();
+
+        assert_eq!(cycle.nodes.len(), 3);
+        assert_eq!(cycle.cost, -1.0);
+        assert!(!cycle.nodes.contains(&0));
+    }
+}
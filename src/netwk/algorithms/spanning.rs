@@ -0,0 +1,143 @@
+use std::collections::BinaryHeap;
+
+use super::{Network, Visit};
+
+/// Links joining every node to every other it can reach, without any cycles: one tree per
+/// connected part of the network. Direction is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forest {
+    pub links: Vec<usize>,
+    pub weight: f32,
+    /// How many trees, one per connected part
+    pub trees: usize,
+}
+
+/// Which nodes are joined so far, merging sets as links are added.
+#[derive(Debug, Clone)]
+pub(crate) struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl DisjointSet {
+    pub fn new(size: usize) -> Self {
+        DisjointSet { parent: (0..size).collect(), rank: vec![0; size] }
+    }
+
+    pub fn find(&mut self, node: usize) -> usize {
+        let parent = self.parent[node];
+
+        if parent == node {
+            return node;
+        }
+
+        let root = self.find(parent);
+        self.parent[node] = root;
+
+        root
+    }
+
+    /// Join the sets of the two, returning false if they were already in the same one.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+
+        if a == b {
+            return false;
+        }
+
+        let (low, high) = if self.rank[a] < self.rank[b] { (a, b) } else { (b, a) };
+        self.parent[low] = high;
+        if self.rank[low] == self.rank[high] {
+            self.rank[high] += 1;
+        }
+
+        true
+    }
+}
+
+/// Lightest (or heaviest) spanning forest by Kruskal's algorithm: links in order of weight,
+/// keeping each one which joins two trees. Best on sparse networks.
+pub fn kruskal(net: &Network, maximum: bool) -> Forest {
+    let mut order: Vec<usize> = (0..net.links.len()).collect();
+    order.sort_by(|&a, &b| {
+        let order = net.links[a].weight.total_cmp(&net.links[b].weight);
+        if maximum { order.reverse() } else { order }
+    });
+
+    let mut sets = DisjointSet::new(net.len());
+    let links: Vec<usize> = order.into_iter().filter(|&link| sets.union(net.links[link].from, net.links[link].to)).collect();
+
+    forest(net, links)
+}
+
+/// Lightest (or heaviest) spanning forest by Prim's algorithm: each tree grown from a node by
+/// adding the cheapest link leaving it. Best on dense networks.
+pub fn prim(net: &Network, maximum: bool) -> Forest {
+    let sign = if maximum { -1.0 } else { 1.0 };
+    let undirected = net.undirected();
+
+    let mut in_tree = vec![false; net.len()];
+    let mut best: Vec<Option<usize>> = vec![None; net.len()];
+    let mut links = vec![];
+
+    for root in 0..net.len() {
+        if in_tree[root] {
+            continue;
+        }
+
+        let mut queue = BinaryHeap::from([Visit { cost: 0.0, node: root }]);
+
+        while let Some(Visit { node, .. }) = queue.pop() {
+            if in_tree[node] {
+                continue;
+            }
+            in_tree[node] = true;
+            links.extend(best[node]);
+
+            for &(link, next) in undirected.adjacent(node) {
+                let cost = sign * net.links[link].weight;
+
+                if !in_tree[next] && best[next].is_none_or(|best| cost < sign * net.links[best].weight) {
+                    best[next] = Some(link);
+                    queue.push(Visit { cost, node: next });
+                }
+            }
+        }
+    }
+
+    forest(net, links)
+}
+
+fn forest(net: &Network, links: Vec<usize>) -> Forest {
+    let weight = links.iter().map(|&link| net.links[link].weight).sum();
+    let trees = net.len() - links.len();
+
+    Forest { links, weight, trees }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::algorithms::test::network;
+
+    /// Both algorithms agree on the weight, whichever way round, and leave out the odd one out.
+    #[test]
+    pub fn test_spanning_forest () {
+        let net = network(
+            6,
+            &[(0, 1, 4.0), (1, 2, 1.0), (0, 2, 2.0), (2, 3, 5.0), (1, 3, 3.0), (3, 3, 0.5), (4, 0, 2.5)],
+            true,
+        );
+
+        for find in [kruskal, prim] {
+            let lightest = find(&net, false);
+            assert_eq!(lightest.weight, 8.5);
+            assert_eq!(lightest.links.len(), 4);
+            assert_eq!(lightest.trees, 2);
+
+            let heaviest = find(&net, true);
+            assert_eq!(heaviest.weight, 14.5);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::geometry::{Color, Point, Rectangle, Size, Vector};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::layout::distance_to_segment;
+
+// Annotations are the free drawing on a diagram: titles, notes, outlines and pointers.
+// They live next to the NetworkGraph rather than in it, so none of the graph code ever sees them.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShapeKind {
+    Text,
+    Rectangle,
+    Ellipse,
+    Arrow,
+}
+
+/// Geometry of an annotation. Two point shapes are stored corner to corner (or tail to tip).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Shape {
+    Text {
+        position: Point,
+        content: String,
+        size: f32,
+    },
+    Rectangle {
+        from: Point,
+        to: Point,
+    },
+    Ellipse {
+        from: Point,
+        to: Point,
+    },
+    Arrow {
+        from: Point,
+        to: Point,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnnotationStyle {
+    pub color: Color,
+    pub filled: bool,
+    pub width: f32,
+}
+
+impl Default for AnnotationStyle {
+    fn default() -> Self {
+        AnnotationStyle {
+            color: Color::from_rgb(0.4, 0.4, 0.4),
+            filled: false,
+            width: 1.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Annotation {
+    id: usize,
+    pub shape: Shape,
+    pub style: AnnotationStyle,
+    /// Drawn over the graph rather than underneath it
+    pub above: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Annotations {
+    items: Vec<Annotation>,
+    last_id: usize,
+}
+
+impl Shape {
+    /// Shape of the given kind spanning the two points. Text only uses the first.
+    pub fn new(kind: ShapeKind, from: Point, to: Point) -> Shape {
+        match kind {
+            ShapeKind::Text => Shape::Text { position: from, content: String::from("Text"), size: 16.0 },
+            ShapeKind::Rectangle => Shape::Rectangle { from, to },
+            ShapeKind::Ellipse => Shape::Ellipse { from, to },
+            ShapeKind::Arrow => Shape::Arrow { from, to },
+        }
+    }
+
+    pub fn kind(&self) -> ShapeKind {
+        match self {
+            Shape::Text { .. } => ShapeKind::Text,
+            Shape::Rectangle { .. } => ShapeKind::Rectangle,
+            Shape::Ellipse { .. } => ShapeKind::Ellipse,
+            Shape::Arrow { .. } => ShapeKind::Arrow,
+        }
+    }
+
+    /// Rough box around the shape. Text is sized by guessing at the glyph width.
+    pub fn bounds(&self) -> Rectangle {
+        match self {
+            Shape::Text { position, content, size } => Rectangle::new(
+                *position,
+                Size::new(content.chars().count() as f32 * size * 0.6, *size),
+            ),
+            Shape::Rectangle { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => {
+                Rectangle::new(
+                    Point::new(from.x.min(to.x), from.y.min(to.y)),
+                    Size::new((to.x - from.x).abs(), (to.y - from.y).abs()),
+                )
+            }
+        }
+    }
+
+    pub fn translate(&mut self, delta: Vector) {
+        match self {
+            Shape::Text { position, .. } => *position = *position + delta,
+            Shape::Rectangle { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => {
+                *from = *from + delta;
+                *to = *to + delta;
+            }
+        }
+    }
+
+    /// Is pos on (or for filled shapes, in) the shape.
+    pub fn hit(&self, pos: Point, filled: bool, tolerance: f32) -> bool {
+        match self {
+            Shape::Text { .. } => self.bounds().contains(pos),
+            Shape::Arrow { from, to } => distance_to_segment(pos, *from, *to) < tolerance,
+            Shape::Rectangle { .. } => {
+                let bounds = self.bounds();
+                let (left, right) = (bounds.x, bounds.x + bounds.width);
+                let (top, bottom) = (bounds.y, bounds.y + bounds.height);
+
+                if filled {
+                    return bounds.contains(pos);
+                }
+
+                [
+                    (Point::new(left, top), Point::new(right, top)),
+                    (Point::new(right, top), Point::new(right, bottom)),
+                    (Point::new(right, bottom), Point::new(left, bottom)),
+                    (Point::new(left, bottom), Point::new(left, top)),
+                ]
+                .iter()
+                .any(|(a, b)| distance_to_segment(pos, *a, *b) < tolerance)
+            }
+            Shape::Ellipse { .. } => {
+                let bounds = self.bounds();
+                let centre = bounds.center();
+                let (rx, ry) = (bounds.width / 2.0, bounds.height / 2.0);
+
+                if rx == 0.0 || ry == 0.0 {
+                    return false;
+                }
+
+                // Distance from the centre in units of the radii, 1.0 is the outline
+                let r = (((pos.x - centre.x) / rx).powi(2) + ((pos.y - centre.y) / ry).powi(2)).sqrt();
+                let band = tolerance / rx.min(ry);
+
+                if filled {
+                    r <= 1.0 + band
+                } else {
+                    (r - 1.0).abs() <= band
+                }
+            }
+        }
+    }
+}
+
+impl Annotation {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Annotations::default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Annotation> + Clone {
+        self.items.iter()
+    }
+
+    pub fn add(&mut self, shape: Shape) -> usize {
+        let id = self.last_id;
+        self.last_id += 1;
+
+        self.items.push(Annotation { id, shape, style: AnnotationStyle::default(), above: false });
+
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Annotation> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Annotation> {
+        self.items.iter_mut().find(|item| item.id == id)
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.items.retain(|item| item.id != id);
+    }
+
+    /// Topmost annotation under pos. Ones above the graph win over those beneath it.
+    pub fn get_at(&self, pos: Point, tolerance: f32) -> Option<&Annotation> {
+        self.get_at_where(pos, tolerance, |_| true)
+    }
+
+    /// As get_at, but skipping any annotation the filter rejects.
+    pub fn get_at_where(&self, pos: Point, tolerance: f32, filter: impl Fn(&Annotation) -> bool) -> Option<&Annotation> {
+        let above = self.items.iter().rev().filter(|item| item.above);
+        let below = self.items.iter().rev().filter(|item| !item.above);
+
+        above
+            .chain(below)
+            .filter(|item| filter(item))
+            .find(|item| item.shape.hit(pos, item.style.filled, tolerance))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Outlines should only be hit near their edge, filled shapes anywhere inside.
+    #[test]
+    pub fn test_annotation_hits () {
+        let mut annotations = Annotations::new();
+        let rect = annotations.add(Shape::new(ShapeKind::Rectangle, Point::new(0.0, 0.0), Point::new(100.0, 50.0)));
+        let ellipse = annotations.add(Shape::new(ShapeKind::Ellipse, Point::new(200.0, 0.0), Point::new(300.0, 50.0)));
+
+        assert_eq!(annotations.get_at(Point::new(1.0, 25.0), 3.0).map(|a| a.id()), Some(rect));
+        assert!(annotations.get_at(Point::new(50.0, 25.0), 3.0).is_none());
+        assert_eq!(annotations.get_at(Point::new(250.0, 1.0), 3.0).map(|a| a.id()), Some(ellipse));
+
+        annotations.get_mut(rect).unwrap().style.filled = true;
+        assert_eq!(annotations.get_at(Point::new(50.0, 25.0), 3.0).map(|a| a.id()), Some(rect));
+
+        annotations.get_mut(rect).unwrap().shape.translate(Vector::new(0.0, 100.0));
+        assert!(annotations.get_at(Point::new(50.0, 25.0), 3.0).is_none());
+
+        annotations.remove(ellipse);
+        assert!(annotations.get(ellipse).is_none());
+    }
+}
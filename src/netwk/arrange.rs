@@ -0,0 +1,144 @@
+use super::geometry::Point;
+use std::collections::HashMap;
+
+use super::format::GraphData;
+use super::job::Progress;
+
+// Automatic layouts. These work on a plain copy of the graph so they can run on a job thread
+// while the document carries on being edited, and hand back positions to be applied after.
+
+/// Distance the spring layout tries to keep between joined nodes.
+const SPRING_LENGTH: f32 = 80.0;
+const SPRING_STEPS: usize = 200;
+
+/// Relax the layout with springs along the edges and every node pushing away the others near
+/// it (Fruchterman-Reingold). Starts from where the nodes already are, so running it again
+/// tidies rather than starts over. Only nodes within a couple of spring lengths push on each
+/// other, which keeps each step close to linear. Returns None if cancelled part way.
+pub fn spring_layout(data: &GraphData, progress: &Progress) -> Option<HashMap<String, Point>> {
+    let index: HashMap<&str, usize> = data.nodes.iter().enumerate().map(|(i, node)| (node.name.as_str(), i)).collect();
+    let edges: Vec<(usize, usize)> = data
+        .edges
+        .iter()
+        .filter_map(|edge| Some((*index.get(edge.from.as_str())?, *index.get(edge.to.as_str())?)))
+        .filter(|(from, to)| from != to)
+        .collect();
+
+    let mut positions: Vec<(f32, f32)> = data.nodes.iter().map(|node| (node.x, node.y)).collect();
+    let reach = 2.0 * SPRING_LENGTH;
+
+    for step in 0..SPRING_STEPS {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.report(step as f32 / SPRING_STEPS as f32, "Arranging");
+
+        let mut shift = vec![(0.0f32, 0.0f32); positions.len()];
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+
+        for (i, (x, y)) in positions.iter().enumerate() {
+            cells.entry(((x / reach).floor() as i32, (y / reach).floor() as i32)).or_default().push(i);
+        }
+
+        for (&(cx, cy), members) in cells.iter() {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let others = match cells.get(&(cx + dx, cy + dy)) {
+                        Some(others) => others,
+                        None => continue,
+                    };
+
+                    for &i in members {
+                        for &j in others.iter().filter(|&&j| j != i) {
+                            let (x, y) = (positions[i].0 - positions[j].0, positions[i].1 - positions[j].1);
+                            let distance = (x * x + y * y).sqrt();
+
+                            if distance > reach {
+                                continue;
+                            }
+
+                            // Nodes sat exactly on top of each other still need telling apart
+                            let (x, y, distance) = if distance < 0.01 {
+                                let angle = (i * 7 + j * 13) as f32;
+                                (angle.cos(), angle.sin(), 0.01)
+                            } else {
+                                (x / distance, y / distance, distance)
+                            };
+
+                            let push = SPRING_LENGTH * SPRING_LENGTH / distance;
+                            shift[i].0 += x * push;
+                            shift[i].1 += y * push;
+                        }
+                    }
+                }
+            }
+        }
+
+        for &(from, to) in edges.iter() {
+            let (x, y) = (positions[to].0 - positions[from].0, positions[to].1 - positions[from].1);
+            let distance = (x * x + y * y).sqrt().max(0.01);
+            let pull = distance / SPRING_LENGTH;
+
+            shift[from].0 += x * pull;
+            shift[from].1 += y * pull;
+            shift[to].0 -= x * pull;
+            shift[to].1 -= y * pull;
+        }
+
+        // Cools as it goes, so nodes settle instead of jiggling about forever
+        let limit = SPRING_LENGTH * (1.0 - step as f32 / SPRING_STEPS as f32);
+
+        for (pos, (x, y)) in positions.iter_mut().zip(shift) {
+            let length = (x * x + y * y).sqrt();
+
+            if length > 0.0 {
+                let scale = length.min(limit) / length;
+                pos.0 += x * scale;
+                pos.1 += y * scale;
+            }
+        }
+    }
+
+    Some(
+        data.nodes
+            .iter()
+            .zip(positions)
+            .map(|(node, (x, y))| (node.name.clone(), Point::new(x, y)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::format::{EdgeData, NodeData};
+
+    fn node(name: &str, x: f32, y: f32) -> NodeData {
+        NodeData { name: name.to_string(), x, y }
+    }
+
+    /// Joined nodes are pulled in from far away, and piled up nodes get pushed apart.
+    #[test]
+    pub fn test_spring_layout () {
+        let data = GraphData {
+            nodes: vec![node("a", 0.0, 0.0), node("b", 1000.0, 0.0), node("c", 500.0, 500.0), node("d", 500.0, 500.0)],
+            edges: vec![EdgeData { from: String::from("a"), to: String::from("b"), weight: None, control: None }],
+            ..GraphData::default()
+        };
+
+        let positions = spring_layout(&data, &Progress::default()).unwrap();
+
+        assert!(positions["a"].distance(positions["b"]) < 200.0);
+        assert!(positions["c"].distance(positions["d"]) > SPRING_LENGTH / 2.0);
+    }
+
+    /// Cancelling gives nothing back rather than a half finished layout.
+    #[test]
+    pub fn test_spring_layout_cancel () {
+        let progress = Progress::default();
+        progress.cancel();
+
+        assert!(spring_layout(&GraphData::default(), &progress).is_none());
+    }
+}
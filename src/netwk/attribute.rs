@@ -0,0 +1,175 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::change::Change;
+use super::NetworkGraph;
+
+/// Numbers an edge can carry besides its weight, for analyses which need more than one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EdgeAttributes {
+    /// Most that can be sent along the edge
+    pub capacity: Option<f32>,
+    /// Price of sending one unit along the edge
+    pub cost: Option<f32>,
+}
+
+/// Attributes of whichever edges have any, going by the names at either end in the direction the
+/// edge was drawn and, for parallel edges, which of those drawn that way it is. So A to B and
+/// B to A have their own, as does each of several edges from A to B.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "Vec<(String, String, usize, EdgeAttributes)>", into = "Vec<(String, String, usize, EdgeAttributes)>")
+)]
+pub struct Attributes {
+    edges: HashMap<(String, String, usize), EdgeAttributes>,
+}
+
+fn edge_key(from: &str, to: &str, nth: usize) -> (String, String, usize) {
+    (from.to_string(), to.to_string(), nth)
+}
+
+/// How many edges are drawn from one node to the other.
+pub(super) fn drawn<N: Clone, E: Clone>(graph: &NetworkGraph<N, E>, from: &str, to: &str) -> usize {
+    graph.get_node(from).map_or(0, |node| {
+        node.edges.borrow().iter().filter(|conn| conn.outgoing && conn.dst.name == to).count()
+    })
+}
+
+/// Whether a node is a member of a collapsed group, and so out of the graph until it is expanded.
+fn hidden<N: Clone, E: Clone>(graph: &NetworkGraph<N, E>, name: &str) -> bool {
+    graph.groups().iter().any(|group| group.is_collapsed() && group.members().iter().any(|member| member == name))
+}
+
+fn present<N: Clone, E: Clone>(graph: &NetworkGraph<N, E>, name: &str) -> bool {
+    graph.get_node(name).is_some() || hidden(graph, name)
+}
+
+impl Attributes {
+    /// Attributes of the `nth` edge drawn from one node to the other.
+    pub fn edge(&self, from: &str, to: &str, nth: usize) -> EdgeAttributes {
+        self.edges.get(&edge_key(from, to, nth)).copied().unwrap_or_default()
+    }
+
+    pub fn set_edge(&mut self, from: &str, to: &str, nth: usize, attributes: EdgeAttributes) {
+        if attributes == EdgeAttributes::default() {
+            self.edges.remove(&edge_key(from, to, nth));
+        } else {
+            self.edges.insert(edge_key(from, to, nth), attributes);
+        }
+    }
+
+    /// Keep up with changes to the graph, so attributes follow renamed nodes and reversed edges,
+    /// survive their edges being collapsed into a group, and don't outlive them otherwise.
+    pub fn apply<N: Clone, E: Clone>(&mut self, graph: &NetworkGraph<N, E>, changes: &[Change]) {
+        let between = |a: &str, b: &str, key: &(String, String, usize)| {
+            (key.0 == a && key.1 == b) || (key.0 == b && key.1 == a)
+        };
+
+        for change in changes {
+            match change {
+                // Members of a collapsed group are put away rather than gone, so theirs are kept
+                // until the group itself goes
+                Change::NodeRemoved(_) => self.edges.retain(|(a, b, _), _| present(graph, a) && present(graph, b)),
+                Change::NodeRenamed { from, to } => {
+                    self.rekey(|(a, b, _)| a == from || b == from, |(a, b, nth)| {
+                        let rename = |name: String| if name == *from { to.clone() } else { name };
+                        (rename(a), rename(b), nth)
+                    });
+                }
+                // Whichever edges are left between the two keep theirs
+                Change::EdgeRemoved(a, b) => {
+                    self.edges.retain(|key, _| {
+                        !between(a, b, key) || key.2 < drawn(graph, &key.0, &key.1) || hidden(graph, &key.0) || hidden(graph, &key.1)
+                    });
+                }
+                Change::EdgeReversed(a, b) => {
+                    self.rekey(|key| between(a, b, key), |(from, to, nth)| (to, from, nth));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Move the attributes of every edge the filter picks to a new key.
+    fn rekey(
+        &mut self,
+        filter: impl Fn(&(String, String, usize)) -> bool,
+        rekey: impl Fn((String, String, usize)) -> (String, String, usize),
+    ) {
+        let moved: Vec<_> = self.edges.keys().filter(|key| filter(key)).cloned().collect();
+        let moved: Vec<_> = moved
+            .into_iter()
+            .map(|key| {
+                let attributes = self.edges.remove(&key).expect("key was just found");
+                (rekey(key), attributes)
+            })
+            .collect();
+
+        self.edges.extend(moved);
+    }
+}
+
+impl From<Vec<(String, String, usize, EdgeAttributes)>> for Attributes {
+    fn from(edges: Vec<(String, String, usize, EdgeAttributes)>) -> Self {
+        Attributes { edges: edges.into_iter().map(|(from, to, nth, attributes)| ((from, to, nth), attributes)).collect() }
+    }
+}
+
+/// Saved in name order, so saving twice gives the same file.
+impl From<Attributes> for Vec<(String, String, usize, EdgeAttributes)> {
+    fn from(attributes: Attributes) -> Self {
+        let mut edges: Vec<_> =
+            attributes.edges.into_iter().map(|((from, to, nth), attributes)| (from, to, nth, attributes)).collect();
+        edges.sort_by(|a, b| (&a.0, &a.1, a.2).cmp(&(&b.0, &b.1, b.2)));
+
+        edges
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Attributes belong to one edge in one direction, follow renames and reversals, and go
+    /// with their edge but not with the others between the same nodes.
+    #[test]
+    pub fn test_attributes () {
+        let mut graph: NetworkGraph<(), ()> = NetworkGraph::new();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &a);
+
+        let mut attributes = Attributes::default();
+        let flow = EdgeAttributes { capacity: Some(10.0), cost: Some(2.0) };
+        let back = EdgeAttributes { capacity: Some(5.0), cost: None };
+
+        attributes.set_edge("0", "1", 1, flow);
+        attributes.set_edge("1", "0", 0, back);
+        assert_eq!(attributes.edge("0", "1", 0), EdgeAttributes::default());
+        assert_eq!(attributes.edge("0", "1", 1), flow);
+
+        let a = graph.rename_node(&a, String::from("a")).unwrap();
+        attributes.apply(&graph, &[Change::NodeRenamed { from: String::from("0"), to: String::from("a") }]);
+        assert_eq!(attributes.edge("a", "1", 1), flow);
+
+        graph.reverse_edge(&a, &b);
+        attributes.apply(&graph, &[Change::EdgeReversed(String::from("a"), String::from("1"))]);
+        assert_eq!(attributes.edge("1", "a", 1), flow);
+        assert_eq!(attributes.edge("a", "1", 0), back);
+
+        // A removal which leaves edges standing leaves their attributes be
+        attributes.apply(&graph, &[Change::EdgeRemoved(String::from("a"), String::from("1"))]);
+        assert_eq!(attributes.edge("1", "a", 1), flow);
+
+        graph.remove_edge(&a, &b);
+        attributes.apply(&graph, &[Change::EdgeRemoved(String::from("a"), String::from("1"))]);
+        assert_eq!(attributes, Attributes::default());
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+use super::NetworkGraph;
+
+/// Something which happened to a graph. Nodes are named and edges given as (source, destination)
+/// names, so a change can be acted on after the nodes themselves have been replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    NodeAdded(String),
+    NodeRemoved(String),
+    NodeRenamed { from: String, to: String },
+    /// What the node carries was replaced, or for a layout, where it is drawn
+    NodeUpdated(String),
+    EdgeAdded(String, String),
+    /// Every edge between the two was removed
+    EdgeRemoved(String, String),
+    /// Weight, payload or curve of the edges between the two changed
+    EdgeUpdated(String, String),
+    /// Every edge between the two now points the other way
+    EdgeReversed(String, String),
+    DirectedChanged,
+    GroupsChanged,
+}
+
+/// Handed each batch of changes, in the order they were made.
+pub type Observer = Box<dyn FnMut(&[Change])>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObserverId(usize);
+
+/// The graph's subscribers, plus whatever is being held back until the current batch ends.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<(ObserverId, Observer)>,
+    next_id: usize,
+    depth: usize,
+    pending: Vec<Change>,
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("observers", &self.observers.len())
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl Observers {
+    fn emit(&mut self, change: Change) {
+        // Nobody listening, e.g. while a graph is being built from data
+        if self.observers.is_empty() {
+            return;
+        }
+
+        self.pending.push(change);
+
+        if self.depth == 0 {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let changes = std::mem::take(&mut self.pending);
+
+        for (_, observer) in self.observers.iter_mut() {
+            observer(&changes);
+        }
+    }
+}
+
+impl<N: Clone, E: Clone> NetworkGraph<N, E> {
+    /// Call the observer with every change made from now on. Changes made inside a batch
+    /// arrive together once it ends, otherwise each arrives as soon as it is made.
+    pub fn subscribe(&mut self, observer: impl FnMut(&[Change]) + 'static) -> ObserverId {
+        let id = ObserverId(self.observers.next_id);
+        self.observers.next_id += 1;
+        self.observers.observers.push((id, Box::new(observer)));
+
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: ObserverId) {
+        self.observers.observers.retain(|(other, _)| *other != id);
+    }
+
+    /// Make several changes as one step: observers hear nothing until the outermost batch
+    /// finishes, then get everything together, so they never see the graph half way through.
+    pub fn batch<T>(&mut self, changes: impl FnOnce(&mut Self) -> T) -> T {
+        self.hold();
+        let result = changes(self);
+        self.release();
+
+        result
+    }
+
+    /// Start holding changes back, for batches made around the graph rather than on it.
+    pub(super) fn hold(&mut self) {
+        self.observers.depth += 1;
+    }
+
+    /// End a hold, delivering everything held once the outermost ends.
+    pub(super) fn release(&mut self) {
+        self.observers.depth -= 1;
+
+        if self.observers.depth == 0 {
+            self.observers.flush();
+        }
+    }
+
+    pub(super) fn emit(&mut self, change: Change) {
+        self.observers.emit(change);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn record(graph: &mut NetworkGraph<i32, ()>) -> (ObserverId, Rc<RefCell<Vec<Vec<Change>>>>) {
+        let batches = Rc::new(RefCell::new(vec![]));
+        let sink = batches.clone();
+        let id = graph.subscribe(move |changes| sink.borrow_mut().push(changes.to_vec()));
+
+        (id, batches)
+    }
+
+    /// Each change arrives on its own unless it is made inside a batch.
+    #[test]
+    pub fn test_change_events () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        let (_, batches) = record(&mut graph);
+
+        let a = graph.add_node(0);
+        let b = graph.add_node(1);
+        graph.add_edge(&a, &b);
+        graph.set_weight(&a, &b, Some(2.0));
+
+        assert_eq!(batches.borrow().len(), 4);
+        assert_eq!(batches.borrow()[2], vec![Change::EdgeAdded(String::from("0"), String::from("1"))]);
+
+        batches.borrow_mut().clear();
+        graph.remove_node(&a);
+
+        assert_eq!(
+            *batches.borrow(),
+            vec![vec![
+                Change::EdgeRemoved(String::from("0"), String::from("1")),
+                Change::NodeRemoved(String::from("0")),
+            ]]
+        );
+    }
+
+    /// Nested batches only deliver once the outermost ends, and unsubscribing stops delivery.
+    #[test]
+    pub fn test_batches () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        let (id, batches) = record(&mut graph);
+
+        graph.batch(|graph| {
+            let a = graph.add_node(0);
+
+            graph.batch(|graph| {
+                let b = graph.add_node(1);
+                graph.add_edge(&a, &b);
+            });
+
+            assert!(batches.borrow().is_empty());
+            graph.set_node_data(&a, 5);
+        });
+
+        assert_eq!(batches.borrow().len(), 1);
+        assert_eq!(batches.borrow()[0].len(), 4);
+        assert_eq!(batches.borrow()[0][3], Change::NodeUpdated(String::from("0")));
+
+        graph.unsubscribe(id);
+        graph.add_node(2);
+        assert_eq!(batches.borrow().len(), 1);
+    }
+}
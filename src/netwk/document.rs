@@ -0,0 +1,542 @@
+use super::geometry::{Point, Rectangle, Vector};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use super::annotation::{Annotations, Shape};
+use super::attribute::Attributes;
+use super::change::Change;
+use super::format::{DocumentData, GraphData};
+use super::layer::Layers;
+use super::layout::LayoutGraph;
+use super::spatial::{merge, SpatialIndex};
+use super::validate::Report;
+
+/// A whole diagram: the graph, the annotations drawn around it and the layers splitting both up.
+/// Changes which touch more than one of these go through here so the layers stay in step.
+#[derive(Debug)]
+pub struct Document {
+    pub graph: LayoutGraph,
+    annotations: Annotations,
+    layers: Layers,
+    /// Capacities and costs of edges, kept up with the graph on sync
+    attributes: Attributes,
+    /// Counts changes to each part, so snapshots only copy the parts which changed
+    revision: Revision,
+    /// Where everything is, for hit-testing and culling. Catches up with the graph on sync.
+    index: SpatialIndex,
+    /// What the graph has reported since the last sync
+    changes: Rc<RefCell<Vec<Change>>>,
+    /// Problems fixed on loading or by repair, until the UI takes them to show the user
+    repairs: Report,
+    /// What debug builds found wrong with the graph after the last change, left for the user to repair
+    problems: Report,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document::from_data(&DocumentData::default())
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document::default()
+    }
+
+    pub fn from_data(data: &DocumentData) -> Self {
+        let mut graph = LayoutGraph::from_data(&data.graph);
+
+        // Files can be edited by hand or come from older versions, so don't trust them
+        let repairs = graph.repair();
+        let mut layers = data.layers.clone();
+        layers.repair();
+
+        let changes = Rc::new(RefCell::new(vec![]));
+        let queue = changes.clone();
+
+        graph.subscribe(move |batch| queue.borrow_mut().extend_from_slice(batch));
+
+        Document {
+            index: SpatialIndex::new(&graph),
+            graph,
+            annotations: data.annotations.clone(),
+            layers,
+            attributes: data.attributes.clone(),
+            changes,
+            repairs,
+            problems: Report::default(),
+            revision: Revision::default(),
+        }
+    }
+
+    pub fn to_data(&self) -> DocumentData {
+        DocumentData {
+            graph: self.graph.to_data(),
+            annotations: self.annotations.clone(),
+            layers: self.layers.clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    /// The document as it is now, for undo.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            graph: Rc::new(self.graph.to_data()),
+            annotations: Rc::new(self.annotations.clone()),
+            layers: Rc::new(self.layers.clone()),
+            attributes: Rc::new(self.attributes.clone()),
+            revision: self.revision,
+        }
+    }
+
+    /// The document as it is now, sharing with an earlier snapshot of it whichever parts
+    /// haven't changed since.
+    pub fn snapshot_since(&self, last: &Snapshot) -> Snapshot {
+        let (now, then) = (self.revision, last.revision);
+
+        Snapshot {
+            graph: if now.graph == then.graph { last.graph.clone() } else { Rc::new(self.graph.to_data()) },
+            annotations: if now.annotations == then.annotations { last.annotations.clone() } else { Rc::new(self.annotations.clone()) },
+            layers: if now.layers == then.layers { last.layers.clone() } else { Rc::new(self.layers.clone()) },
+            attributes: if now.attributes == then.attributes { last.attributes.clone() } else { Rc::new(self.attributes.clone()) },
+            revision: now,
+        }
+    }
+
+    /// Go back to a snapshot. Revisions carry on from it, so it can be built on again.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut document = Document::from_data(&snapshot.to_data());
+        document.revision = snapshot.revision;
+
+        document
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        self.revision.annotations += 1;
+        &mut self.annotations
+    }
+
+    pub fn layers(&self) -> &Layers {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Layers {
+        self.revision.layers += 1;
+        &mut self.layers
+    }
+
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut Attributes {
+        self.revision.attributes += 1;
+        &mut self.attributes
+    }
+
+    pub fn index(&self) -> &SpatialIndex {
+        &self.index
+    }
+
+    /// Everything repaired since this was last called, so the UI can tell the user about it.
+    pub fn take_repairs(&mut self) -> Report {
+        std::mem::take(&mut self.repairs)
+    }
+
+    /// Whatever is wrong with the graph as of the last sync. Only checked in debug builds.
+    pub fn problems(&self) -> &Report {
+        &self.problems
+    }
+
+    /// Fix whatever is wrong with the graph, keeping what that was for take_repairs.
+    /// Returns the area which needs drawing again.
+    pub fn repair(&mut self) -> Option<Rectangle> {
+        let report = self.graph.repair();
+        self.layers_mut().repair();
+        self.problems = Report::default();
+
+        if report.is_ok() {
+            return None;
+        }
+
+        // Repairs can go deeper than the changes reported, so start the index afresh
+        let changes = std::mem::take(&mut *self.changes.borrow_mut());
+        let before = self.index.extent();
+        self.index = SpatialIndex::new(&self.graph);
+        self.attributes.apply(&self.graph, &changes);
+        self.revision.graph += 1;
+        self.revision.attributes += 1;
+        self.repairs.violations.extend(report.violations);
+
+        merge(before, self.index.extent())
+    }
+
+    /// Bring the index and edge attributes up to date with everything done to the graph since the last sync.
+    /// Returns the area which needs drawing again, or None if nothing changed. Debug builds
+    /// also check the graph still holds together after every change, leaving anything wrong
+    /// in problems rather than fixing it behind the user's back.
+    pub fn sync(&mut self) -> Option<Rectangle> {
+        let changes = std::mem::take(&mut *self.changes.borrow_mut());
+
+        if changes.is_empty() {
+            return None;
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            self.problems = self.graph.validate();
+        }
+
+        let dirty = self.index.apply(&self.graph, &changes);
+        self.attributes.apply(&self.graph, &changes);
+        self.revision.graph += 1;
+        self.revision.attributes += 1;
+
+        // Arrows and group boxes could be anywhere
+        if changes.iter().any(|change| matches!(change, Change::DirectedChanged | Change::GroupsChanged)) {
+            return merge(dirty, self.index.extent());
+        }
+
+        dirty
+    }
+
+    /// Add a node on the active layer, returning its name.
+    pub fn add_node(&mut self, pos: Point) -> String {
+        let name = self.graph.add_node(pos).name().clone();
+        let active = self.layers.active();
+        self.layers_mut().assign_node(&name, active);
+
+        name
+    }
+
+    /// Remove a node unless it is on a hidden or locked layer. Returns whether it was removed.
+    pub fn remove_node(&mut self, name: &str) -> bool {
+        match self.graph.get_node(name).filter(|_| self.layers.node_interactive(name)) {
+            Some(node) => {
+                self.graph.remove_node(&node);
+                self.layers_mut().forget_node(name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns false if the new name is already taken.
+    pub fn rename_node(&mut self, name: &str, new_name: &str) -> bool {
+        let renamed = self
+            .graph
+            .get_node(name)
+            .and_then(|node| self.graph.rename_node(&node, new_name.to_string()));
+
+        if renamed.is_some() {
+            self.layers_mut().rename_node(name, new_name);
+        }
+
+        renamed.is_some()
+    }
+
+    /// Connect two nodes, straight or curved, putting the edge on the active layer.
+    pub fn add_edge(&mut self, from: &str, to: &str, control: Option<Point>) {
+        if let (Some(src), Some(dst)) = (self.graph.get_node(from), self.graph.get_node(to)) {
+            match control {
+                Some(control) => self.graph.add_curve(&src, &dst, control),
+                None => self.graph.add_edge(&src, &dst),
+            }
+
+            let active = self.layers.active();
+            self.layers_mut().assign_edge(from, to, active);
+        }
+    }
+
+    pub fn remove_edge(&mut self, from: &str, to: &str) {
+        if let (Some(src), Some(dst)) = (self.graph.get_node(from), self.graph.get_node(to)) {
+            self.graph.remove_edge(&src, &dst);
+            self.layers_mut().forget_edge(from, to);
+        }
+    }
+
+    pub fn add_annotation(&mut self, shape: Shape) -> usize {
+        let id = self.annotations_mut().add(shape);
+        let active = self.layers.active();
+        self.layers_mut().assign_annotation(id, active);
+
+        id
+    }
+
+    pub fn remove_annotation(&mut self, id: usize) {
+        self.annotations_mut().remove(id);
+        self.layers_mut().forget_annotation(id);
+    }
+
+    /// Paste a copy of the data onto the active layer, returning the new node names.
+    pub fn insert(&mut self, data: &GraphData, offset: Vector) -> Vec<String> {
+        let names = self.graph.insert(data, offset);
+        let active = self.layers.active();
+        let edges = self.graph.extract(&names.iter().cloned().collect()).edges;
+        let layers = self.layers_mut();
+
+        for name in names.iter() {
+            layers.assign_node(name, active);
+        }
+
+        for edge in edges {
+            layers.assign_edge(&edge.from, &edge.to, active);
+        }
+
+        names
+    }
+
+    /// Put the named nodes, and the edges running between them, onto a layer.
+    pub fn move_to_layer(&mut self, names: &HashSet<String>, layer: usize) {
+        let edges = self.graph.extract(names).edges;
+        let layers = self.layers_mut();
+
+        for name in names.iter() {
+            layers.assign_node(name, layer);
+        }
+
+        for edge in edges {
+            layers.assign_edge(&edge.from, &edge.to, layer);
+        }
+    }
+
+    /// Names of every node on a visible layer.
+    pub fn visible_nodes(&self) -> HashSet<String> {
+        self.graph
+            .node_list()
+            .iter()
+            .filter(|node| self.layers.node_visible(node.name()))
+            .map(|node| node.name().clone())
+            .collect()
+    }
+
+    /// Names of every node which can be picked, those on layers which are visible and not locked.
+    pub fn interactive_nodes(&self) -> HashSet<String> {
+        self.graph
+            .node_list()
+            .iter()
+            .filter(|node| self.layers.node_interactive(node.name()))
+            .map(|node| node.name().clone())
+            .collect()
+    }
+
+    /// Like LayoutGraph::extract, but leaving out anything on a hidden layer.
+    pub fn export(&self, names: &HashSet<String>) -> GraphData {
+        let visible: HashSet<String> = names.intersection(&self.visible_nodes()).cloned().collect();
+        let mut data = self.graph.extract(&visible);
+
+        data.edges.retain(|edge| self.layers.edge_visible(&edge.from, &edge.to));
+
+        data
+    }
+
+    /// Everything which is currently visible.
+    pub fn export_visible(&self) -> GraphData {
+        self.export(&self.visible_nodes())
+    }
+}
+
+/// How many times each part of a document has been changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Revision {
+    graph: u64,
+    annotations: u64,
+    layers: u64,
+    attributes: u64,
+}
+
+/// A copy of a document to come back to. Copies taken one after another share the parts
+/// which didn't change in between, so an edit only costs a copy of what it touched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    graph: Rc<GraphData>,
+    annotations: Rc<Annotations>,
+    layers: Rc<Layers>,
+    attributes: Rc<Attributes>,
+    revision: Revision,
+}
+
+impl Snapshot {
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    /// Whether the graph differs from that of an earlier snapshot of the same document.
+    pub fn graph_changed_since(&self, earlier: &Snapshot) -> bool {
+        self.revision.graph != earlier.revision.graph
+    }
+
+    pub fn to_data(&self) -> DocumentData {
+        DocumentData {
+            graph: (*self.graph).clone(),
+            annotations: (*self.annotations).clone(),
+            layers: (*self.layers).clone(),
+            attributes: (*self.attributes).clone(),
+        }
+    }
+
+    /// Whether this is what the data holds, without copying either.
+    pub fn matches(&self, data: &DocumentData) -> bool {
+        *self.graph == data.graph
+            && *self.annotations == data.annotations
+            && *self.layers == data.layers
+            && *self.attributes == data.attributes
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::attribute::EdgeAttributes;
+    use crate::netwk::geometry::Size;
+    use crate::netwk::Connection;
+
+    /// Collapsing a group and expanding it again gives its edges back their attributes, while
+    /// deleting the collapsed group takes them away.
+    #[test]
+    pub fn test_collapse_keeps_attributes () {
+        let mut document = Document::new();
+        let a = document.add_node(Point::new(0.0, 0.0));
+        let b = document.add_node(Point::new(10.0, 0.0));
+        let c = document.add_node(Point::new(100.0, 0.0));
+        document.add_edge(&a, &b, None);
+        document.add_edge(&b, &c, None);
+
+        let flow = EdgeAttributes { capacity: Some(10.0), cost: Some(2.0) };
+        document.attributes.set_edge(&a, &b, 0, flow);
+        document.attributes.set_edge(&b, &c, 0, flow);
+
+        let members: HashSet<String> = [a.clone(), b.clone()].into_iter().collect();
+        document.graph.create_group(String::from("Site"), &members);
+        document.graph.collapse_group("Site");
+        document.sync();
+        document.graph.expand_group("Site");
+        document.sync();
+
+        assert_eq!(document.attributes.edge(&a, &b, 0), flow);
+        assert_eq!(document.attributes.edge(&b, &c, 0), flow);
+
+        document.graph.collapse_group("Site");
+        document.sync();
+        let summary = document.graph.get_node("Site").unwrap();
+        document.graph.remove_node(&summary);
+        document.sync();
+
+        assert_eq!(document.attributes, Attributes::default());
+    }
+
+    /// Debug builds find what an edit left broken but only fix it when asked.
+    #[test]
+    pub fn test_repair_on_request () {
+        let mut document = Document::new();
+        let a = document.add_node(Point::new(0.0, 0.0));
+        let b = document.add_node(Point::new(10.0, 0.0));
+
+        let (from, to) = (document.graph.get_node(&a).unwrap(), document.graph.get_node(&b).unwrap());
+        from.edges.borrow_mut().push(Connection { dst: to.clone(), weight: None, data: (), outgoing: true });
+        document.graph.move_node(&to, Point::new(20.0, 0.0));
+        document.sync();
+
+        assert_eq!(document.problems().is_ok(), !cfg!(debug_assertions));
+        assert!(!document.graph.validate().is_ok());
+
+        assert!(document.repair().is_some());
+        assert!(document.problems().is_ok());
+        assert!(document.graph.validate().is_ok());
+        assert_eq!(document.take_repairs().violations.len(), 1);
+    }
+
+    /// Nodes on a locked layer can't be picked out or removed.
+    #[test]
+    pub fn test_locked_nodes () {
+        let mut document = Document::new();
+        let a = document.add_node(Point::new(0.0, 0.0));
+
+        let locked = document.layers.add(String::from("Locked"));
+        document.layers.set_active(locked);
+        let b = document.add_node(Point::new(10.0, 0.0));
+        document.layers.get_mut(locked).unwrap().locked = true;
+
+        assert_eq!(document.visible_nodes().len(), 2);
+        assert_eq!(document.interactive_nodes(), [a.clone()].into_iter().collect());
+
+        assert!(!document.remove_node(&b));
+        assert!(document.remove_node(&a));
+        assert_eq!(document.graph.node_list().len(), 1);
+    }
+
+    /// A snapshot taken after an edit shares the parts the edit didn't touch with the one before.
+    #[test]
+    pub fn test_snapshot_sharing () {
+        let mut document = Document::new();
+        document.add_node(Point::new(0.0, 0.0));
+        document.sync();
+        let first = document.snapshot();
+
+        document.add_annotation(Shape::Text { position: Point::new(0.0, 0.0), content: String::from("Rack"), size: 16.0 });
+        let second = document.snapshot_since(&first);
+
+        assert!(Rc::ptr_eq(&first.graph, &second.graph));
+        assert!(!Rc::ptr_eq(&first.annotations, &second.annotations));
+        assert!(!second.graph_changed_since(&first));
+        assert!(second.matches(&document.to_data()));
+
+        let restored = Document::from_snapshot(&first);
+        assert_eq!(restored.to_data(), first.to_data());
+        assert_eq!(restored.snapshot_since(&first), first);
+    }
+
+    /// Hidden layers are left out of exports but kept in saved data.
+    #[test]
+    pub fn test_export_skips_hidden () {
+        let mut document = Document::new();
+        let a = document.add_node(Point::new(0.0, 0.0));
+        let b = document.add_node(Point::new(10.0, 0.0));
+
+        let hidden = document.layers.add(String::from("Hidden"));
+        document.layers.set_active(hidden);
+
+        let c = document.add_node(Point::new(20.0, 0.0));
+        document.add_edge(&a, &b, None);
+        document.add_edge(&b, &c, None);
+
+        document.layers.get_mut(hidden).unwrap().visible = false;
+
+        let exported = document.export_visible();
+        assert_eq!(exported.nodes.len(), 2);
+        assert!(exported.edges.is_empty());
+
+        let saved = document.to_data();
+        assert_eq!(saved.graph.nodes.len(), 3);
+        assert_eq!(Document::from_data(&saved).export_visible(), exported);
+    }
+
+    /// Edits made straight on the graph reach the index on the next sync.
+    #[test]
+    pub fn test_sync () {
+        let mut document = Document::new();
+        let a = document.add_node(Point::new(0.0, 0.0));
+        let b = document.add_node(Point::new(10.0, 0.0));
+        document.add_edge(&a, &b, None);
+
+        assert!(document.sync().is_some());
+        assert!(document.sync().is_none());
+
+        let node = document.graph.get_node(&b).unwrap();
+        document.graph.move_node(&node, Point::new(10.0, 40.0));
+
+        assert_eq!(document.sync(), Some(Rectangle::new(Point::new(0.0, 0.0), Size::new(10.0, 40.0))));
+        assert!(document.index().nearest_node(Point::new(10.0, 40.0), 1.0, |_| true).is_some());
+        assert_eq!(document.index().edges_in(Rectangle::new(Point::new(9.0, 39.0), Size::new(2.0, 2.0))).len(), 1);
+    }
+}
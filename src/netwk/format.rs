@@ -0,0 +1,125 @@
+use super::geometry::{Point, Rectangle, Size};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::annotation::Annotations;
+use super::attribute::Attributes;
+use super::group::Group;
+use super::layer::Layers;
+
+/// Plain, serialisable copy of a graph (or part of one).
+/// Nodes refer to each other by name so this can be moved between graphs, written to the
+/// clipboard as JSON, or dumped as DOT for other tools.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GraphData {
+    pub directed: bool,
+    pub nodes: Vec<NodeData>,
+    pub edges: Vec<EdgeData>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub groups: Vec<Group>,
+}
+
+/// Everything saved in a diagram file.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DocumentData {
+    pub graph: GraphData,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub annotations: Annotations,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub layers: Layers,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NodeData {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Each edge appears once, from the end it was drawn from.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EdgeData {
+    pub from: String,
+    pub to: String,
+    pub weight: Option<f32>,
+    pub control: Option<(f32, f32)>,
+}
+
+impl NodeData {
+    pub fn position(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+}
+
+impl EdgeData {
+    pub fn control_point(&self) -> Option<Point> {
+        self.control.map(|(x, y)| Point::new(x, y))
+    }
+}
+
+impl GraphData {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Graph data is always representable as JSON")
+    }
+
+    /// Parse text produced by `to_json`. Anything else gives None.
+    #[cfg(feature = "serde")]
+    pub fn from_json(text: &str) -> Option<GraphData> {
+        serde_json::from_str(text).ok()
+    }
+
+    /// Graphviz representation, keeping positions in the `pos` attribute.
+    pub fn to_dot(&self) -> String {
+        let (keyword, arrow) = if self.directed { ("digraph", "->") } else { ("graph", "--") };
+        let mut dot = format!("{} ntwk {{\n", keyword);
+
+        for node in self.nodes.iter() {
+            dot += &format!("    {:?} [pos=\"{},{}\"];\n", node.name, node.x, node.y);
+        }
+
+        for edge in self.edges.iter() {
+            match edge.weight {
+                Some(weight) => dot += &format!("    {:?} {} {:?} [weight={}];\n", edge.from, arrow, edge.to, weight),
+                None => dot += &format!("    {:?} {} {:?};\n", edge.from, arrow, edge.to),
+            }
+        }
+
+        dot + "}\n"
+    }
+
+    /// Bounding box around the node positions, if there are any nodes.
+    pub fn bounds(&self) -> Option<Rectangle> {
+        let first = self.nodes.first()?.position();
+        let (mut min, mut max) = (first, first);
+
+        for node in self.nodes.iter() {
+            min = Point::new(min.x.min(node.x), min.y.min(node.y));
+            max = Point::new(max.x.max(node.x), max.y.max(node.y));
+        }
+
+        Some(Rectangle::new(min, Size::new(max.x - min.x, max.y - min.y)))
+    }
+}
+
+impl DocumentData {
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Documents are always representable as JSON")
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(text: &str) -> Option<DocumentData> {
+        serde_json::from_str(text).ok()
+    }
+}
@@ -0,0 +1,119 @@
+use super::format::{EdgeData, GraphData, NodeData};
+
+// Graphs made up for testing and benchmarking. The same seed always gives the same graph,
+// so timings taken on different builds can be compared.
+
+/// Roughly square mesh of `nodes` nodes 40 units apart, each nudged a little off the grid.
+/// Most neighbours on the grid are joined, with the odd longer edge (some of them curved)
+/// to a node a few places away. Edges get small whole number weights.
+pub fn benchmark_graph(nodes: usize, seed: u64) -> GraphData {
+    const SPACING: f32 = 40.0;
+
+    let mut random = Random::new(seed);
+    let columns = (nodes as f32).sqrt().ceil().max(1.0) as usize;
+    let mut data = GraphData::default();
+
+    for i in 0..nodes {
+        data.nodes.push(NodeData {
+            name: i.to_string(),
+            x: (i % columns) as f32 * SPACING + random.between(-10.0, 10.0),
+            y: (i / columns) as f32 * SPACING + random.between(-10.0, 10.0),
+        });
+    }
+
+    let edge = |from: usize, to: usize, random: &mut Random| EdgeData {
+        from: from.to_string(),
+        to: to.to_string(),
+        weight: Some(random.between(1.0, 10.0).floor()),
+        control: None,
+    };
+
+    for i in 0..nodes {
+        let right = i + 1;
+        let down = i + columns;
+
+        if right % columns != 0 && right < nodes && random.chance(0.7) {
+            data.edges.push(edge(i, right, &mut random));
+        }
+
+        if down < nodes && random.chance(0.7) {
+            data.edges.push(edge(i, down, &mut random));
+        }
+
+        if random.chance(0.05) {
+            let reach = 5 * columns + 5;
+            let other = (i + 1 + random.below(reach)).min(nodes - 1);
+
+            if other != i {
+                let mut long = edge(i, other, &mut random);
+
+                if random.chance(0.3) {
+                    let (from, to) = (&data.nodes[i], &data.nodes[other]);
+                    long.control = Some(((from.x + to.x) / 2.0 + SPACING, (from.y + to.y) / 2.0 - SPACING));
+                }
+
+                data.edges.push(long);
+            }
+        }
+    }
+
+    data
+}
+
+/// xorshift64*, which is plenty for laying out test graphs and saves a dependency.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift can never leave
+        Random { state: seed.max(1) }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in [0, 1).
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn between(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.unit()
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f32) -> bool {
+        self.unit() < probability
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::layout::LayoutGraph;
+
+    /// Same seed, same graph, and every edge joins nodes which exist.
+    #[test]
+    pub fn test_benchmark_graph () {
+        let data = benchmark_graph(2500, 7);
+
+        assert_eq!(data, benchmark_graph(2500, 7));
+        assert_ne!(data, benchmark_graph(2500, 8));
+        assert_eq!(data.nodes.len(), 2500);
+        assert!(data.edges.len() > 2500);
+
+        let graph = LayoutGraph::from_data(&data);
+        assert_eq!(graph.to_data().edges.len(), data.edges.len());
+    }
+}
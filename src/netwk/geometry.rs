@@ -0,0 +1,196 @@
+//! The handful of geometry types the graph model needs. They are the model's own whatever the
+//! features, so the library never has to build iced; the canvas converts them to iced's where
+//! it draws.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, Sub};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Size {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rectangle {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Point {
+    pub const ORIGIN: Point = Point { x: 0.0, y: 0.0 };
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+
+    pub fn distance(&self, to: Point) -> f32 {
+        (self.x - to.x).hypot(self.y - to.y)
+    }
+}
+
+impl Vector {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Vector { x, y }
+    }
+}
+
+impl Size {
+    pub const fn new(width: f32, height: f32) -> Self {
+        Size { width, height }
+    }
+}
+
+impl Rectangle {
+    pub fn new(top_left: Point, size: Size) -> Self {
+        Rectangle { x: top_left.x, y: top_left.y, width: size.width, height: size.height }
+    }
+
+    pub fn position(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.x <= point.x && point.x <= self.x + self.width && self.y <= point.y && point.y <= self.y + self.height
+    }
+}
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+
+    pub const fn from_rgb(r: f32, g: f32, b: f32) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+}
+
+impl Add<Vector> for Point {
+    type Output = Point;
+
+    fn add(self, vector: Vector) -> Point {
+        Point::new(self.x + vector.x, self.y + vector.y)
+    }
+}
+
+impl Sub<Vector> for Point {
+    type Output = Point;
+
+    fn sub(self, vector: Vector) -> Point {
+        Point::new(self.x - vector.x, self.y - vector.y)
+    }
+}
+
+impl Sub<Point> for Point {
+    type Output = Vector;
+
+    fn sub(self, point: Point) -> Vector {
+        Vector::new(self.x - point.x, self.y - point.y)
+    }
+}
+
+impl Add<Vector> for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+/// The canvas draws with iced's types, so the model's turn into them, and back for whatever
+/// the user points at.
+#[cfg(feature = "gui")]
+mod gui {
+    use super::{Color, Point, Rectangle, Size, Vector};
+
+    impl From<Point> for iced::Point {
+        fn from(point: Point) -> Self {
+            iced::Point::new(point.x, point.y)
+        }
+    }
+
+    impl From<iced::Point> for Point {
+        fn from(point: iced::Point) -> Self {
+            Point::new(point.x, point.y)
+        }
+    }
+
+    impl From<Vector> for iced::Vector {
+        fn from(vector: Vector) -> Self {
+            iced::Vector::new(vector.x, vector.y)
+        }
+    }
+
+    impl From<iced::Vector> for Vector {
+        fn from(vector: iced::Vector) -> Self {
+            Vector::new(vector.x, vector.y)
+        }
+    }
+
+    impl From<Size> for iced::Size {
+        fn from(size: Size) -> Self {
+            iced::Size::new(size.width, size.height)
+        }
+    }
+
+    impl From<iced::Size> for Size {
+        fn from(size: iced::Size) -> Self {
+            Size::new(size.width, size.height)
+        }
+    }
+
+    impl From<Rectangle> for iced::Rectangle {
+        fn from(rectangle: Rectangle) -> Self {
+            iced::Rectangle::new(rectangle.position().into(), rectangle.size().into())
+        }
+    }
+
+    impl From<iced::Rectangle> for Rectangle {
+        fn from(rectangle: iced::Rectangle) -> Self {
+            Rectangle::new(rectangle.position().into(), rectangle.size().into())
+        }
+    }
+
+    impl From<Color> for iced::Color {
+        fn from(color: Color) -> Self {
+            iced::Color { r: color.r, g: color.g, b: color.b, a: color.a }
+        }
+    }
+
+    impl From<iced::Color> for Color {
+        fn from(color: iced::Color) -> Self {
+            Color { r: color.r, g: color.g, b: color.b, a: color.a }
+        }
+    }
+}
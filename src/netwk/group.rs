@@ -0,0 +1,456 @@
+use super::geometry::{Point, Rectangle, Size, Vector};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::format::{EdgeData, GraphData};
+use super::layout::{LayoutGraph, LayoutNode};
+use super::change::Change;
+use super::{Node, NetworkGraph};
+
+/// A named cluster of nodes, e.g. a site or a DMZ.
+/// Nodes belong to at most one group. A collapsed group's members are taken out of the graph and
+/// replaced by a single summary node which picks up every edge leaving the group. Edges between
+/// two collapsed groups run between their summary nodes, and each group remembers the edge
+/// by its real ends so it comes back whichever group is expanded first.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Group {
+    name: String,
+    members: Vec<String>,
+    collapsed: Option<Collapsed>,
+}
+
+/// Everything needed to put a collapsed group back the way it was.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Collapsed {
+    summary: String,
+    centre: (f32, f32),
+    inner: GraphData,
+    outer: Vec<EdgeData>,
+}
+
+impl Group {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn members(&self) -> &Vec<String> {
+        &self.members
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed.is_some()
+    }
+
+    /// Name of the node standing in for the group while it is collapsed.
+    pub fn summary(&self) -> Option<&String> {
+        self.collapsed.as_ref().map(|collapsed| &collapsed.summary)
+    }
+}
+
+impl<N: Clone, E: Clone> NetworkGraph<N, E> {
+    pub fn groups(&self) -> &Vec<Group> {
+        &self.groups
+    }
+
+    pub fn get_group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// The group a node is drawn as part of, either as a member or as its summary.
+    pub fn group_of(&self, node: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| {
+            group.members.iter().any(|member| member == node) || group.summary().map(|s| s == node).unwrap_or(false)
+        })
+    }
+
+    /// Start a new group from the given nodes, taking them out of any group they were in.
+    /// Returns false if the name is taken or none of the nodes exist.
+    pub fn create_group(&mut self, name: String, members: &HashSet<String>) -> bool {
+        let members: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|node| members.contains(&node.name))
+            .map(|node| node.name.clone())
+            .collect();
+
+        if members.is_empty() || self.get_group(&name).is_some() {
+            return false;
+        }
+
+        for group in self.groups.iter_mut().filter(|group| group.collapsed.is_none()) {
+            group.members.retain(|member| !members.contains(member));
+        }
+        self.groups.retain(|group| !group.members.is_empty());
+
+        self.groups.push(Group { name, members, collapsed: None });
+        self.emit(Change::GroupsChanged);
+
+        true
+    }
+
+    /// Called when a node is removed. Removing a summary node throws its whole group away.
+    pub(super) fn forget_group_member(&mut self, node: &str) {
+        self.groups.retain(|group| group.summary().map(|s| s != node).unwrap_or(true));
+
+        for group in self.groups.iter_mut().filter(|group| group.collapsed.is_none()) {
+            group.members.retain(|member| member != node);
+        }
+        self.groups.retain(|group| !group.members.is_empty());
+    }
+
+    /// Drop members which aren't in the graph, then any group left standing for nothing.
+    pub(super) fn prune_groups(&mut self) {
+        let lookup = &self.lookup;
+
+        self.groups.retain(|group| group.summary().map(|s| lookup.contains_key(s)).unwrap_or(true));
+
+        for group in self.groups.iter_mut().filter(|group| group.collapsed.is_none()) {
+            group.members.retain(|member| lookup.contains_key(member));
+        }
+        self.groups.retain(|group| !group.members.is_empty());
+    }
+
+    pub(super) fn rename_group_member(&mut self, old: &str, new: &str) {
+        for group in self.groups.iter_mut() {
+            for member in group.members.iter_mut().filter(|member| member.as_str() == old) {
+                *member = new.to_string();
+            }
+
+            if let Some(collapsed) = group.collapsed.as_mut() {
+                if collapsed.summary == old {
+                    collapsed.summary = new.to_string();
+                }
+
+                // Edges out of the group remember what they lead to by name
+                for edge in collapsed.outer.iter_mut() {
+                    for end in [&mut edge.from, &mut edge.to] {
+                        if end == old {
+                            *end = new.to_string();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Groups are drawn as a box around their members, so everything past membership needs positions.
+/// Collapsed members are put away as plain data, so only a graph carrying nothing else can collapse.
+impl LayoutGraph {
+    /// Dissolve a group, leaving its nodes in place.
+    pub fn remove_group(&mut self, name: &str) {
+        self.batch(|layout| {
+            layout.expand_group(name);
+            layout.graph.groups.retain(|group| group.name != name);
+            layout.graph.emit(Change::GroupsChanged);
+        })
+    }
+
+    /// Box around the group's visible nodes. Collapsed groups have none.
+    pub fn group_bounds(&self, name: &str) -> Option<Rectangle> {
+        let group = self.get_group(name).filter(|group| !group.is_collapsed())?;
+        let mut positions = group.members.iter().filter(|member| self.get_node(member).is_some()).map(|member| self.position(member));
+
+        let first = positions.next()?;
+        let (min, max) = positions.fold((first, first), |(min, max), pos| {
+            (Point::new(min.x.min(pos.x), min.y.min(pos.y)), Point::new(max.x.max(pos.x), max.y.max(pos.y)))
+        });
+
+        Some(Rectangle::new(min, Size::new(max.x - min.x, max.y - min.y)))
+    }
+
+    /// Expanded group whose box, grown by padding, contains pos.
+    pub fn get_group_at(&self, pos: Point, padding: f32) -> Option<&Group> {
+        self.groups.iter().filter(|group| !group.is_collapsed()).find(|group| {
+            self.group_bounds(&group.name)
+                .map(|bounds| {
+                    pos.x >= bounds.x - padding
+                        && pos.x <= bounds.x + bounds.width + padding
+                        && pos.y >= bounds.y - padding
+                        && pos.y <= bounds.y + bounds.height + padding
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// The node drawn for a node name: the node itself, or the summary of the collapsed group it is hidden in.
+    fn stand_in(&self, name: &str) -> Option<Arc<LayoutNode>> {
+        self.get_node(name).or_else(|| {
+            self.groups
+                .iter()
+                .filter(|group| group.members.iter().any(|member| member == name))
+                .find_map(|group| group.summary().and_then(|summary| self.get_node(summary)))
+        })
+    }
+
+    /// Move every node in the group (or its summary node) by delta.
+    pub fn move_group(&mut self, name: &str, delta: Vector) {
+        let names = match self.get_group(name) {
+            Some(group) => match group.summary() {
+                Some(summary) => vec![summary.clone()],
+                None => group.members.clone(),
+            },
+            None => return,
+        };
+
+        self.batch(|layout| {
+            for name in names {
+                if let Some(node) = layout.get_node(&name) {
+                    layout.move_node(&node, layout.position(&name) + delta);
+                }
+            }
+        })
+    }
+
+    /// Swap the group's members for one summary node at their centre. Edges leaving the group
+    /// are moved onto the summary node, edges inside it are put away with the members.
+    pub fn collapse_group(&mut self, name: &str) {
+        let index = match self.groups.iter().position(|group| group.name == name && !group.is_collapsed()) {
+            Some(index) => index,
+            None => return,
+        };
+
+        // Taken out while the members are removed so remove_node doesn't dissolve it
+        let mut group = self.graph.groups.remove(index);
+        let members: HashSet<String> = group.members.iter().cloned().collect();
+        let nodes: Vec<_> = self.nodes.iter().filter(|node| members.contains(&node.name)).cloned().collect();
+
+        if nodes.is_empty() {
+            self.graph.groups.insert(index, group);
+            return;
+        }
+
+        let count = nodes.len() as f32;
+        let centre = Point::new(
+            nodes.iter().map(|node| self.position(&node.name).x).sum::<f32>() / count,
+            nodes.iter().map(|node| self.position(&node.name).y).sum::<f32>() / count,
+        );
+
+        let inner = self.extract(&members);
+        let summaries: HashSet<&String> = self.groups.iter().filter_map(|group| group.summary()).collect();
+
+        // Edges to another collapsed group are taken from what it remembers, not its summary node
+        let mut outer: Vec<EdgeData> = self
+            .groups
+            .iter()
+            .filter_map(|group| group.collapsed.as_ref())
+            .flat_map(|collapsed| collapsed.outer.iter())
+            .filter(|edge| members.contains(&edge.from) || members.contains(&edge.to))
+            .cloned()
+            .collect();
+
+        for node in nodes.iter() {
+            let controls = self.connection_controls(node);
+
+            for (conn, control) in node.edges.borrow().iter().zip(controls).filter(|(conn, _)| {
+                !members.contains(&conn.dst.name) && !summaries.contains(&conn.dst.name)
+            }) {
+                let (from, to) = if conn.outgoing {
+                    (node.name.clone(), conn.dst.name.clone())
+                } else {
+                    (conn.dst.name.clone(), node.name.clone())
+                };
+
+                outer.push(EdgeData { from, to, weight: conn.weight, control: control.map(|ctl| (ctl.x, ctl.y)) });
+            }
+        }
+
+        self.batch(|layout| {
+            for node in nodes.iter() {
+                layout.remove_node(node);
+            }
+
+            let summary_name = if layout.get_node(name).is_none() { name.to_string() } else { layout.graph.fresh_name() };
+            let summary = Node::new(summary_name.clone(), ());
+            layout.push_node(summary.clone(), centre);
+
+            for edge in outer.iter() {
+                if members.contains(&edge.from) {
+                    if let Some(other) = layout.stand_in(&edge.to) {
+                        layout.add_edge_with(&summary, &other, edge.weight, None);
+                    }
+                } else if let Some(other) = layout.stand_in(&edge.from) {
+                    layout.add_edge_with(&other, &summary, edge.weight, None);
+                }
+            }
+
+            group.collapsed = Some(Collapsed { summary: summary_name, centre: (centre.x, centre.y), inner, outer });
+            layout.graph.groups.insert(index, group);
+            layout.graph.emit(Change::GroupsChanged);
+        })
+    }
+
+    /// Put a collapsed group's members back, shifted by however far the summary node was moved,
+    /// and reconnect the edges which left the group.
+    pub fn expand_group(&mut self, name: &str) {
+        let collapsed = match self.graph.groups.iter_mut().find(|group| group.name == name) {
+            Some(group) => match group.collapsed.take() {
+                Some(collapsed) => collapsed,
+                None => return,
+            },
+            None => return,
+        };
+
+        self.batch(|layout| {
+            let centre = Point::new(collapsed.centre.0, collapsed.centre.1);
+            let offset = match layout.get_node(&collapsed.summary) {
+                Some(summary) => {
+                    let offset = layout.position(&collapsed.summary) - centre;
+                    // The group has already let go of the summary, so removing it leaves the group be
+                    layout.remove_node(&summary);
+                    offset
+                }
+                None => Vector::new(0.0, 0.0),
+            };
+
+            let renamed = layout.insert_with(&collapsed.inner, offset, true);
+
+            for edge in collapsed.outer.iter() {
+                let from = renamed.get(&edge.from).unwrap_or(&edge.from);
+                let to = renamed.get(&edge.to).unwrap_or(&edge.to);
+
+                // The far end may still be inside another collapsed group
+                if let (Some(src), Some(dst)) = (layout.stand_in(from), layout.stand_in(to)) {
+                    let control = if src.name() == from && dst.name() == to { edge.control_point() } else { None };
+                    layout.add_edge_with(&src, &dst, edge.weight, control);
+                }
+            }
+
+            // Other collapsed groups remember edges to the members by name, which may have changed
+            for other in layout.graph.groups.iter_mut().filter_map(|group| group.collapsed.as_mut()) {
+                for edge in other.outer.iter_mut() {
+                    if let Some(new) = renamed.get(&edge.from) {
+                        edge.from = new.clone();
+                    }
+                    if let Some(new) = renamed.get(&edge.to) {
+                        edge.to = new.clone();
+                    }
+                }
+            }
+
+            if let Some(group) = layout.graph.groups.iter_mut().find(|group| group.name == name) {
+                group.members = collapsed.inner.nodes.iter().map(|node| renamed[&node.name].clone()).collect();
+            }
+            layout.graph.emit(Change::GroupsChanged);
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Collapsing then expanding should give back the same nodes and edges.
+    #[test]
+    pub fn test_collapse_expand () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(10.0, 0.0));
+        graph.add_node(Point::new(100.0, 0.0));
+
+        let (a, b, c) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap(), graph.get_node("2").unwrap());
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+
+        let members: HashSet<String> = ["0", "1"].iter().map(|name| name.to_string()).collect();
+        assert!(graph.create_group(String::from("Site A"), &members));
+
+        let before = graph.to_data();
+        graph.collapse_group("Site A");
+
+        let summary = graph.get_node("Site A").unwrap();
+        assert_eq!(graph.node_list().len(), 2);
+        assert_eq!(graph.position(summary.name()), Point::new(5.0, 0.0));
+        assert_eq!(summary.edges.borrow()[0].destination().name(), "2");
+
+        graph.expand_group("Site A");
+
+        assert_eq!(graph.node_list().len(), 3);
+        assert!(graph.get_node("Site A").is_none());
+        assert_eq!(graph.to_data().edges.len(), before.edges.len());
+        assert_eq!(graph.get_group("Site A").unwrap().members().len(), 2);
+    }
+
+    /// An edge between two collapsed groups runs between their summaries, and comes back as it
+    /// was whichever group is expanded first.
+    #[test]
+    pub fn test_adjacent_groups () {
+        for first in ["A", "B"] {
+            let mut graph = LayoutGraph::new();
+            for x in [0.0, 10.0, 100.0, 110.0] {
+                graph.add_node(Point::new(x, 0.0));
+            }
+
+            let node = |graph: &LayoutGraph, name: &str| graph.get_node(name).unwrap();
+            graph.add_edge(&node(&graph, "0"), &node(&graph, "1"));
+            graph.add_edge(&node(&graph, "1"), &node(&graph, "2"));
+            graph.add_edge(&node(&graph, "2"), &node(&graph, "3"));
+
+            let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<HashSet<String>>();
+            graph.create_group(String::from("A"), &names(&["0", "1"]));
+            graph.create_group(String::from("B"), &names(&["2", "3"]));
+
+            let mut before = graph.to_data().edges;
+            graph.collapse_group("A");
+            graph.collapse_group("B");
+
+            assert_eq!(graph.node_list().len(), 2);
+            assert_eq!(node(&graph, "A").edges.borrow()[0].destination().name(), "B");
+
+            let second = if first == "A" { "B" } else { "A" };
+            graph.expand_group(first);
+
+            // The expanded group's member is joined to the other group's summary
+            let (member, summary) = if first == "A" { ("1", "B") } else { ("2", "A") };
+            assert_eq!(node(&graph, member).edges.borrow().iter().filter(|conn| conn.destination().name() == summary).count(), 1);
+
+            graph.expand_group(second);
+
+            let mut after = graph.to_data().edges;
+            before.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+            after.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+            assert_eq!(after, before, "expanding {} first", first);
+        }
+    }
+
+    /// Moving a collapsed group moves its members when it is expanded again.
+    #[test]
+    pub fn test_move_collapsed_group () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(10.0, 0.0));
+
+        let members: HashSet<String> = ["0", "1"].iter().map(|name| name.to_string()).collect();
+        graph.create_group(String::from("DMZ"), &members);
+
+        graph.collapse_group("DMZ");
+        graph.move_group("DMZ", Vector::new(0.0, 50.0));
+        graph.expand_group("DMZ");
+
+        assert_eq!(graph.position("0"), Point::new(0.0, 50.0));
+        assert_eq!(graph.group_bounds("DMZ").unwrap().y, 50.0);
+    }
+
+    /// Removing the last member of a group removes the group.
+    #[test]
+    pub fn test_group_membership () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+
+        let members: HashSet<String> = ["0"].iter().map(|name| name.to_string()).collect();
+        graph.create_group(String::from("Rack"), &members);
+        assert_eq!(graph.group_of("0").map(|group| group.name().as_str()), Some("Rack"));
+
+        let node = graph.get_node("0").unwrap();
+        let node = graph.rename_node(&node, String::from("core")).unwrap();
+        assert_eq!(graph.group_of("core").map(|group| group.name().as_str()), Some("Rack"));
+
+        graph.remove_node(&node);
+        assert!(graph.groups().is_empty());
+    }
+}
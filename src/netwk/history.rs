@@ -0,0 +1,133 @@
+use super::document::Snapshot;
+
+/// Undo and redo for a document, kept as the states between edits. Snapshots share the parts
+/// of the document an edit left alone, so each step only holds a copy of what it changed.
+#[derive(Debug)]
+pub struct History<T = Snapshot> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    /// Edits sharing a group, like the steps of one drag, are undone together
+    group: Option<String>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        History { undo: vec![], redo: vec![], group: None }
+    }
+}
+
+impl<T> History<T> {
+    const LIMIT: usize = 100;
+
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// Remember the state from before an edit. If the edit is in the same group as the
+    /// last one the earlier snapshot already covers it, so nothing is added.
+    pub fn record(&mut self, before: T, group: Option<String>) {
+        if group.is_some() && group == self.group {
+            return;
+        }
+
+        self.group = group;
+        self.redo.clear();
+        self.undo.push(before);
+
+        if self.undo.len() > Self::LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Whether an edit in this group would be covered by the snapshot already taken for the
+    /// group, so there is no need to take another.
+    pub fn continues(&self, group: Option<&str>) -> bool {
+        group.is_some() && group == self.group.as_deref()
+    }
+
+    /// End the current group, so the next edit gets its own snapshot even if it shares the key.
+    pub fn seal(&mut self) {
+        self.group = None;
+    }
+
+    /// Step back, given the current state to come back to on redo.
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        self.group = None;
+
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        self.group = None;
+
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::format::{DocumentData, NodeData};
+
+    fn with_nodes(count: usize) -> DocumentData {
+        let mut data = DocumentData::default();
+        for i in 0..count {
+            data.graph.nodes.push(NodeData { name: i.to_string(), x: 0.0, y: 0.0 });
+        }
+
+        data
+    }
+
+    /// Undo walks back through snapshots and redo replays them, until a new edit is made.
+    #[test]
+    pub fn test_undo_redo () {
+        let mut history: History<DocumentData> = History::new();
+        history.record(with_nodes(0), None);
+        history.record(with_nodes(1), None);
+
+        assert_eq!(history.undo(with_nodes(2)), Some(with_nodes(1)));
+        assert_eq!(history.undo(with_nodes(1)), Some(with_nodes(0)));
+        assert_eq!(history.undo(with_nodes(0)), None);
+
+        assert_eq!(history.redo(with_nodes(0)), Some(with_nodes(1)));
+        assert!(history.can_redo());
+
+        history.record(with_nodes(1), None);
+        assert!(!history.can_redo());
+    }
+
+    /// A drag is made of many moves but should only take one undo.
+    #[test]
+    pub fn test_grouped_edits () {
+        let mut history: History<DocumentData> = History::new();
+        let drag = Some(String::from("node a"));
+
+        history.record(with_nodes(0), drag.clone());
+        history.record(with_nodes(1), drag.clone());
+        history.record(with_nodes(2), drag.clone());
+
+        assert!(history.continues(drag.as_deref()));
+        assert!(!history.continues(Some("node b")) && !history.continues(None));
+
+        history.seal();
+        assert!(!history.continues(drag.as_deref()));
+        history.record(with_nodes(3), drag);
+
+        assert_eq!(history.undo(with_nodes(4)), Some(with_nodes(3)));
+        assert_eq!(history.undo(with_nodes(3)), Some(with_nodes(0)));
+        assert!(!history.can_undo());
+    }
+}
@@ -0,0 +1,203 @@
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+// Long running work moved onto a thread of its own. The work takes a snapshot of whatever it
+// needs up front and hands back a result at the end, so the only thing shared while it runs
+// is how far it has got.
+
+/// How far a job has got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    /// Between 0 and 1
+    pub fraction: f32,
+    pub stage: String,
+    pub finished: bool,
+    /// Counts reports, so a watcher can tell whether anything happened since it last looked
+    step: u64,
+}
+
+/// Handed to the work for reporting progress and finding out whether it should give up.
+#[derive(Debug)]
+pub struct Progress {
+    cancelled: AtomicBool,
+    state: Mutex<(Status, Option<Waker>)>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new("")
+    }
+}
+
+impl Progress {
+    fn new(stage: &str) -> Self {
+        let status = Status { fraction: 0.0, stage: stage.to_string(), finished: false, step: 0 };
+
+        Progress { cancelled: AtomicBool::new(false), state: Mutex::new((status, None)) }
+    }
+
+    pub fn report(&self, fraction: f32, stage: &str) {
+        self.update(|status| {
+            status.fraction = fraction.clamp(0.0, 1.0);
+
+            if status.stage != stage {
+                status.stage = stage.to_string();
+            }
+        });
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Work should check this every so often and return None once it is set.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn finish(&self) {
+        self.update(|status| status.finished = true);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Status)) {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state.0);
+        state.0.step += 1;
+
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Work running on another thread, ending in an R unless it was cancelled or panicked.
+#[derive(Debug)]
+pub struct Job<R> {
+    progress: Arc<Progress>,
+    result: Arc<Mutex<Option<R>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<R: Send + 'static> Job<R> {
+    /// Start the work straight away, starting out at the given stage.
+    pub fn spawn(stage: &str, work: impl FnOnce(&Progress) -> Option<R> + Send + 'static) -> Self {
+        let progress = Arc::new(Progress::new(stage));
+        let result = Arc::new(Mutex::new(None));
+
+        let thread = {
+            let (progress, result) = (progress.clone(), result.clone());
+
+            thread::spawn(move || {
+                // A panic counts as giving up, rather than leaving anyone waiting forever
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| work(&progress))).ok().flatten();
+
+                *result.lock().unwrap() = outcome;
+                progress.finish();
+            })
+        };
+
+        Job { progress, result, thread: Some(thread) }
+    }
+
+    pub fn status(&self) -> Status {
+        self.progress.state.lock().unwrap().0.clone()
+    }
+
+    /// Ask the work to stop. It finishes with no result once it notices.
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+
+    /// The result, once the job has finished, if it gave one. Can only be taken once.
+    pub fn take(&self) -> Option<R> {
+        self.result.lock().unwrap().take()
+    }
+
+    /// Block until the job finishes and take its result.
+    pub fn wait(mut self) -> Option<R> {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        self.take()
+    }
+
+    /// Resolves with the status as soon as it has moved on from `seen`. Only the most recent
+    /// of these gets woken, so there should be one watcher at a time.
+    pub fn next(&self, seen: &Status) -> impl Future<Output = Status> + Send + 'static {
+        Next { progress: self.progress.clone(), step: seen.step }
+    }
+}
+
+struct Next {
+    progress: Arc<Progress>,
+    step: u64,
+}
+
+impl Future for Next {
+    type Output = Status;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Status> {
+        let mut state = self.progress.state.lock().unwrap();
+
+        if state.0.step != self.step {
+            Poll::Ready(state.0.clone())
+        } else {
+            state.1 = Some(context.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::time::Duration;
+
+    /// Results come back from the other thread, and progress is seen from this one.
+    #[test]
+    pub fn test_job_result () {
+        let job = Job::spawn("Counting", |progress| {
+            for i in 0..10 {
+                progress.report(i as f32 / 10.0, "Counting");
+            }
+
+            Some(45)
+        });
+
+        assert_eq!(job.status().stage, "Counting");
+        assert_eq!(job.wait(), Some(45));
+    }
+
+    /// Cancelled and panicking jobs both finish without a result.
+    #[test]
+    pub fn test_job_cancel () {
+        let job: Job<()> = Job::spawn("Waiting", |progress| {
+            while !progress.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            None
+        });
+
+        job.cancel();
+        assert!(job.is_cancelled());
+        assert_eq!(job.wait(), None);
+
+        let job: Job<()> = Job::spawn("Failing", |_| panic!("oops"));
+        let status = job.progress.clone();
+
+        assert_eq!(job.wait(), None);
+        assert!(status.state.lock().unwrap().0.finished);
+    }
+}
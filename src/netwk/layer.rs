@@ -0,0 +1,322 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Layers split a diagram into parts which can be hidden or locked together, e.g. physical
+/// cabling, logical links and notes. Anything not put on a layer belongs to the default layer.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Layer {
+    id: usize,
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+    nodes: HashSet<String>,
+    edges: HashSet<(String, String)>,
+    annotations: HashSet<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Layers {
+    layers: Vec<Layer>,
+    active: usize,
+    last_id: usize,
+}
+
+/// Edges are undirected as far as layers care, so the key is always in name order.
+fn edge_key(from: &str, to: &str) -> (String, String) {
+    if from <= to {
+        (from.to_string(), to.to_string())
+    } else {
+        (to.to_string(), from.to_string())
+    }
+}
+
+impl Layer {
+    fn new(id: usize, name: String) -> Self {
+        Layer {
+            id,
+            name,
+            visible: true,
+            locked: false,
+            nodes: HashSet::new(),
+            edges: HashSet::new(),
+            annotations: HashSet::new(),
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Can things on this layer be clicked on.
+    pub fn interactive(&self) -> bool {
+        self.visible && !self.locked
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers {
+            layers: vec![Layer::new(Self::DEFAULT, String::from("Default"))],
+            active: Self::DEFAULT,
+            last_id: Self::DEFAULT + 1,
+        }
+    }
+}
+
+impl Layers {
+    pub const DEFAULT: usize = 0;
+
+    pub fn iter(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id == id)
+    }
+
+    /// Layer which new things are put on.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn set_active(&mut self, id: usize) {
+        if self.get(id).is_some() {
+            self.active = id;
+        }
+    }
+
+    pub fn add(&mut self, name: String) -> usize {
+        let id = self.last_id;
+        self.last_id += 1;
+        self.layers.push(Layer::new(id, name));
+
+        id
+    }
+
+    /// Put back whatever loaded data left out: the default layer, which everything else falls
+    /// back on, and an active layer which exists. New ids also start after every layer's.
+    pub fn repair(&mut self) {
+        if self.get(Self::DEFAULT).is_none() {
+            self.layers.insert(0, Layer::new(Self::DEFAULT, String::from("Default")));
+        }
+
+        if self.get(self.active).is_none() {
+            self.active = Self::DEFAULT;
+        }
+
+        self.last_id = self.layers.iter().map(|layer| layer.id + 1).fold(self.last_id, usize::max);
+    }
+
+    /// Remove a layer, handing everything on it back to the default layer.
+    pub fn remove(&mut self, id: usize) {
+        if id == Self::DEFAULT {
+            return;
+        }
+
+        self.layers.retain(|layer| layer.id != id);
+
+        if self.active == id {
+            self.active = Self::DEFAULT;
+        }
+    }
+
+    fn default_layer(&self) -> &Layer {
+        self.get(Self::DEFAULT).expect("The default layer is never removed, and repair puts it back in loaded data")
+    }
+
+    pub fn node_layer(&self, name: &str) -> &Layer {
+        self.layers
+            .iter()
+            .find(|layer| layer.nodes.contains(name))
+            .unwrap_or_else(|| self.default_layer())
+    }
+
+    pub fn edge_layer(&self, from: &str, to: &str) -> &Layer {
+        let key = edge_key(from, to);
+
+        self.layers
+            .iter()
+            .find(|layer| layer.edges.contains(&key))
+            .unwrap_or_else(|| self.default_layer())
+    }
+
+    pub fn annotation_layer(&self, id: usize) -> &Layer {
+        self.layers
+            .iter()
+            .find(|layer| layer.annotations.contains(&id))
+            .unwrap_or_else(|| self.default_layer())
+    }
+
+    pub fn assign_node(&mut self, name: &str, layer: usize) {
+        for other in self.layers.iter_mut() {
+            other.nodes.remove(name);
+        }
+
+        if let Some(layer) = self.get_mut(layer) {
+            layer.nodes.insert(name.to_string());
+        }
+    }
+
+    pub fn assign_edge(&mut self, from: &str, to: &str, layer: usize) {
+        let key = edge_key(from, to);
+
+        for other in self.layers.iter_mut() {
+            other.edges.remove(&key);
+        }
+
+        if let Some(layer) = self.get_mut(layer) {
+            layer.edges.insert(key);
+        }
+    }
+
+    pub fn assign_annotation(&mut self, id: usize, layer: usize) {
+        for other in self.layers.iter_mut() {
+            other.annotations.remove(&id);
+        }
+
+        if let Some(layer) = self.get_mut(layer) {
+            layer.annotations.insert(id);
+        }
+    }
+
+    pub fn node_visible(&self, name: &str) -> bool {
+        self.node_layer(name).visible
+    }
+
+    /// Edges are only shown when their own layer and both of their ends are.
+    pub fn edge_visible(&self, from: &str, to: &str) -> bool {
+        self.edge_layer(from, to).visible && self.node_visible(from) && self.node_visible(to)
+    }
+
+    pub fn annotation_visible(&self, id: usize) -> bool {
+        self.annotation_layer(id).visible
+    }
+
+    pub fn node_interactive(&self, name: &str) -> bool {
+        self.node_layer(name).interactive()
+    }
+
+    pub fn edge_interactive(&self, from: &str, to: &str) -> bool {
+        self.edge_layer(from, to).interactive() && self.edge_visible(from, to)
+    }
+
+    pub fn annotation_interactive(&self, id: usize) -> bool {
+        self.annotation_layer(id).interactive()
+    }
+
+    /// Drop a removed node and any of its edges.
+    pub fn forget_node(&mut self, name: &str) {
+        for layer in self.layers.iter_mut() {
+            layer.nodes.remove(name);
+            layer.edges.retain(|(from, to)| from != name && to != name);
+        }
+    }
+
+    pub fn forget_edge(&mut self, from: &str, to: &str) {
+        let key = edge_key(from, to);
+
+        for layer in self.layers.iter_mut() {
+            layer.edges.remove(&key);
+        }
+    }
+
+    pub fn forget_annotation(&mut self, id: usize) {
+        for layer in self.layers.iter_mut() {
+            layer.annotations.remove(&id);
+        }
+    }
+
+    pub fn rename_node(&mut self, old: &str, new: &str) {
+        for layer in self.layers.iter_mut() {
+            if layer.nodes.remove(old) {
+                layer.nodes.insert(new.to_string());
+            }
+
+            let renamed: Vec<(String, String)> = layer
+                .edges
+                .iter()
+                .filter(|(from, to)| from == old || to == old)
+                .cloned()
+                .collect();
+
+            for (from, to) in renamed {
+                layer.edges.remove(&(from.clone(), to.clone()));
+
+                let rename = |name: String| if name == old { new.to_string() } else { name };
+                layer.edges.insert(edge_key(&rename(from), &rename(to)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Things default to the default layer until they are moved.
+    #[test]
+    pub fn test_layer_assignment () {
+        let mut layers = Layers::default();
+        let cabling = layers.add(String::from("Cabling"));
+
+        assert_eq!(layers.node_layer("a").id(), Layers::DEFAULT);
+
+        layers.assign_node("a", cabling);
+        layers.assign_edge("b", "a", cabling);
+
+        assert_eq!(layers.node_layer("a").id(), cabling);
+        assert_eq!(layers.edge_layer("a", "b").id(), cabling);
+
+        layers.get_mut(cabling).unwrap().visible = false;
+
+        assert!(!layers.node_visible("a"));
+        assert!(layers.edge_visible("b", "c"));
+        assert!(!layers.edge_visible("a", "c"));
+
+        layers.rename_node("a", "z");
+        assert_eq!(layers.edge_layer("b", "z").id(), cabling);
+
+        layers.remove(cabling);
+        assert_eq!(layers.node_layer("z").id(), Layers::DEFAULT);
+        assert!(layers.node_visible("z"));
+    }
+
+    /// Data without the default layer gets it back, and ids carry on after the highest.
+    #[test]
+    pub fn test_layer_repair () {
+        let mut layers = Layers::default();
+        let cabling = layers.add(String::from("Cabling"));
+        layers.assign_node("a", cabling);
+        layers.layers.retain(|layer| layer.id != Layers::DEFAULT);
+        layers.last_id = 0;
+
+        layers.repair();
+
+        assert_eq!(layers.node_layer("b").id(), Layers::DEFAULT);
+        assert_eq!(layers.node_layer("a").id(), cabling);
+        assert_eq!(layers.active(), Layers::DEFAULT);
+        assert!(layers.add(String::from("Notes")) > cabling);
+    }
+
+    /// Locked layers stay visible but can't be interacted with.
+    #[test]
+    pub fn test_layer_lock () {
+        let mut layers = Layers::default();
+        let notes = layers.add(String::from("Notes"));
+
+        layers.assign_annotation(3, notes);
+        layers.get_mut(notes).unwrap().locked = true;
+
+        assert!(layers.annotation_visible(3));
+        assert!(!layers.annotation_interactive(3));
+        assert!(layers.annotation_interactive(4));
+    }
+}
@@ -0,0 +1,593 @@
+use super::geometry::{Point, Rectangle, Size, Vector};
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::attribute::drawn;
+use super::change::{Change, ObserverId};
+use super::format::{EdgeData, GraphData, NodeData};
+use super::{NetworkGraph, Node};
+
+/// A graph as it is drawn. Where each node sits and the control point of each curved edge are
+/// kept beside the graph rather than in it, going by node name and, for edges, the names at
+/// either end and which of the edges drawn that way it is, the same as Attributes. So the nodes
+/// and edges themselves are free to carry whatever N and E the caller wants.
+///
+/// Reading goes straight through to the graph. Changes go through here, so the positions and
+/// control points follow renamed nodes and reversed edges and go with whatever they belong to.
+#[derive(Debug)]
+pub struct Layout<N = (), E = ()> {
+    pub(super) graph: NetworkGraph<N, E>,
+    pub(super) positions: HashMap<String, Point>,
+    pub(super) controls: HashMap<(String, String, usize), Point>,
+}
+
+/// The graph the editor draws, which carries nothing but where things are.
+pub type LayoutGraph = Layout;
+
+pub type LayoutNode = Node<(), ()>;
+
+impl<N, E> Default for Layout<N, E> {
+    fn default() -> Self {
+        Layout { graph: NetworkGraph::default(), positions: HashMap::new(), controls: HashMap::new() }
+    }
+}
+
+impl<N, E> Deref for Layout<N, E> {
+    type Target = NetworkGraph<N, E>;
+
+    fn deref(&self) -> &NetworkGraph<N, E> {
+        &self.graph
+    }
+}
+
+/// Source and destination of an edge.
+type Ends<N, E> = (Arc<Node<N, E>>, Arc<Node<N, E>>);
+
+fn edge_key(from: &str, to: &str, nth: usize) -> (String, String, usize) {
+    (from.to_string(), to.to_string(), nth)
+}
+
+fn between(a: &str, b: &str, key: &(String, String, usize)) -> bool {
+    (key.0 == a && key.1 == b) || (key.0 == b && key.1 == a)
+}
+
+impl<N: Clone, E: Clone> Layout<N, E> {
+    pub fn new() -> Self {
+        Layout::default()
+    }
+
+    /// Where the node is drawn. Anything without a position is drawn at the origin.
+    pub fn position(&self, name: &str) -> Point {
+        self.positions.get(name).copied().unwrap_or(Point::ORIGIN)
+    }
+
+    /// Control point of the `nth` edge drawn from one node to the other, if it is curved.
+    pub fn control(&self, from: &str, to: &str, nth: usize) -> Option<Point> {
+        self.controls.get(&edge_key(from, to, nth)).copied()
+    }
+
+    /// True if any edge between the two, either way round, is curved.
+    pub fn curved_between(&self, a: &str, b: &str) -> bool {
+        self.controls.keys().any(|key| between(a, b, key))
+    }
+
+    /// Add a node at pos carrying data.
+    pub fn add_node_carrying(&mut self, pos: Point, data: N) -> Arc<Node<N, E>> {
+        let node = Node::new(self.graph.fresh_name(), data);
+        self.push_node(node.clone(), pos);
+
+        node
+    }
+
+    /// Add a node under the name it already has, as loading and restoring groups do.
+    pub(super) fn push_node(&mut self, node: Arc<Node<N, E>>, pos: Point) {
+        self.positions.insert(node.name.clone(), pos);
+        self.graph.push_node(node);
+    }
+
+    /// Connect two nodes, giving the edge its weight, curve and payload up front.
+    pub fn add_edge_carrying(
+        &mut self,
+        node_src: &Arc<Node<N, E>>,
+        node_dst: &Arc<Node<N, E>>,
+        weight: Option<f32>,
+        control: Option<Point>,
+        data: E,
+    ) {
+        if let Some(ctl) = control {
+            let nth = drawn(&self.graph, &node_src.name, &node_dst.name);
+            self.controls.insert(edge_key(&node_src.name, &node_dst.name, nth), ctl);
+        }
+
+        self.graph.add_edge_with(node_src, node_dst, weight, data);
+    }
+
+    pub fn remove_node(&mut self, node_ref: &Arc<Node<N, E>>) {
+        self.batch(|layout| {
+            layout.graph.remove_node(node_ref);
+            layout.positions.remove(&node_ref.name);
+            layout.controls.retain(|(a, b, _), _| *a != node_ref.name && *b != node_ref.name);
+        })
+    }
+
+    /// Give a node a new name, taking its position and curves with it. Returns None if the
+    /// name is already taken.
+    pub fn rename_node(&mut self, node_ref: &Arc<Node<N, E>>, name: String) -> Option<Arc<Node<N, E>>> {
+        self.batch(|layout| {
+            let renamed = layout.graph.rename_node(node_ref, name.clone())?;
+
+            if let Some(pos) = layout.positions.remove(&node_ref.name) {
+                layout.positions.insert(name.clone(), pos);
+            }
+
+            let from = &node_ref.name;
+            layout.rekey(|(a, b, _)| a == from || b == from, |(a, b, nth)| {
+                let rename = |old: String| if old == *from { name.clone() } else { old };
+                (rename(a), rename(b), nth)
+            });
+
+            Some(renamed)
+        })
+    }
+
+    /// Removes every edge between the two nodes, curves and all.
+    pub fn remove_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        self.batch(|layout| {
+            layout.graph.remove_edge(node_src, node_dst);
+            layout.controls.retain(|key, _| !between(&node_src.name, &node_dst.name, key));
+        })
+    }
+
+    /// Swap which end of the edge is the source. Curves stay where they are drawn.
+    pub fn reverse_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        self.batch(|layout| {
+            layout.graph.reverse_edge(node_src, node_dst);
+            layout.rekey(|key| between(&node_src.name, &node_dst.name, key), |(from, to, nth)| (to, from, nth));
+        })
+    }
+
+    pub fn set_weight(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, weight: Option<f32>) {
+        self.graph.set_weight(node_src, node_dst, weight)
+    }
+
+    pub fn set_directed(&mut self, directed: bool) {
+        self.graph.set_directed(directed)
+    }
+
+    /// Swap what the node carries, returning the replacement node. It stays where it is.
+    pub fn set_node_data(&mut self, node_ref: &Arc<Node<N, E>>, data: N) -> Arc<Node<N, E>> {
+        self.graph.set_node_data(node_ref, data)
+    }
+
+    pub fn set_edge_data(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, data: E) {
+        self.graph.set_edge_data(node_src, node_dst, data)
+    }
+
+    pub fn create_group(&mut self, name: String, members: &HashSet<String>) -> bool {
+        self.graph.create_group(name, members)
+    }
+
+    pub fn subscribe(&mut self, observer: impl FnMut(&[Change]) + 'static) -> ObserverId {
+        self.graph.subscribe(observer)
+    }
+
+    pub fn unsubscribe(&mut self, id: ObserverId) {
+        self.graph.unsubscribe(id)
+    }
+
+    /// As NetworkGraph::batch, for changes to where things are as well as to the graph.
+    pub fn batch<T>(&mut self, changes: impl FnOnce(&mut Self) -> T) -> T {
+        self.graph.hold();
+        let result = changes(self);
+        self.graph.release();
+
+        result
+    }
+
+    pub fn move_node(&mut self, node_ref: &Arc<Node<N, E>>, pos: Point) {
+        if self.positions.get(&node_ref.name) == Some(&pos) {
+            return;
+        }
+
+        self.positions.insert(node_ref.name.clone(), pos);
+        self.graph.emit(Change::NodeUpdated(node_ref.name.clone()));
+    }
+
+    /// Move every named node at once, such as to apply a layout. Names no longer in the graph
+    /// are skipped, since the positions may have been worked out from an older copy.
+    pub fn set_positions(&mut self, positions: &HashMap<String, Point>) {
+        self.batch(|layout| {
+            for (name, pos) in positions {
+                if let Some(node) = layout.get_node(name) {
+                    layout.move_node(&node, *pos);
+                }
+            }
+        })
+    }
+
+    /// Curve, or straighten with None, every edge between the two nodes.
+    pub fn set_control(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, ctl: Option<Point>) {
+        for (from, to) in [(&node_src.name, &node_dst.name), (&node_dst.name, &node_src.name)] {
+            for nth in 0..drawn(&self.graph, from, to) {
+                match ctl {
+                    Some(ctl) => self.controls.insert(edge_key(from, to, nth), ctl),
+                    None => self.controls.remove(&edge_key(from, to, nth)),
+                };
+            }
+        }
+
+        self.graph.emit(Change::EdgeUpdated(node_src.name.clone(), node_dst.name.clone()));
+    }
+
+    /// Move the control points of every edge the filter picks to a new key.
+    fn rekey(
+        &mut self,
+        filter: impl Fn(&(String, String, usize)) -> bool,
+        rekey: impl Fn((String, String, usize)) -> (String, String, usize),
+    ) {
+        let moved: Vec<_> = self.controls.keys().filter(|key| filter(key)).cloned().collect();
+        let moved: Vec<_> = moved
+            .into_iter()
+            .map(|key| {
+                let ctl = self.controls.remove(&key).expect("key was just found");
+                (rekey(key), ctl)
+            })
+            .collect();
+
+        self.controls.extend(moved);
+    }
+
+    /// Control point of each of the node's connections, in the order it stores them.
+    pub(super) fn connection_controls(&self, node: &Node<N, E>) -> Vec<Option<Point>> {
+        let mut seen: HashMap<(bool, String), usize> = HashMap::new();
+
+        node.edges
+            .borrow()
+            .iter()
+            .map(|conn| {
+                let nth = seen.entry((conn.outgoing, conn.dst.name.clone())).or_default();
+                let control = if conn.outgoing {
+                    self.control(&node.name, &conn.dst.name, *nth)
+                } else {
+                    self.control(&conn.dst.name, &node.name, *nth)
+                };
+                *nth += 1;
+
+                control
+            })
+            .collect()
+    }
+
+    /// The whole graph as plain data.
+    pub fn to_data(&self) -> GraphData {
+        GraphData {
+            groups: self.groups.clone(),
+            ..self.extract(&self.nodes.iter().map(|node| node.name.clone()).collect())
+        }
+    }
+
+    /// Copy out the named nodes and the edges running between them.
+    pub fn extract(&self, names: &HashSet<String>) -> GraphData {
+        let mut data = GraphData { directed: self.directed, ..GraphData::default() };
+
+        for node in self.nodes.iter().filter(|node| names.contains(&node.name)) {
+            let pos = self.position(&node.name);
+            data.nodes.push(NodeData { name: node.name.clone(), x: pos.x, y: pos.y });
+
+            let controls = self.connection_controls(node);
+
+            for (conn, control) in node.edges.borrow().iter().zip(controls) {
+                if conn.outgoing && names.contains(&conn.dst.name) {
+                    data.edges.push(EdgeData {
+                        from: node.name.clone(),
+                        to: conn.dst.name.clone(),
+                        weight: conn.weight,
+                        control: control.map(|ctl| (ctl.x, ctl.y)),
+                    });
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Bounding box around every node position, if there are any nodes.
+    pub fn bounds(&self) -> Option<Rectangle> {
+        let first = self.position(&self.nodes.first()?.name);
+        let (mut min, mut max) = (first, first);
+
+        for pos in self.nodes.iter().map(|node| self.position(&node.name)) {
+            min = Point::new(min.x.min(pos.x), min.y.min(pos.y));
+            max = Point::new(max.x.max(pos.x), max.y.max(pos.y));
+        }
+
+        Some(Rectangle::new(min, Size::new(max.x - min.x, max.y - min.y)))
+    }
+
+    pub fn get_exact_point(&self, pos: Point) -> Option<Arc<Node<N, E>>> {
+
+        for node in self.node_list().iter() {
+
+            if self.position(&node.name).eq(&pos) {
+                return Some(node.clone())
+            }
+        }
+
+        None
+    }
+
+    pub fn get_near_point(&self, pos: Point) -> Option<Arc<Node<N, E>>> {
+        self.get_near_point_where(pos, |_| true)
+    }
+
+    /// As get_near_point, but skipping any node the filter rejects. Looks at every node, so
+    /// anything drawing the graph should ask its SpatialIndex instead.
+    pub fn get_near_point_where(&self, pos: Point, filter: impl Fn(&Node<N, E>) -> bool) -> Option<Arc<Node<N, E>>> {
+        self.node_list()
+            .iter()
+            .filter(|node| filter(node))
+            .map(|node| (node, self.position(&node.name)))
+            .filter(|(_, at)| f32::abs(at.x - pos.x) < 5.0 && f32::abs(at.y - pos.y) < 5.0)
+            .min_by(|(_, a), (_, b)| a.distance(pos).total_cmp(&b.distance(pos)))
+            .map(|(node, _)| node.clone())
+    }
+
+    /// Finds the nearest edge passing within `tolerance` of pos, returned as (source, destination).
+    pub fn get_near_edge(&self, pos: Point, tolerance: f32) -> Option<Ends<N, E>> {
+        self.get_near_edge_where(pos, tolerance, |_, _| true)
+    }
+
+    /// As get_near_edge, but skipping any (source, destination) pair the filter rejects.
+    pub fn get_near_edge_where(
+        &self,
+        pos: Point,
+        tolerance: f32,
+        filter: impl Fn(&Node<N, E>, &Node<N, E>) -> bool,
+    ) -> Option<Ends<N, E>> {
+        let mut nearest = None;
+        let mut best = tolerance;
+
+        for node in self.node_list().iter() {
+            let controls = self.connection_controls(node);
+
+            for (conn, control) in node.edges.borrow().iter().zip(controls).filter(|(conn, _)| filter(node, &conn.dst)) {
+                let (from, to) = (self.position(&node.name), self.position(&conn.dst.name));
+
+                let distance = match control {
+                    None => distance_to_segment(pos, from, to),
+                    Some(ctl) => distance_to_curve(pos, from, ctl, to),
+                };
+
+                if distance < best {
+                    best = distance;
+                    nearest = Some((node.clone(), conn.dst.clone()));
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+/// Plain data has nothing to say about payloads, so whatever is built from it gets the defaults.
+impl<N: Clone + Default, E: Clone + Default> Layout<N, E> {
+    /// Rebuild a graph from saved data, keeping the node names as they were.
+    pub fn from_data(data: &GraphData) -> Self {
+        let mut layout = Layout {
+            graph: NetworkGraph { directed: data.directed, groups: data.groups.clone(), ..NetworkGraph::default() },
+            ..Layout::default()
+        };
+
+        for node in data.nodes.iter() {
+            layout.push_node(Node::new(node.name.clone(), N::default()), node.position());
+        }
+
+        for edge in data.edges.iter() {
+            if let (Some(from), Some(to)) = (layout.get_node(&edge.from), layout.get_node(&edge.to)) {
+                layout.add_edge_with(&from, &to, edge.weight, edge.control_point());
+            }
+        }
+
+        layout
+    }
+
+    pub fn add_node(&mut self, pos: Point) -> Arc<Node<N, E>> {
+        self.add_node_carrying(pos, N::default())
+    }
+
+    pub fn add_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        self.add_edge_with(node_src, node_dst, None, None)
+    }
+
+    pub fn add_curve(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, control: Point) {
+        self.add_edge_with(node_src, node_dst, None, Some(control))
+    }
+
+    pub fn add_edge_with(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, weight: Option<f32>, control: Option<Point>) {
+        self.add_edge_carrying(node_src, node_dst, weight, control, E::default())
+    }
+
+    /// Add a copy of the data to the graph, shifted by offset. Every node gets a fresh name
+    /// so nothing collides with what is already here. Returns the new names.
+    pub fn insert(&mut self, data: &GraphData, offset: Vector) -> Vec<String> {
+        let renamed = self.insert_with(data, offset, false);
+
+        data.nodes.iter().map(|node| renamed[&node.name].clone()).collect()
+    }
+
+    /// Shared by pasting and restoring collapsed groups. With keep_names nodes only get a
+    /// fresh name if theirs has been taken. Returns a map from the old names to the new.
+    pub(super) fn insert_with(&mut self, data: &GraphData, offset: Vector, keep_names: bool) -> HashMap<String, String> {
+        let mut renamed = HashMap::new();
+
+        self.batch(|layout| {
+            for node in data.nodes.iter() {
+                let name = if keep_names && layout.get_node(&node.name).is_none() {
+                    node.name.clone()
+                } else {
+                    layout.graph.fresh_name()
+                };
+
+                renamed.insert(node.name.clone(), name.clone());
+                layout.push_node(Node::new(name, N::default()), node.position() + offset);
+            }
+
+            for edge in data.edges.iter() {
+                let from = renamed.get(&edge.from).and_then(|name| layout.get_node(name));
+                let to = renamed.get(&edge.to).and_then(|name| layout.get_node(name));
+
+                if let (Some(from), Some(to)) = (from, to) {
+                    layout.add_edge_with(&from, &to, edge.weight, edge.control_point().map(|ctl| ctl + offset));
+                }
+            }
+        });
+
+        renamed
+    }
+}
+
+pub(super) fn distance_to_segment(pos: Point, from: Point, to: Point) -> f32 {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = dx * dx + dy * dy;
+
+    if length == 0.0 {
+        return pos.distance(from);
+    }
+
+    let t = (((pos.x - from.x) * dx + (pos.y - from.y) * dy) / length).clamp(0.0, 1.0);
+
+    pos.distance(Point::new(from.x + t * dx, from.y + t * dy))
+}
+
+/// Approximates the quadratic bezier as a polyline and measures against each piece.
+pub(super) fn distance_to_curve(pos: Point, from: Point, ctl: Point, to: Point) -> f32 {
+    const STEPS: usize = 16;
+
+    let at = |t: f32| {
+        let u = 1.0 - t;
+        Point::new(
+            u * u * from.x + 2.0 * u * t * ctl.x + t * t * to.x,
+            u * u * from.y + 2.0 * u * t * ctl.y + t * t * to.y,
+        )
+    };
+
+    (0..STEPS)
+        .map(|i| distance_to_segment(pos, at(i as f32 / STEPS as f32), at((i + 1) as f32 / STEPS as f32)))
+        .fold(f32::INFINITY, f32::min)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Edge edits should land on both halves of the mirrored connection.
+    #[test]
+    pub fn test_edge_updates () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(10.0, 0.0));
+
+        let (a, b) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap());
+        graph.add_edge(&a, &b);
+
+        graph.set_weight(&a, &b, Some(3.0));
+        graph.reverse_edge(&b, &a);
+
+        assert_eq!(*b.edges.borrow()[0].weight(), Some(3.0));
+        assert!(!a.edges.borrow()[0].outgoing());
+        assert!(b.edges.borrow()[0].outgoing());
+
+        assert!(graph.get_near_edge(Point::new(5.0, 1.0), 2.0).is_some());
+        assert!(graph.get_near_edge(Point::new(5.0, 4.0), 2.0).is_none());
+
+        graph.set_control(&a, &b, Some(Point::new(5.0, 10.0)));
+        assert_eq!(graph.control("1", "0", 0), Some(Point::new(5.0, 10.0)));
+        assert!(graph.get_near_edge(Point::new(5.0, 5.0), 2.0).is_some());
+
+        graph.remove_edge(&a, &b);
+
+        assert_eq!(a.edges.borrow().len(), 0);
+        assert_eq!(b.edges.borrow().len(), 0);
+        assert!(!graph.curved_between("0", "1"));
+    }
+
+    /// Copying a selection and pasting it back gives new names and keeps only internal edges.
+    #[test]
+    pub fn test_extract_insert () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(10.0, 0.0));
+        graph.add_node(Point::new(20.0, 0.0));
+
+        let (a, b, c) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap(), graph.get_node("2").unwrap());
+        graph.add_edge(&a, &b);
+        graph.add_curve(&b, &c, Point::new(15.0, 5.0));
+        graph.set_weight(&a, &b, Some(2.0));
+
+        let selection: HashSet<String> = ["0", "1"].iter().map(|name| name.to_string()).collect();
+        let data = graph.extract(&selection);
+
+        assert_eq!(data.nodes.len(), 2);
+        assert_eq!(data.edges.len(), 1);
+        #[cfg(feature = "serde")]
+        assert_eq!(GraphData::from_json(&data.to_json()), Some(data.clone()));
+
+        let pasted = graph.insert(&data, Vector::new(0.0, 50.0));
+
+        assert_eq!(pasted, vec![String::from("3"), String::from("4")]);
+        assert_eq!(graph.node_list().len(), 5);
+
+        let copy = graph.get_node("3").unwrap();
+        assert_eq!(graph.position(copy.name()), Point::new(0.0, 50.0));
+        assert_eq!(copy.edges.borrow()[0].destination().name(), "4");
+        assert_eq!(*copy.edges.borrow()[0].weight(), Some(2.0));
+    }
+
+    /// Saving and loading should give back the same graph, names included.
+    #[test]
+    pub fn test_data_round_trip () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(10.0, 0.0));
+
+        let (a, b) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap());
+        graph.add_curve(&a, &b, Point::new(5.0, 5.0));
+        graph.rename_node(&a, String::from("gateway"));
+
+        let loaded = LayoutGraph::from_data(&graph.to_data());
+
+        assert_eq!(loaded.to_data(), graph.to_data());
+        assert_eq!(loaded.control("gateway", "1", 0), Some(Point::new(5.0, 5.0)));
+    }
+
+    /// Nodes and edges keep their own payloads, and where they are drawn follows them about.
+    #[test]
+    pub fn test_domain_layout () {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Router { ports: u8 }
+
+        let mut graph: Layout<Router, u32> = Layout::new();
+        let core = graph.add_node_carrying(Point::new(0.0, 0.0), Router { ports: 48 });
+        let edge = graph.add_node_carrying(Point::new(10.0, 0.0), Router { ports: 8 });
+
+        graph.add_edge_carrying(&core, &edge, None, None, 1000);
+        graph.add_edge_carrying(&core, &edge, None, Some(Point::new(5.0, 5.0)), 10000);
+
+        let edge = graph.set_node_data(&edge, Router { ports: 16 });
+        let edge = graph.rename_node(&edge, String::from("edge")).unwrap();
+        graph.move_node(&edge, Point::new(20.0, 0.0));
+        graph.reverse_edge(&core, &edge);
+
+        assert_eq!(edge.data().ports, 16);
+        assert_eq!(edge.edges.borrow()[1].data(), &10000);
+        assert_eq!(graph.position("edge"), Point::new(20.0, 0.0));
+        assert_eq!(graph.control("edge", "0", 0), None);
+        assert_eq!(graph.control("edge", "0", 1), Some(Point::new(5.0, 5.0)));
+
+        graph.remove_node(&edge);
+        assert!(!graph.curved_between("edge", "0"));
+        assert_eq!(graph.bounds(), Some(Rectangle::new(Point::ORIGIN, Size::new(0.0, 0.0))));
+    }
+}
@@ -1,139 +1,278 @@
-use iced::Point;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::cell::RefCell;
 
+use self::change::{Change, Observers};
+use self::group::Group;
+
+#[cfg(feature = "algorithms")]
+pub mod algorithms;
+pub mod annotation;
+pub mod arrange;
+pub mod attribute;
+pub mod change;
+pub mod format;
+pub mod generate;
+pub mod geometry;
+pub mod document;
+pub mod group;
+pub mod history;
+pub mod job;
+pub mod layer;
+pub mod layout;
+pub mod query;
+pub mod spatial;
+pub mod validate;
+
 /// This module is basically just the actual data structures and stuff for the network
 /// all wrapped up to keep namespaces clear.
-/// 
-/// TODO: Make storage more generic so we can swap between say Adj List and G=(V, E) forms (e.g.) 
+///
+/// The graph itself doesn't know what its nodes and edges mean: N is whatever each node carries
+/// and E whatever each edge carries on top of its weight. Where things are drawn is kept beside
+/// the graph rather than in it, see layout::Layout.
+///
+/// TODO: Make storage more generic so we can swap between say Adj List and G=(V, E) forms (e.g.)
+
+#[derive(Debug)]
+pub struct NetworkGraph<N, E> {
+    nodes: Vec<Arc<Node<N, E>>>,
+    /// Where each name sits in nodes, so finding a node doesn't mean walking all of them
+    lookup: HashMap<String, usize>,
+    groups: Vec<Group>,
+    last_id: usize,
+    directed: bool,
+    /// Whoever wants to hear about changes, see change::Change
+    observers: Observers,
+}
 
-#[derive(Debug, Default)]
-pub struct NetworkGraph {
-    nodes: Vec<Arc<Node<Point>>>,
-    last_id: usize
+impl<N, E> Default for NetworkGraph<N, E> {
+    fn default() -> Self {
+        NetworkGraph {
+            nodes: vec![],
+            lookup: HashMap::new(),
+            groups: vec![],
+            last_id: 0,
+            directed: false,
+            observers: Observers::default(),
+        }
+    }
 }
 
-impl NetworkGraph {
+impl<N: Clone, E: Clone> NetworkGraph<N, E> {
 
     pub fn new () -> Self {
         NetworkGraph::default()
     }
 
-    pub fn node_list(&self) -> &Vec<Arc<Node<Point>>> {
+    pub fn node_list(&self) -> &Vec<Arc<Node<N, E>>> {
         &self.nodes
     }
 
-    pub fn add_node(&mut self, pos: &Point) {
-        self.nodes.push(Node::new(format!("{}", self.last_id), pos.clone()));
-        self.last_id += 1;
+    pub fn add_node(&mut self, data: N) -> Arc<Node<N, E>> {
+        let name = self.fresh_name();
+        let node = Node::new(name, data);
+        self.push_node(node.clone());
+
+        node
+    }
+
+    fn push_node(&mut self, node: Arc<Node<N, E>>) {
+        self.emit(Change::NodeAdded(node.name.clone()));
+        self.lookup.insert(node.name.clone(), self.nodes.len());
+        self.nodes.push(node);
+    }
+
+    /// Keep only the nodes passing the filter, without touching their connections.
+    fn retain_nodes(&mut self, filter: impl Fn(&Arc<Node<N, E>>) -> bool) {
+        self.nodes.retain(filter);
+        self.lookup = self.nodes.iter().enumerate().map(|(i, node)| (node.name.clone(), i)).collect();
     }
 
-    pub fn remove_node(&mut self, node_ref: &Arc<Node<Point>>) { // rewrite using filter?
-        let mut current_index = 0;
-        let mut matching_index = vec![];
-        
-        for node in self.node_list() {
-            if node.eq(node_ref) {
-                matching_index.push(current_index);
+    /// Next numbered name which isn't already in use (renames and pastes can claim them early).
+    fn fresh_name(&mut self) -> String {
+        loop {
+            let name = format!("{}", self.last_id);
+            self.last_id += 1;
+
+            if self.get_node(&name).is_none() {
+                return name;
             }
-            node.disconnect(node_ref);
-            current_index += 1;
         }
+    }
 
-        for i in matching_index {
-            self.nodes.remove(i);
-        }
+    pub fn remove_node(&mut self, node_ref: &Arc<Node<N, E>>) {
+        self.batch(|graph| {
+            for edge in graph.incident_edges(node_ref) {
+                graph.emit(Change::EdgeRemoved(edge.from().name.clone(), edge.to().name.clone()));
+            }
+
+            // Only the far ends of its own connections can hold the other halves
+            let neighbours: Vec<_> = node_ref.edges.borrow().iter().map(|conn| conn.dst.clone()).collect();
+
+            for node in neighbours.iter() {
+                if !node.eq(node_ref) {
+                    node.disconnect(node_ref);
+                }
+            }
+            node_ref.edges.borrow_mut().clear();
+
+            graph.retain_nodes(|node| !node.eq(node_ref));
+            graph.forget_group_member(&node_ref.name);
+            graph.emit(Change::NodeRemoved(node_ref.name.clone()));
+        })
     }
 
-    pub fn add_edge(&mut self, node_src: &Arc<Node<Point>>, node_dst: &Arc<Node<Point>>) {
-        Node::connect(node_src, node_dst)
+    /// Connect two nodes, giving the edge a weight and payload up front.
+    pub fn add_edge_with(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, weight: Option<f32>, data: E) {
+        Node::link(node_src, node_dst, weight, data);
+        self.emit(Change::EdgeAdded(node_src.name.clone(), node_dst.name.clone()));
     }
 
-    pub fn add_curve(&mut self, node_src: &Arc<Node<Point>>, node_dst: &Arc<Node<Point>>, control: Point) {
-        Node::connect_with_curve(node_src, node_dst, control)
+    /// Removes every connection between the two nodes, from both sides.
+    pub fn remove_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        node_src.disconnect(node_dst);
+        node_dst.disconnect(node_src);
+        self.emit(Change::EdgeRemoved(node_src.name.clone(), node_dst.name.clone()));
     }
 
-    pub fn remove_edge(&mut self, _node_src: &Arc<Node<Point>>, _node_dst: &Arc<Node<Point>>) {
-        println!("How the hell am I supposed to do this");
-        unimplemented!() // TODO
+    pub fn is_directed(&self) -> bool {
+        self.directed
     }
 
-    pub fn get_exact_point(&self, pos: Point) -> Option<Arc<Node<Point>>> {
-        
-        for node in self.node_list().iter() {
-            
-            if node.data.eq(&pos) {
-                return Some(node.clone())
-            }
+    pub fn set_directed(&mut self, directed: bool) {
+        if self.directed != directed {
+            self.directed = directed;
+            self.emit(Change::DirectedChanged);
         }
+    }
 
-        None
+    pub fn get_node(&self, name: &str) -> Option<Arc<Node<N, E>>> {
+        self.lookup.get(name).map(|&i| self.nodes[i].clone())
     }
 
-    pub fn get_near_point(&self, pos: Point) -> Option<Arc<Node<Point>>> {
+    /// Give a node a new name. Returns None if the name is already taken.
+    pub fn rename_node(&mut self, node_ref: &Arc<Node<N, E>>, name: String) -> Option<Arc<Node<N, E>>> {
+        if self.get_node(&name).is_some() {
+            return None;
+        }
 
-        for node in self.node_list().iter() {
+        self.rename_group_member(&node_ref.name, &name);
+        self.emit(Change::NodeRenamed { from: node_ref.name.clone(), to: name.clone() });
 
-            let dx = f32::abs(node.data.x - pos.x);
-            let dy = f32::abs(node.data.y - pos.y);
+        Some(self.replace_node(node_ref, name, node_ref.data.clone()))
+    }
+
+    /// Swap what the node carries, returning the replacement node.
+    pub fn set_node_data(&mut self, node_ref: &Arc<Node<N, E>>, data: N) -> Arc<Node<N, E>> {
+        self.emit(Change::NodeUpdated(node_ref.name.clone()));
+        self.replace_node(node_ref, node_ref.name.clone(), data)
+    }
 
-            if dx < 5.0 && dy < 5.0 {
-                return Some(node.clone())
+    /// Nodes are immutable once shared, so changing one means building a replacement
+    /// and pointing both halves of each of its connections at the new node.
+    fn replace_node(&mut self, old: &Arc<Node<N, E>>, name: String, data: N) -> Arc<Node<N, E>> {
+        let new = Node::new(name, data);
+        let edges: Vec<Connection<N, E>> = old.edges.borrow_mut().drain(..).collect();
+
+        for conn in edges.iter() {
+            if !conn.dst.eq(old) {
+                for back in conn.dst.edges.borrow_mut().iter_mut() {
+                    if back.dst.eq(old) {
+                        back.dst = new.clone();
+                    }
+                }
             }
         }
 
-        None
+        *new.edges.borrow_mut() = edges.into_iter().map(|conn| {
+            if conn.dst.eq(old) {
+                Connection { dst: new.clone(), ..conn }
+            } else {
+                conn
+            }
+        }).collect();
+
+        if let Some(i) = self.lookup.remove(&old.name) {
+            self.nodes[i] = new.clone();
+            self.lookup.insert(new.name.clone(), i);
+        }
+
+        new
     }
 
+    pub fn set_weight(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, weight: Option<f32>) {
+        Node::update_connection(node_src, node_dst, |conn| conn.weight = weight);
+        self.emit(Change::EdgeUpdated(node_src.name.clone(), node_dst.name.clone()));
+    }
+
+    pub fn set_edge_data(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>, data: E) {
+        Node::update_connection(node_src, node_dst, |conn| conn.data = data.clone());
+        self.emit(Change::EdgeUpdated(node_src.name.clone(), node_dst.name.clone()));
+    }
+
+    /// Swap which end of the edge is the source. Only visible when the graph is directed.
+    pub fn reverse_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        Node::update_connection(node_src, node_dst, |conn| conn.outgoing = !conn.outgoing);
+        self.emit(Change::EdgeReversed(node_src.name.clone(), node_dst.name.clone()));
+    }
+}
 
+impl<N: Clone, E: Clone + Default> NetworkGraph<N, E> {
+    /// Connect two nodes with no weight and a default payload.
+    pub fn add_edge(&mut self, node_src: &Arc<Node<N, E>>, node_dst: &Arc<Node<N, E>>) {
+        self.add_edge_with(node_src, node_dst, None, E::default())
+    }
 }
 
-/// Node<T>
+/// Node<N, E>
 /// Basic node in the network structure has a identifying name and
 /// some data associated with it, as well as a list of connected nodes
 /// edges is wrapped in a RefCell to allow the inner Vec to be borrowed mutuably even though the Rc
 /// would not normally allow mutuable borrows.
 #[derive(Debug, Clone)]
-pub struct Node<T: PartialEq> {
+pub struct Node<N, E> {
     name: String,
-    data: T,
-    edges: RefCell<Vec<Connection<T>>>
+    data: N,
+    edges: RefCell<Vec<Connection<N, E>>>
 }
 
-impl<T: PartialEq> PartialEq for Node<T> {
-    
+/// Names are unique within a graph, so they are all that is needed to tell nodes apart.
+impl<N, E> PartialEq for Node<N, E> {
+
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.data == other.data    
+        self.name == other.name
     }
 
 }
 
-/// Connection<T>
-/// Represents connection between two nodes (the implict owner of this connection) 
-/// and the referenced node dst. 
-/// Optionally this connection can have some weight, and a ctl point for display as a bezier curve
+/// Connection<N, E>
+/// Represents connection between two nodes (the implict owner of this connection)
+/// and the referenced node dst.
+/// Optionally this connection can have some weight, and carries some data of its own (whatever the
+/// graph's user wants, e.g. a link's bandwidth).
 /// dst is wraped in Weak because of circular pointers and stuff. Weak doesnt "own" its value, and doesn't prevent
 /// the inner Node<T> from being dropped.
 #[derive(Debug, Clone)]
-pub struct Connection<T: PartialEq> {
-    dst: Arc<Node<T>>,
+pub struct Connection<N, E> {
+    dst: Arc<Node<N, E>>,
     weight: Option<f32>,
-    ctl: Option<Point>
+    data: E,
+    outgoing: bool
 }
 
-impl<T: PartialEq> Node<T> {
+impl<N, E: Clone> Node<N, E> {
 
     /// Create a new Rc<Node<T>> based off some name and data. Has empty connection list.
     /// Rc is needed to put this onto the heap and allow multiple pointers to it.
-    pub fn new(name: String, data: T) -> Arc<Node<T>> {
+    pub fn new(name: String, data: N) -> Arc<Node<N, E>> {
         Arc::new(Node {name, data, edges: RefCell::new(vec![])})
     }
 
-    pub fn data(&self) -> &T {
+    pub fn data(&self) -> &N {
         &self.data
     }
 
-    pub fn edges(&self) -> &RefCell<Vec<Connection<T>>> {
+    pub fn edges(&self) -> &RefCell<Vec<Connection<N, E>>> {
         &self.edges
     }
 
@@ -144,48 +283,44 @@ impl<T: PartialEq> Node<T> {
     /// Connect two Nodes by a connection. Creates mutual Connection structs in both their edge lists
     /// clones the references.
     /// Takes &Rc<Node<T>> to not take ownership of the original Rc<Node<T>>
-    pub fn connect(source: &Arc<Node<T>>, dest: &Arc<Node<T>>) {
-        source.edges.borrow_mut().push(Connection::new(dest.clone()));
-        dest.edges.borrow_mut().push(Connection::new(source.clone()));
+    pub fn connect(source: &Arc<Node<N, E>>, dest: &Arc<Node<N, E>>) where E: Default {
+        Node::link(source, dest, None, E::default());
     }
 
-    /// Connect two nodes via bezier curve
-    pub fn connect_with_curve(source: &Arc<Node<T>>, dest: &Arc<Node<T>>, ctl: Point) {
-        source.edges.borrow_mut().push(Connection::new_curve(dest.clone(), ctl.clone()));
-        dest.edges.borrow_mut().push(Connection::new_curve(source.clone(), ctl));
+    /// Connect two nodes with every connection property given up front.
+    fn link(source: &Arc<Node<N, E>>, dest: &Arc<Node<N, E>>, weight: Option<f32>, data: E) {
+        source.edges.borrow_mut().push(Connection { dst: dest.clone(), weight, data: data.clone(), outgoing: true });
+        dest.edges.borrow_mut().push(Connection { dst: source.clone(), weight, data, outgoing: false });
     }
 
-    pub fn disconnect(&self, other: &Arc<Node<T>>) { // might be able to do this more idiomatically
-
-        let mut conn_index = vec![];
-        let mut index = 0;
+    pub fn disconnect(&self, other: &Arc<Node<N, E>>) {
+        self.edges.borrow_mut().retain(|conn| !conn.dst.eq(other));
+    }
 
-        for conn in self.edges.borrow().iter() {
+    /// Apply the same change to both mirrored halves of every connection between the two nodes.
+    fn update_connection(source: &Arc<Node<N, E>>, dest: &Arc<Node<N, E>>, mut update: impl FnMut(&mut Connection<N, E>)) {
+        for conn in source.edges.borrow_mut().iter_mut().filter(|conn| conn.dst.eq(dest)) {
+            update(conn);
+        }
 
-            if conn.dst.eq(other) {
-                conn_index.push(index)
-            }
-            index += 1;
+        if source.eq(dest) {
+            return;
         }
 
-        for i in conn_index {
-            self.edges.borrow_mut().swap_remove(i);
+        for conn in dest.edges.borrow_mut().iter_mut().filter(|conn| conn.dst.eq(source)) {
+            update(conn);
         }
     }
 }
 
-impl<T: PartialEq> Connection<T> {
+impl<N, E> Connection<N, E> {
     /// Create a new connection from a Rc Node<T>.
     /// TODO: fully expand this impl
-    pub fn new(dst: Arc<Node<T>>) -> Self {
-        Connection { dst: dst.clone(), weight: None, ctl: None }
+    pub fn new(dst: Arc<Node<N, E>>, data: E) -> Self {
+        Connection { dst, weight: None, data, outgoing: true }
     }
 
-    pub fn new_curve(dst: Arc<Node<T>>, ctl: Point) -> Self {
-        Connection { dst: dst.clone(), weight: None, ctl: Some(ctl) }
-    }
-
-    pub fn destination(&self) -> &Arc<Node<T>> {
+    pub fn destination(&self) -> &Arc<Node<N, E>> {
         &self.dst
     }
 
@@ -193,12 +328,15 @@ impl<T: PartialEq> Connection<T> {
         &self.weight
     }
 
-    pub fn control(&self) -> &Option<Point> {
-        &self.ctl
+    pub fn data(&self) -> &E {
+        &self.data
     }
-}
 
-// TODO: Expand impl and maybe add iterators?
+    /// True on the half of the connection stored by the node the edge was drawn from.
+    pub fn outgoing(&self) -> bool {
+        self.outgoing
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -209,7 +347,7 @@ mod test {
     #[test]
     pub fn test_node_creation () {
 
-        let node: Arc<Node<i32>> = Node::new(String::from("Hello World"), 42);
+        let node: Arc<Node<i32, ()>> = Node::new(String::from("Hello World"), 42);
 
         assert_eq!(node.name, String::from("Hello World"));
         assert_eq!(node.data, 42);
@@ -219,18 +357,94 @@ mod test {
     /// Can we connect two nodes?
     #[test]
     pub fn test_node_connection () {
-        let node: Arc<Node<i32>> = Node::new(String::from("Node 1"), 10);
+        let node: Arc<Node<i32, ()>> = Node::new(String::from("Node 1"), 10);
 
-        let node2: Arc<Node<i32>> = Node::new(String::from("Node 2"), 20);
+        let node2: Arc<Node<i32, ()>> = Node::new(String::from("Node 2"), 20);
 
         Node::connect(&node, &node2);
 
         println!("{:?}", node.edges.borrow().iter().map(|x| {
-            format!("Connection {{ {:?}, {:?}, {:?} }}", x.dst, x.weight, x.ctl)
+            format!("Connection {{ {:?}, {:?}, {:?} }}", x.dst, x.weight, x.data)
         }).collect::<Vec<String>>()
         );
         println!("{:?}", node2.edges.borrow());
 
         assert_eq!(1,1);
     }
-}
\ No newline at end of file
+
+    /// Removing a node should drop it and every connection pointing at it.
+    #[test]
+    pub fn test_remove_node () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        graph.add_node(0);
+        graph.add_node(1);
+        graph.add_node(2);
+
+        let (a, b, c) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap(), graph.get_node("2").unwrap());
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+        graph.add_edge(&a, &c);
+
+        graph.remove_node(&b);
+
+        assert_eq!(graph.node_list().len(), 2);
+        assert_eq!(a.edges.borrow().len(), 1);
+        assert_eq!(c.edges.borrow().len(), 1);
+    }
+
+    /// Renaming rewires the neighbours' connections onto the replacement node.
+    #[test]
+    pub fn test_rename_node () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        graph.add_node(0);
+        graph.add_node(1);
+
+        let (a, b) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap());
+        graph.add_edge(&a, &b);
+
+        assert!(graph.rename_node(&a, String::from("1")).is_none());
+
+        let renamed = graph.rename_node(&a, String::from("router")).unwrap();
+
+        assert!(graph.get_node("0").is_none());
+        assert_eq!(b.edges.borrow()[0].destination().name(), "router");
+        assert_eq!(renamed.edges.borrow()[0].destination().name(), "1");
+    }
+
+    /// New nodes skip over names that have already been claimed.
+    #[test]
+    pub fn test_fresh_names () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        graph.add_node(0);
+
+        let node = graph.get_node("0").unwrap();
+        graph.rename_node(&node, String::from("1"));
+        graph.add_node(1);
+
+        assert!(graph.get_node("2").is_some());
+    }
+
+    /// Graphs can carry whatever a caller wants on their nodes and edges.
+    #[test]
+    pub fn test_domain_payloads () {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Router { ports: u8 }
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Link { bandwidth: u32 }
+
+        let mut graph: NetworkGraph<Router, Link> = NetworkGraph::new();
+        let core = graph.add_node(Router { ports: 48 });
+        let edge = graph.add_node(Router { ports: 8 });
+
+        graph.add_edge_with(&core, &edge, Some(1.0), Link { bandwidth: 1000 });
+        graph.set_edge_data(&edge, &core, Link { bandwidth: 10000 });
+
+        let edge = graph.set_node_data(&edge, Router { ports: 16 });
+
+        assert_eq!(edge.data().ports, 16);
+        assert_eq!(core.edges.borrow()[0].data(), &Link { bandwidth: 10000 });
+        assert_eq!(core.edges.borrow()[0].destination().data().ports, 16);
+        assert_eq!(*edge.edges.borrow()[0].weight(), Some(1.0));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use super::{Connection, NetworkGraph, Node};
+
+// Read-only walks over the graph. These hand out owned copies of what they find,
+// so callers never have to borrow a node's RefCell themselves.
+
+/// One edge of the graph, pointing the way it was drawn.
+#[derive(Debug, Clone)]
+pub struct Edge<N, E> {
+    from: Arc<Node<N, E>>,
+    to: Arc<Node<N, E>>,
+    weight: Option<f32>,
+    data: E,
+}
+
+impl<N, E: Clone> Edge<N, E> {
+    /// Build from whichever half of the connection `node` holds.
+    fn from_half(node: &Arc<Node<N, E>>, conn: &Connection<N, E>) -> Self {
+        let (from, to) = if conn.outgoing {
+            (node.clone(), conn.dst.clone())
+        } else {
+            (conn.dst.clone(), node.clone())
+        };
+
+        Edge { from, to, weight: conn.weight, data: conn.data.clone() }
+    }
+
+    pub fn from(&self) -> &Arc<Node<N, E>> {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Arc<Node<N, E>> {
+        &self.to
+    }
+
+    pub fn weight(&self) -> Option<f32> {
+        self.weight
+    }
+
+    pub fn data(&self) -> &E {
+        &self.data
+    }
+
+    /// The end which isn't `node`, or None if the edge doesn't touch it. Loops give back the node itself.
+    pub fn other(&self, node: &Node<N, E>) -> Option<&Arc<Node<N, E>>> {
+        if self.from.as_ref() == node {
+            Some(&self.to)
+        } else if self.to.as_ref() == node {
+            Some(&self.from)
+        } else {
+            None
+        }
+    }
+}
+
+impl<N: Clone, E: Clone> NetworkGraph<N, E> {
+    pub fn nodes(&self) -> impl Iterator<Item = &Arc<Node<N, E>>> {
+        self.nodes.iter()
+    }
+
+    /// Every edge exactly once, taken from the half stored by its source.
+    pub fn edges(&self) -> impl Iterator<Item = Edge<N, E>> + '_ {
+        self.nodes.iter().flat_map(|node| {
+            node.edges
+                .borrow()
+                .iter()
+                .filter(|conn| conn.outgoing)
+                .map(|conn| Edge::from_half(node, conn))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Every edge touching the node, whichever way it points. Loops are only listed once.
+    pub fn incident_edges(&self, node: &Arc<Node<N, E>>) -> impl Iterator<Item = Edge<N, E>> {
+        node.edges
+            .borrow()
+            .iter()
+            .filter(|conn| conn.outgoing || !conn.dst.eq(node))
+            .map(|conn| Edge::from_half(node, conn))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Edges running between the two nodes, in either direction.
+    pub fn edges_between(&self, a: &Arc<Node<N, E>>, b: &Arc<Node<N, E>>) -> impl Iterator<Item = Edge<N, E>> {
+        let (a, b) = (a.clone(), b.clone());
+
+        self.incident_edges(&a).filter(move |edge| matches!(edge.other(&a), Some(other) if other.eq(&b)))
+    }
+
+    /// Nodes sharing an edge with this one, each listed once, ignoring direction.
+    pub fn neighbours(&self, node: &Arc<Node<N, E>>) -> impl Iterator<Item = Arc<Node<N, E>>> {
+        Self::adjacent(node, |_| true)
+    }
+
+    /// Nodes this one has an edge pointing to.
+    pub fn successors(&self, node: &Arc<Node<N, E>>) -> impl Iterator<Item = Arc<Node<N, E>>> {
+        Self::adjacent(node, |conn| conn.outgoing)
+    }
+
+    /// Nodes with an edge pointing to this one.
+    pub fn predecessors(&self, node: &Arc<Node<N, E>>) -> impl Iterator<Item = Arc<Node<N, E>>> {
+        Self::adjacent(node, |conn| !conn.outgoing)
+    }
+
+    /// Number of edge ends at the node, so a loop counts twice.
+    pub fn degree(&self, node: &Arc<Node<N, E>>) -> usize {
+        node.edges.borrow().len()
+    }
+
+    pub fn out_degree(&self, node: &Arc<Node<N, E>>) -> usize {
+        node.edges.borrow().iter().filter(|conn| conn.outgoing).count()
+    }
+
+    pub fn in_degree(&self, node: &Arc<Node<N, E>>) -> usize {
+        node.edges.borrow().iter().filter(|conn| !conn.outgoing).count()
+    }
+
+    /// Everything within k edges of the node, ignoring direction, paired with how many hops away
+    /// it is. Comes out nearest first, starting with the node itself at 0.
+    pub fn k_hop(&self, node: &Arc<Node<N, E>>, k: usize) -> Vec<(Arc<Node<N, E>>, usize)> {
+        let mut seen = HashSet::new();
+        let mut found = vec![];
+        let mut queue = VecDeque::new();
+
+        seen.insert(node.name.clone());
+        queue.push_back((node.clone(), 0));
+
+        while let Some((current, hops)) = queue.pop_front() {
+            if hops < k {
+                for next in self.neighbours(&current) {
+                    if seen.insert(next.name.clone()) {
+                        queue.push_back((next, hops + 1));
+                    }
+                }
+            }
+
+            found.push((current, hops));
+        }
+
+        found
+    }
+
+    /// Far ends of the node's connections which pass the filter. Parallel edges only give
+    /// their neighbour once, in the order first seen.
+    fn adjacent(
+        node: &Arc<Node<N, E>>,
+        filter: impl Fn(&Connection<N, E>) -> bool,
+    ) -> std::vec::IntoIter<Arc<Node<N, E>>> {
+        let mut seen = HashSet::new();
+        let adjacent: Vec<_> = node
+            .edges
+            .borrow()
+            .iter()
+            .filter(|conn| filter(conn) && seen.insert(conn.dst.name.clone()))
+            .map(|conn| conn.dst.clone())
+            .collect();
+
+        adjacent.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Undirected edges are stored twice but should only be walked once.
+    #[test]
+    pub fn test_unique_edges () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        let a = graph.add_node(0);
+        let b = graph.add_node(1);
+        let c = graph.add_node(2);
+
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+        graph.add_edge(&c, &b);
+        graph.add_edge(&c, &c);
+
+        assert_eq!(graph.edges().count(), 4);
+        assert_eq!(graph.incident_edges(&c).count(), 3);
+        assert_eq!(graph.edges_between(&b, &c).count(), 2);
+        assert_eq!(graph.edges_between(&c, &c).count(), 1);
+        assert_eq!(graph.edges_between(&a, &c).count(), 0);
+
+        let edge = graph.edges_between(&a, &b).next().unwrap();
+        assert_eq!(edge.from().name(), "0");
+        assert_eq!(edge.other(&b).map(|node| node.name().as_str()), Some("0"));
+    }
+
+    /// Degrees count edge ends, neighbour lists skip repeats.
+    #[test]
+    pub fn test_neighbours () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        let a = graph.add_node(0);
+        let b = graph.add_node(1);
+        let c = graph.add_node(2);
+
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &b);
+        graph.add_edge(&c, &a);
+
+        let names = |nodes: Vec<Arc<Node<i32, ()>>>| nodes.iter().map(|node| node.name().clone()).collect::<Vec<_>>();
+
+        assert_eq!(names(graph.neighbours(&a).collect()), vec!["1", "2"]);
+        assert_eq!(names(graph.successors(&a).collect()), vec!["1"]);
+        assert_eq!(names(graph.predecessors(&a).collect()), vec!["2"]);
+
+        assert_eq!(graph.degree(&a), 3);
+        assert_eq!(graph.out_degree(&a), 2);
+        assert_eq!(graph.in_degree(&a), 1);
+    }
+
+    /// The k-hop neighbourhood grows one ring at a time.
+    #[test]
+    pub fn test_k_hop () {
+        let mut graph: NetworkGraph<i32, ()> = NetworkGraph::new();
+        let nodes: Vec<_> = (0..5).map(|i| graph.add_node(i)).collect();
+
+        for pair in nodes.windows(2) {
+            graph.add_edge(&pair[0], &pair[1]);
+        }
+
+        let hops: Vec<(String, usize)> = graph
+            .k_hop(&nodes[2], 1)
+            .into_iter()
+            .map(|(node, hops)| (node.name().clone(), hops))
+            .collect();
+
+        assert_eq!(hops, vec![(String::from("2"), 0), (String::from("1"), 1), (String::from("3"), 1)]);
+        assert_eq!(graph.k_hop(&nodes[0], 10).len(), 5);
+        assert_eq!(graph.k_hop(&nodes[0], 0).len(), 1);
+    }
+}
@@ -0,0 +1,555 @@
+use super::geometry::{Point, Rectangle, Size};
+use std::collections::HashMap;
+
+use super::change::Change;
+use super::layout::{distance_to_curve, distance_to_segment, LayoutGraph};
+
+/// Quadtree over where every node and edge is drawn, so hit-testing and working out what is on
+/// screen don't have to look at the whole graph. It only knows names and positions; the
+/// Document keeps it in step with the graph by passing on the graph's changes.
+#[derive(Debug, Default)]
+pub struct SpatialIndex {
+    nodes: Quadtree<String>,
+    edges: Quadtree<IndexedEdge>,
+    positions: HashMap<String, Point>,
+    /// Edges as they were filed, by (from, to), so they can be found again once they have moved
+    filed: HashMap<(String, String), Vec<IndexedEdge>>,
+}
+
+/// An edge as the index sees it: the names at each end and the line or curve between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedEdge {
+    pub from: String,
+    pub to: String,
+    pub from_pos: Point,
+    pub to_pos: Point,
+    pub control: Option<Point>,
+    /// Which of the edges drawn from `from` to `to` this is, for telling parallel edges apart
+    pub nth: usize,
+}
+
+impl IndexedEdge {
+    fn new(graph: &LayoutGraph, from: &str, to: &str, nth: usize) -> Self {
+        IndexedEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            from_pos: graph.position(from),
+            to_pos: graph.position(to),
+            control: graph.control(from, to, nth),
+            nth,
+        }
+    }
+
+    /// A quadratic curve never leaves the triangle of its three points, so their box covers it.
+    pub fn bounds(&self) -> Rectangle {
+        let points = [Some(self.from_pos), self.control, Some(self.to_pos)];
+
+        bounding_box(points.iter().flatten().copied()).unwrap_or_else(|| point_box(self.from_pos))
+    }
+
+    fn distance(&self, pos: Point) -> f32 {
+        match self.control {
+            None => distance_to_segment(pos, self.from_pos, self.to_pos),
+            Some(ctl) => distance_to_curve(pos, self.from_pos, ctl, self.to_pos),
+        }
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(graph: &LayoutGraph) -> Self {
+        let mut index = SpatialIndex::default();
+        let bounds = graph.bounds().unwrap_or_else(|| point_box(Point::ORIGIN));
+
+        index.nodes = Quadtree::new(bounds);
+        index.edges = Quadtree::new(bounds);
+
+        for node in graph.nodes() {
+            index.insert_node(node.name(), graph.position(node.name()));
+        }
+
+        let mut drawn: HashMap<(String, String), usize> = HashMap::new();
+
+        for edge in graph.edges() {
+            let nth = drawn.entry((edge.from().name().clone(), edge.to().name().clone())).or_default();
+            index.file_edge(IndexedEdge::new(graph, edge.from().name(), edge.to().name(), *nth));
+            *nth += 1;
+        }
+
+        index
+    }
+
+    /// Bring the index up to date with changes reported by the graph, reading back whatever they
+    /// touched. Returns the area covering everything which changed, before and after, so only
+    /// that much needs drawing again.
+    pub fn apply(&mut self, graph: &LayoutGraph, changes: &[Change]) -> Option<Rectangle> {
+        let mut dirty = None;
+
+        for change in changes {
+            let area = match change {
+                Change::NodeAdded(name) | Change::NodeRemoved(name) | Change::NodeUpdated(name) => {
+                    self.refresh_node(graph, name)
+                }
+                Change::EdgeAdded(from, to)
+                | Change::EdgeRemoved(from, to)
+                | Change::EdgeUpdated(from, to)
+                | Change::EdgeReversed(from, to) => {
+                    self.refresh_edges(graph, from, to)
+                }
+                // Every edge at the node is filed under the old name, simpler to start again
+                Change::NodeRenamed { .. } => {
+                    let old = self.extent();
+                    *self = SpatialIndex::new(graph);
+
+                    merge(old, self.extent())
+                }
+                Change::DirectedChanged | Change::GroupsChanged => None,
+            };
+
+            dirty = merge(dirty, area);
+        }
+
+        dirty
+    }
+
+    /// Area the index covers, if there is anything in it.
+    pub fn extent(&self) -> Option<Rectangle> {
+        merge(self.nodes.extent(), self.edges.extent())
+    }
+
+    pub fn position(&self, name: &str) -> Option<Point> {
+        self.positions.get(name).copied()
+    }
+
+    /// Closest node no further than tolerance from pos, skipping any the filter rejects.
+    pub fn nearest_node(&self, pos: Point, tolerance: f32, filter: impl Fn(&str) -> bool) -> Option<&String> {
+        let mut best: Option<(&String, f32)> = None;
+
+        self.nodes.visit(around(pos, tolerance), &mut |_, name| {
+            let distance = self.positions[name].distance(pos);
+
+            if distance <= tolerance && filter(name) && best.map(|(_, d)| distance < d).unwrap_or(true) {
+                best = Some((name, distance));
+            }
+        });
+
+        best.map(|(name, _)| name)
+    }
+
+    /// Closest edge, straight or curved, no further than tolerance from pos.
+    pub fn nearest_edge(&self, pos: Point, tolerance: f32, filter: impl Fn(&str, &str) -> bool) -> Option<&IndexedEdge> {
+        let mut best: Option<(&IndexedEdge, f32)> = None;
+
+        self.edges.visit(around(pos, tolerance), &mut |_, edge| {
+            let distance = edge.distance(pos);
+
+            if distance <= tolerance && filter(&edge.from, &edge.to) && best.map(|(_, d)| distance < d).unwrap_or(true) {
+                best = Some((edge, distance));
+            }
+        });
+
+        best.map(|(edge, _)| edge)
+    }
+
+    /// Every node inside the region, with its position.
+    pub fn nodes_in(&self, region: Rectangle) -> Vec<(&String, Point)> {
+        let mut found = vec![];
+        self.nodes.visit(region, &mut |_, name| found.push((name, self.positions[name])));
+
+        found
+    }
+
+    /// Every edge which might cross the region. Curves are judged by their bounding box.
+    pub fn edges_in(&self, region: Rectangle) -> Vec<&IndexedEdge> {
+        let mut found = vec![];
+        self.edges.visit(region, &mut |_, edge| found.push(edge));
+
+        found
+    }
+
+    fn insert_node(&mut self, name: &str, pos: Point) {
+        self.positions.insert(name.to_string(), pos);
+        self.nodes.insert(point_box(pos), name.to_string());
+    }
+
+    fn file_edge(&mut self, entry: IndexedEdge) {
+        self.filed.entry((entry.from.clone(), entry.to.clone())).or_default().push(entry.clone());
+        self.edges.insert(entry.bounds(), entry);
+    }
+
+    /// Take the node out and put it back wherever the graph now has it, if anywhere,
+    /// along with the edges which are drawn from its position.
+    fn refresh_node(&mut self, graph: &LayoutGraph, name: &str) -> Option<Rectangle> {
+        let mut dirty = None;
+
+        if let Some(old) = self.positions.remove(name) {
+            self.nodes.remove(point_box(old), |entry| entry == name);
+            dirty = Some(point_box(old));
+        }
+
+        if let Some(node) = graph.get_node(name) {
+            self.insert_node(name, graph.position(name));
+            dirty = merge(dirty, Some(point_box(graph.position(name))));
+
+            for other in graph.neighbours(&node) {
+                dirty = merge(dirty, self.refresh_edges(graph, name, other.name()));
+            }
+        }
+
+        dirty
+    }
+
+    /// Refile every edge between the two nodes, in either direction, as the graph now has them.
+    fn refresh_edges(&mut self, graph: &LayoutGraph, a: &str, b: &str) -> Option<Rectangle> {
+        let mut dirty = None;
+
+        for key in [(a.to_string(), b.to_string()), (b.to_string(), a.to_string())] {
+            for stale in self.filed.remove(&key).unwrap_or_default() {
+                dirty = merge(dirty, Some(stale.bounds()));
+                self.edges.remove(stale.bounds(), |entry| entry == &stale);
+            }
+        }
+
+        if let (Some(a), Some(b)) = (graph.get_node(a), graph.get_node(b)) {
+            let mut drawn = [0, 0];
+
+            for edge in graph.edges_between(&a, &b) {
+                let nth = &mut drawn[usize::from(edge.from().eq(&a))];
+                let entry = IndexedEdge::new(graph, edge.from().name(), edge.to().name(), *nth);
+                *nth += 1;
+
+                dirty = merge(dirty, Some(entry.bounds()));
+                self.file_edge(entry);
+            }
+        }
+
+        dirty
+    }
+}
+
+/// Entries are kept in the smallest quadrant which holds their whole box. Anything landing
+/// outside the root grows the tree so the root always covers everything.
+#[derive(Debug)]
+struct Quadtree<T> {
+    root: Quad<T>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Quad<T> {
+    bounds: Rectangle,
+    entries: Vec<(Rectangle, T)>,
+    children: Option<Box<[Quad<T>; 4]>>,
+}
+
+impl<T: PartialEq> Default for Quadtree<T> {
+    fn default() -> Self {
+        Quadtree::new(point_box(Point::ORIGIN))
+    }
+}
+
+/// Entries a quadrant holds before it splits.
+const QUAD_CAPACITY: usize = 8;
+const QUAD_DEPTH: usize = 16;
+/// Furthest out the tree grows. Boxes beyond it, or not at any real position at all, are
+/// filed at its edge instead, as growing to fit them would never finish.
+const QUAD_LIMIT: f32 = 1e30;
+
+impl<T: PartialEq> Quadtree<T> {
+    fn new(bounds: Rectangle) -> Self {
+        let bounds = within_limit(bounds);
+        // Never start from nothing, or growing to fit would take forever to get anywhere
+        let side = bounds.width.max(bounds.height).max(64.0);
+
+        Quadtree { root: Quad::new(Rectangle::new(bounds.position(), Size::new(side, side))), len: 0 }
+    }
+
+    fn insert(&mut self, area: Rectangle, item: T) {
+        let area = within_limit(area);
+
+        while !encloses(self.root.bounds, area) {
+            self.grow(area);
+        }
+
+        self.root.insert(area, item, 0);
+        self.len += 1;
+    }
+
+    /// Remove one entry filed under this box which matches.
+    fn remove(&mut self, area: Rectangle, matches: impl Fn(&T) -> bool) {
+        if self.root.remove(within_limit(area), &matches) {
+            self.len -= 1;
+        }
+    }
+
+    fn extent(&self) -> Option<Rectangle> {
+        (self.len > 0).then_some(self.root.bounds)
+    }
+
+    fn visit<'a>(&'a self, region: Rectangle, visitor: &mut impl FnMut(&'a Rectangle, &'a T)) {
+        if self.len > 0 {
+            self.root.visit(region, visitor);
+        }
+    }
+
+    /// Double the root towards area, rebuilding everything underneath.
+    fn grow(&mut self, area: Rectangle) {
+        let old = self.root.bounds;
+        let x = if area.x < old.x { old.x - old.width } else { old.x };
+        let y = if area.y < old.y { old.y - old.height } else { old.y };
+
+        let mut entries = vec![];
+        self.root.drain(&mut entries);
+        self.root = Quad::new(Rectangle::new(Point::new(x, y), Size::new(old.width * 2.0, old.height * 2.0)));
+
+        for (area, item) in entries {
+            self.root.insert(area, item, 0);
+        }
+    }
+}
+
+impl<T: PartialEq> Quad<T> {
+    fn new(bounds: Rectangle) -> Self {
+        Quad { bounds, entries: vec![], children: None }
+    }
+
+    fn insert(&mut self, area: Rectangle, item: T, depth: usize) {
+        if let Some(child) = self.child_for(area) {
+            return child.insert(area, item, depth + 1);
+        }
+
+        self.entries.push((area, item));
+
+        if self.children.is_none() && self.entries.len() > QUAD_CAPACITY && depth < QUAD_DEPTH {
+            self.split(depth);
+        }
+    }
+
+    fn remove(&mut self, area: Rectangle, matches: &impl Fn(&T) -> bool) -> bool {
+        if let Some(child) = self.child_for(area) {
+            return child.remove(area, matches);
+        }
+
+        match self.entries.iter().position(|(_, item)| matches(item)) {
+            Some(index) => {
+                self.entries.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn visit<'a>(&'a self, region: Rectangle, visitor: &mut impl FnMut(&'a Rectangle, &'a T)) {
+        for (area, item) in self.entries.iter().filter(|(area, _)| overlaps(*area, region)) {
+            visitor(area, item);
+        }
+
+        for child in self.children.iter().flat_map(|children| children.iter()) {
+            if overlaps(child.bounds, region) {
+                child.visit(region, visitor);
+            }
+        }
+    }
+
+    fn split(&mut self, depth: usize) {
+        let (x, y) = (self.bounds.x, self.bounds.y);
+        let size = Size::new(self.bounds.width / 2.0, self.bounds.height / 2.0);
+
+        self.children = Some(Box::new([
+            Quad::new(Rectangle::new(Point::new(x, y), size)),
+            Quad::new(Rectangle::new(Point::new(x + size.width, y), size)),
+            Quad::new(Rectangle::new(Point::new(x, y + size.height), size)),
+            Quad::new(Rectangle::new(Point::new(x + size.width, y + size.height), size)),
+        ]));
+
+        for (area, item) in std::mem::take(&mut self.entries) {
+            self.insert(area, item, depth);
+        }
+    }
+
+    fn child_for(&mut self, area: Rectangle) -> Option<&mut Quad<T>> {
+        self.children.as_mut()?.iter_mut().find(|child| encloses(child.bounds, area))
+    }
+
+    fn drain(&mut self, into: &mut Vec<(Rectangle, T)>) {
+        into.append(&mut self.entries);
+
+        if let Some(children) = self.children.take() {
+            let children: [Quad<T>; 4] = *children;
+
+            for mut child in children {
+                child.drain(into);
+            }
+        }
+    }
+}
+
+fn point_box(pos: Point) -> Rectangle {
+    Rectangle::new(pos, Size::new(0.0, 0.0))
+}
+
+/// The box cut down to what the tree can cover, with NaN taken as 0.
+fn within_limit(area: Rectangle) -> Rectangle {
+    let clamp = |value: f32| if value.is_nan() { 0.0 } else { value.clamp(-QUAD_LIMIT, QUAD_LIMIT) };
+    let (x, y) = (clamp(area.x), clamp(area.y));
+    let (right, bottom) = (clamp(area.x + area.width), clamp(area.y + area.height));
+
+    Rectangle::new(Point::new(x, y), Size::new((right - x).max(0.0), (bottom - y).max(0.0)))
+}
+
+fn around(pos: Point, distance: f32) -> Rectangle {
+    Rectangle::new(Point::new(pos.x - distance, pos.y - distance), Size::new(2.0 * distance, 2.0 * distance))
+}
+
+fn bounding_box(mut points: impl Iterator<Item = Point>) -> Option<Rectangle> {
+    let first = points.next()?;
+    let (min, max) = points.fold((first, first), |(min, max), pos| {
+        (Point::new(min.x.min(pos.x), min.y.min(pos.y)), Point::new(max.x.max(pos.x), max.y.max(pos.y)))
+    });
+
+    Some(Rectangle::new(min, Size::new(max.x - min.x, max.y - min.y)))
+}
+
+/// Smallest rectangle holding both.
+pub fn union(a: Rectangle, b: Rectangle) -> Rectangle {
+    let corners = [
+        Point::new(a.x, a.y),
+        Point::new(a.x + a.width, a.y + a.height),
+        Point::new(b.x, b.y),
+        Point::new(b.x + b.width, b.y + b.height),
+    ];
+
+    bounding_box(corners.into_iter()).unwrap_or(a)
+}
+
+/// Union of whichever of the two there are.
+pub fn merge(a: Option<Rectangle>, b: Option<Rectangle>) -> Option<Rectangle> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(union(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn overlaps(a: Rectangle, b: Rectangle) -> bool {
+    a.x <= b.x + b.width && b.x <= a.x + a.width && a.y <= b.y + b.height && b.y <= a.y + a.height
+}
+
+fn encloses(outer: Rectangle, inner: Rectangle) -> bool {
+    outer.x <= inner.x
+        && outer.y <= inner.y
+        && inner.x + inner.width <= outer.x + outer.width
+        && inner.y + inner.height <= outer.y + outer.height
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// The nearest node wins, not whichever happens to come first.
+    #[test]
+    pub fn test_nearest_node () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        graph.add_node(Point::new(4.0, 0.0));
+
+        for i in 0..100 {
+            graph.add_node(Point::new(i as f32 * 50.0, 500.0));
+        }
+
+        let index = SpatialIndex::new(&graph);
+
+        assert_eq!(index.nearest_node(Point::new(3.0, 0.0), 5.0, |_| true).map(|name| name.as_str()), Some("1"));
+        assert_eq!(index.nearest_node(Point::new(3.0, 0.0), 5.0, |name| name != "1").map(|name| name.as_str()), Some("0"));
+        assert_eq!(index.nearest_node(Point::new(2450.0, 502.0), 5.0, |_| true).map(|name| name.as_str()), Some("51"));
+        assert!(index.nearest_node(Point::new(25.0, 500.0), 5.0, |_| true).is_none());
+    }
+
+    /// Moving a node, even well outside where the tree started, carries its edges along.
+    #[test]
+    pub fn test_move_node () {
+        let mut graph = LayoutGraph::new();
+        let a = graph.add_node(Point::new(0.0, 0.0));
+        let b = graph.add_node(Point::new(100.0, 0.0));
+        graph.add_curve(&a, &b, Point::new(50.0, 50.0));
+
+        let mut index = SpatialIndex::new(&graph);
+        assert_eq!(index.nearest_edge(Point::new(50.0, 25.0), 2.0, |_, _| true).map(|edge| edge.to.as_str()), Some("1"));
+
+        graph.move_node(&b, Point::new(-5000.0, 0.0));
+        let dirty = index.apply(&graph, &[Change::NodeUpdated(String::from("1"))]);
+
+        assert_eq!(dirty, Some(Rectangle::new(Point::new(-5000.0, 0.0), Size::new(5100.0, 50.0))));
+
+        assert!(index.nearest_node(Point::new(100.0, 0.0), 5.0, |_| true).is_none());
+        assert_eq!(index.nearest_node(Point::new(-5000.0, 0.0), 5.0, |_| true).map(|name| name.as_str()), Some("1"));
+        assert!(index.nearest_edge(Point::new(50.0, 25.0), 2.0, |_, _| true).is_none());
+        assert_eq!(index.edges_in(Rectangle::new(Point::new(-3000.0, -10.0), Size::new(10.0, 50.0))).len(), 1);
+    }
+
+    /// Only what overlaps the region comes back.
+    #[test]
+    pub fn test_region_query () {
+        let mut graph = LayoutGraph::new();
+        let nodes: Vec<_> = (0..1000).map(|i| graph.add_node(Point::new((i % 100) as f32 * 10.0, (i / 100) as f32 * 10.0))).collect();
+
+        for pair in nodes.windows(2) {
+            graph.add_edge(&pair[0], &pair[1]);
+        }
+
+        let index = SpatialIndex::new(&graph);
+        let region = Rectangle::new(Point::new(-1.0, -1.0), Size::new(22.0, 12.0));
+
+        assert_eq!(index.nodes_in(region).len(), 6);
+        assert_eq!(index.edges_in(region).len(), 8);
+    }
+
+    /// Nodes at infinite or NaN positions are filed at the edge of the tree rather than growing
+    /// it forever, and can still be moved back and found again.
+    #[test]
+    pub fn test_non_finite_position () {
+        let mut graph = LayoutGraph::new();
+        graph.add_node(Point::new(0.0, 0.0));
+        let lost = graph.add_node(Point::new(f32::NAN, 0.0));
+        graph.add_node(Point::new(f32::NEG_INFINITY, f32::INFINITY));
+        graph.add_node(Point::new(f32::MAX, 10.0));
+
+        let mut index = SpatialIndex::new(&graph);
+        assert_eq!(index.nearest_node(Point::new(1.0, 0.0), 5.0, |_| true).map(|name| name.as_str()), Some("0"));
+
+        graph.move_node(&lost, Point::new(50.0, 0.0));
+        index.apply(&graph, &[Change::NodeUpdated(String::from("1"))]);
+        assert_eq!(index.nearest_node(Point::new(50.0, 1.0), 5.0, |_| true).map(|name| name.as_str()), Some("1"));
+        assert_eq!(index.nodes_in(Rectangle::new(Point::new(-1e31, -1e31), Size::new(2e31, 2e31))).len(), 4);
+    }
+
+    /// Changes reported by the graph are enough to keep the index in step, including removals.
+    #[test]
+    pub fn test_apply_changes () {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut graph = LayoutGraph::new();
+        let a = graph.add_node(Point::new(0.0, 0.0));
+        let b = graph.add_node(Point::new(100.0, 0.0));
+
+        let mut index = SpatialIndex::new(&graph);
+        let changes = Rc::new(RefCell::new(vec![]));
+        let queue = changes.clone();
+        graph.subscribe(move |batch| queue.borrow_mut().extend_from_slice(batch));
+
+        graph.add_edge(&a, &b);
+        let c = graph.add_node(Point::new(50.0, 50.0));
+        graph.add_edge(&b, &c);
+        index.apply(&graph, &changes.borrow_mut().split_off(0));
+
+        assert_eq!(index.nearest_node(Point::new(50.0, 48.0), 5.0, |_| true).map(|name| name.as_str()), Some("2"));
+        assert_eq!(index.edges_in(Rectangle::new(Point::new(-1.0, -1.0), Size::new(200.0, 100.0))).len(), 2);
+
+        graph.remove_node(&b);
+        let dirty = index.apply(&graph, &changes.borrow_mut().split_off(0));
+
+        assert_eq!(dirty, Some(Rectangle::new(Point::new(0.0, 0.0), Size::new(100.0, 50.0))));
+        assert!(index.nearest_node(Point::new(100.0, 0.0), 5.0, |_| true).is_none());
+        assert!(index.edges_in(Rectangle::new(Point::new(-1.0, -1.0), Size::new(200.0, 100.0))).is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+use super::geometry::Point;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use super::attribute::drawn;
+use super::layout::Layout;
+use super::{Connection, NetworkGraph, Node};
+
+// Checks that the graph's own bookkeeping holds together. Every edge is stored as two halves,
+// one on each end, and nodes are found through a name lookup, so there is plenty which could
+// drift apart without anything noticing until much later.
+
+/// Payloads which can hold values the rest of the program can't cope with.
+pub trait Valid: Sized {
+    fn is_valid(&self) -> bool;
+
+    /// Nearest usable value, for repairs.
+    fn repaired(&self) -> Self;
+}
+
+impl Valid for Point {
+    fn is_valid(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+
+    fn repaired(&self) -> Self {
+        let fix = |value: f32| if value.is_finite() { value } else { 0.0 };
+
+        Point::new(fix(self.x), fix(self.y))
+    }
+}
+
+/// Nothing carried, nothing to go wrong.
+impl Valid for () {
+    fn is_valid(&self) -> bool {
+        true
+    }
+
+    fn repaired(&self) -> Self {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Halves of the edge stored on one end with no matching half on the other, or the
+    /// other way round. Given the way the edge was drawn.
+    Asymmetric { from: String, to: String },
+    /// A connection to a node which isn't in the graph, or is an out of date copy of one
+    Dangling { node: String, missing: String },
+    DuplicateName(String),
+    /// The name lookup doesn't agree with the node list
+    Lookup(String),
+    /// A group naming a node which isn't there. Collapsed groups only need their summary.
+    MissingMember { group: String, member: String },
+    /// What the node carries or where it is drawn can't be used, e.g. a NaN position
+    InvalidNode(String),
+    /// A control point, weight or payload which can't be used, e.g. an infinite weight
+    InvalidEdge { from: String, to: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Asymmetric { from, to } => write!(f, "edge {} -> {} isn't stored the same on both ends", from, to),
+            Violation::Dangling { node, missing } => write!(f, "{} is connected to {}, which isn't in the graph", node, missing),
+            Violation::DuplicateName(name) => write!(f, "more than one node is called {}", name),
+            Violation::Lookup(name) => write!(f, "lookup for {} points at the wrong node", name),
+            Violation::MissingMember { group, member } => write!(f, "group {} includes {}, which isn't in the graph", group, member),
+            Violation::InvalidNode(name) => write!(f, "node {} has an unusable value", name),
+            Violation::InvalidEdge { from, to } => write!(f, "edge {} -> {} has an unusable value", from, to),
+        }
+    }
+}
+
+/// Everything validate found wrong, in no particular order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for violation in self.violations.iter() {
+            writeln!(f, "{}", violation)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Identifies the halves of one edge: drawn from, drawn to, and the weight's bits so NaN
+/// weights still compare equal to themselves.
+type EdgeKey = (String, String, Option<u32>);
+
+impl<N: Clone + Valid, E: Clone + Valid> NetworkGraph<N, E> {
+    /// Check every invariant the rest of the graph relies on.
+    pub fn validate(&self) -> Report {
+        let mut violations = vec![];
+        let mut seen = HashSet::new();
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !seen.insert(node.name.as_str()) {
+                violations.push(Violation::DuplicateName(node.name.clone()));
+            } else if self.lookup.get(&node.name) != Some(&i) {
+                violations.push(Violation::Lookup(node.name.clone()));
+            }
+
+            if !node.data.is_valid() {
+                violations.push(Violation::InvalidNode(node.name.clone()));
+            }
+        }
+
+        for (name, &i) in self.lookup.iter() {
+            if !matches!(self.nodes.get(i), Some(node) if node.name == *name) {
+                violations.push(Violation::Lookup(name.clone()));
+            }
+        }
+
+        // Ordered so the same graph always gives the same report
+        let mut keys = BTreeSet::new();
+
+        for node in self.nodes.iter() {
+            for conn in node.edges.borrow().iter() {
+                if !self.holds(&conn.dst) {
+                    violations.push(Violation::Dangling { node: node.name.clone(), missing: conn.dst.name.clone() });
+                    continue;
+                }
+
+                if conn.outgoing && (!conn.data.is_valid() || conn.weight.is_some_and(|weight| !weight.is_finite())) {
+                    violations.push(Violation::InvalidEdge { from: node.name.clone(), to: conn.dst.name.clone() });
+                }
+
+                keys.insert(Self::key(node, conn));
+            }
+        }
+
+        for (from, to, weight) in keys {
+            let (source, dest) = (self.get_node(&from), self.get_node(&to));
+
+            if let (Some(source), Some(dest)) = (source, dest) {
+                if Self::count(&source, &dest, true, weight) != Self::count(&dest, &source, false, weight) {
+                    violations.push(Violation::Asymmetric { from, to });
+                }
+            }
+        }
+
+        for group in self.groups.iter() {
+            let needed = match group.summary() {
+                Some(summary) => vec![summary.clone()],
+                None => group.members().clone(),
+            };
+
+            for member in needed.into_iter().filter(|member| self.get_node(member).is_none()) {
+                violations.push(Violation::MissingMember { group: group.name().clone(), member });
+            }
+        }
+
+        Report { violations }
+    }
+
+    /// Fix whatever validate finds, returning what that was. Duplicate nodes after the first are
+    /// dropped, connections to stale copies are pointed back at the real node and ones to missing
+    /// nodes dropped. Where the two ends of an edge disagree the end it was drawn from wins.
+    /// Weights which aren't finite are taken off. Observers only hear about the payloads replaced, so this is best done straight after loading.
+    pub fn repair(&mut self) -> Report {
+        let report = self.validate();
+
+        if report.is_ok() {
+            return report;
+        }
+
+        let mut seen = HashSet::new();
+        for node in std::mem::take(&mut self.nodes) {
+            if seen.insert(node.name.clone()) {
+                self.nodes.push(node);
+            } else {
+                node.edges.borrow_mut().clear();
+            }
+        }
+        self.lookup = self.nodes.iter().enumerate().map(|(i, node)| (node.name.clone(), i)).collect();
+
+        for node in self.nodes.iter() {
+            node.edges.borrow_mut().retain_mut(|conn| {
+                if self.holds(&conn.dst) {
+                    return true;
+                }
+
+                match self.get_node(&conn.dst.name) {
+                    Some(current) => {
+                        conn.dst = current;
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+
+        self.mirror_halves();
+        self.prune_groups();
+
+        // Both halves carry the weight, so both lose it
+        for node in self.nodes.iter() {
+            for conn in node.edges.borrow_mut().iter_mut().filter(|conn| conn.weight.is_some_and(|weight| !weight.is_finite())) {
+                conn.weight = None;
+            }
+        }
+
+        let broken: Vec<_> = self.nodes.iter().filter(|node| !node.data.is_valid()).cloned().collect();
+        for node in broken {
+            self.set_node_data(&node, node.data.repaired());
+        }
+
+        let broken: Vec<_> = self.edges().filter(|edge| !edge.data().is_valid()).collect();
+        for edge in broken {
+            self.set_edge_data(edge.from(), edge.to(), edge.data().repaired());
+        }
+
+        report
+    }
+
+    /// Give every half stored on the end an edge was drawn from a matching half on the
+    /// other end, and drop halves on the far end which have nothing to match.
+    fn mirror_halves(&self) {
+        let mut keys: HashMap<EdgeKey, E> = HashMap::new();
+
+        for node in self.nodes.iter() {
+            for conn in node.edges.borrow().iter() {
+                let key = Self::key(node, conn);
+
+                if conn.outgoing || !keys.contains_key(&key) {
+                    keys.insert(key, conn.data.clone());
+                }
+            }
+        }
+
+        for ((from, to, weight), data) in keys {
+            let (source, dest) = match (self.get_node(&from), self.get_node(&to)) {
+                (Some(source), Some(dest)) => (source, dest),
+                _ => continue,
+            };
+
+            let (drawn, mirrored) = (Self::count(&source, &dest, true, weight), Self::count(&dest, &source, false, weight));
+
+            for _ in mirrored..drawn {
+                dest.edges.borrow_mut().push(Connection {
+                    dst: source.clone(),
+                    weight: weight.map(f32::from_bits),
+                    data: data.clone(),
+                    outgoing: false,
+                });
+            }
+
+            let mut surplus = mirrored.saturating_sub(drawn);
+            dest.edges.borrow_mut().retain(|conn| {
+                let extra = surplus > 0 && Self::matches(conn, &source, false, weight);
+                if extra {
+                    surplus -= 1;
+                }
+
+                !extra
+            });
+        }
+    }
+
+    /// True if this is the very node the graph holds under its name.
+    fn holds(&self, node: &Arc<Node<N, E>>) -> bool {
+        matches!(self.lookup.get(&node.name).and_then(|&i| self.nodes.get(i)), Some(held) if Arc::ptr_eq(held, node))
+    }
+
+    fn key(node: &Node<N, E>, conn: &Connection<N, E>) -> EdgeKey {
+        let weight = conn.weight.map(f32::to_bits);
+
+        if conn.outgoing {
+            (node.name.clone(), conn.dst.name.clone(), weight)
+        } else {
+            (conn.dst.name.clone(), node.name.clone(), weight)
+        }
+    }
+
+    /// Halves stored on `node` pointing at `other`, facing the given way, with this weight.
+    fn count(node: &Node<N, E>, other: &Arc<Node<N, E>>, outgoing: bool, weight: Option<u32>) -> usize {
+        node.edges.borrow().iter().filter(|conn| Self::matches(conn, other, outgoing, weight)).count()
+    }
+
+    fn matches(conn: &Connection<N, E>, other: &Arc<Node<N, E>>, outgoing: bool, weight: Option<u32>) -> bool {
+        conn.dst.eq(other) && conn.outgoing == outgoing && conn.weight.map(f32::to_bits) == weight
+    }
+}
+
+impl<N: Clone + Valid, E: Clone + Valid> Layout<N, E> {
+    /// As for the graph, and also that every node has a usable position and every curve a
+    /// usable control point.
+    pub fn validate(&self) -> Report {
+        let mut report = self.graph.validate();
+
+        for node in self.nodes.iter().filter(|node| !self.positions.get(&node.name).is_some_and(Valid::is_valid)) {
+            report.violations.push(Violation::InvalidNode(node.name.clone()));
+        }
+
+        // Ordered so the same graph always gives the same report
+        let broken: BTreeSet<_> = self
+            .controls
+            .iter()
+            .filter(|(_, ctl)| !ctl.is_valid())
+            .map(|((from, to, _), _)| (from, to))
+            .collect();
+
+        for (from, to) in broken {
+            report.violations.push(Violation::InvalidEdge { from: from.clone(), to: to.clone() });
+        }
+
+        report
+    }
+
+    /// Repair the graph, then put nodes with no usable position at the nearest usable one and
+    /// straighten unusable curves, along with any left over from edges repairs dropped.
+    pub fn repair(&mut self) -> Report {
+        let report = self.validate();
+
+        if report.is_ok() {
+            return report;
+        }
+
+        self.graph.repair();
+
+        let names: HashSet<_> = self.nodes.iter().map(|node| node.name.clone()).collect();
+        self.positions.retain(|name, _| names.contains(name));
+
+        for node in self.nodes.clone() {
+            let pos = self.position(&node.name);
+
+            if !pos.is_valid() || !self.positions.contains_key(&node.name) {
+                self.move_node(&node, pos.repaired());
+            }
+        }
+
+        let graph = &self.graph;
+        self.controls.retain(|(from, to, nth), ctl| ctl.is_valid() && *nth < drawn(graph, from, to));
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netwk::layout::LayoutGraph;
+
+    fn sample() -> LayoutGraph {
+        let mut graph = LayoutGraph::new();
+        let a = graph.add_node(Point::new(0.0, 0.0));
+        let b = graph.add_node(Point::new(10.0, 0.0));
+
+        graph.add_edge(&a, &b);
+        graph.add_edge_with(&a, &b, Some(2.0), None);
+        graph.add_edge_with(&b, &b, None, Some(Point::new(20.0, 20.0)));
+
+        graph
+    }
+
+    /// Parallel edges, loops and groups are all fine as they are.
+    #[test]
+    pub fn test_validate_clean () {
+        let mut graph = sample();
+        let members: HashSet<String> = [String::from("0")].into_iter().collect();
+        graph.create_group(String::from("Site"), &members);
+
+        assert!(graph.validate().is_ok());
+
+        graph.collapse_group("Site");
+        assert!(graph.validate().is_ok());
+    }
+
+    /// Halves out of step are spotted, and the end an edge was drawn from is taken as right.
+    #[test]
+    pub fn test_repair_halves () {
+        let mut graph = sample();
+        let (a, b) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap());
+
+        // One drawn half with no mirror, one mirror half with nothing drawn
+        a.edges.borrow_mut().push(Connection { dst: b.clone(), weight: Some(5.0), data: (), outgoing: true });
+        a.edges.borrow_mut().push(Connection { dst: b.clone(), weight: Some(9.0), data: (), outgoing: false });
+
+        let report = graph.validate();
+        assert_eq!(report.violations.len(), 2);
+        assert!(report.violations.contains(&Violation::Asymmetric { from: String::from("0"), to: String::from("1") }));
+        assert!(report.violations.contains(&Violation::Asymmetric { from: String::from("1"), to: String::from("0") }));
+
+        assert_eq!(graph.repair(), report);
+        assert!(graph.validate().is_ok());
+        assert_eq!(graph.edges().count(), 4);
+        assert!(graph.edges().any(|edge| edge.weight() == Some(5.0)));
+    }
+
+    /// Duplicate names, stale and missing neighbours, NaN positions and curves, and lost group members.
+    #[test]
+    pub fn test_repair_references () {
+        let mut graph = sample();
+        let a = graph.get_node("0").unwrap();
+        graph.add_node(Point::new(30.0, 0.0));
+
+        let members: HashSet<String> = ["1", "2"].into_iter().map(String::from).collect();
+        graph.create_group(String::from("Site"), &members);
+        graph.graph.retain_nodes(|node| node.name() != "2");
+
+        // Replacing a node without telling its neighbours leaves them holding the old copy
+        let moved = Node::new(String::from("1"), ());
+        *moved.edges.borrow_mut() = graph.get_node("1").unwrap().edges.borrow().clone();
+        graph.graph.nodes[1] = moved;
+        graph.positions.insert(String::from("1"), Point::new(f32::NAN, 5.0));
+
+        a.edges.borrow_mut().push(Connection { dst: Node::new(String::from("ghost"), ()), weight: None, data: (), outgoing: true });
+        graph.push_node(Node::new(String::from("0"), ()), Point::ORIGIN);
+        graph.controls.insert((String::from("0"), String::from("1"), 0), Point::new(f32::INFINITY, 0.0));
+
+        let report = graph.validate();
+        assert!(report.violations.contains(&Violation::DuplicateName(String::from("0"))));
+        assert!(report.violations.contains(&Violation::Dangling { node: String::from("0"), missing: String::from("1") }));
+        assert!(report.violations.contains(&Violation::Dangling { node: String::from("0"), missing: String::from("ghost") }));
+        assert!(report.violations.contains(&Violation::InvalidNode(String::from("1"))));
+        assert!(report.violations.contains(&Violation::InvalidEdge { from: String::from("0"), to: String::from("1") }));
+        assert!(report.violations.contains(&Violation::MissingMember { group: String::from("Site"), member: String::from("2") }));
+
+        graph.repair();
+
+        assert!(graph.validate().is_ok(), "{}", graph.validate());
+        assert_eq!(graph.node_list().len(), 2);
+        assert_eq!(graph.position("1"), Point::new(0.0, 5.0));
+        assert!(graph.control("0", "1", 0).is_none());
+        assert!(graph.control("1", "1", 0).is_some());
+        assert_eq!(graph.edges().count(), 3);
+        assert_eq!(graph.groups()[0].members(), &vec![String::from("1")]);
+    }
+
+    /// NaN and infinite weights are spotted and taken off both halves.
+    #[test]
+    pub fn test_repair_weights () {
+        let mut graph = sample();
+        let (a, b) = (graph.get_node("0").unwrap(), graph.get_node("1").unwrap());
+        graph.add_edge_with(&b, &a, Some(f32::NAN), None);
+        graph.add_edge_with(&a, &b, Some(f32::INFINITY), None);
+
+        let report = graph.validate();
+        assert_eq!(report.violations, vec![
+            Violation::InvalidEdge { from: String::from("0"), to: String::from("1") },
+            Violation::InvalidEdge { from: String::from("1"), to: String::from("0") },
+        ]);
+
+        assert_eq!(graph.repair(), report);
+        assert!(graph.validate().is_ok(), "{}", graph.validate());
+        assert_eq!(graph.edges().count(), 5);
+        assert!(graph.edges().all(|edge| edge.weight().is_none_or(f32::is_finite)));
+    }
+}
@@ -1,26 +1,134 @@
-use iced::pure::widget::canvas;
+use std::collections::{HashMap, HashSet};
+
 use iced::pure::widget::Button;
 use iced::pure::widget::Canvas;
+use iced::pure::widget::Checkbox;
 use iced::pure::widget::Column;
 use iced::pure::widget::Row;
+use iced::pure::widget::Scrollable;
+use iced::pure::widget::Text;
+use iced::pure::widget::TextInput;
+use iced::pure::Application;
 use iced::pure::Element;
-use iced::pure::Sandbox;
 use iced::Alignment;
-use iced::Point;
+use iced::{clipboard, executor, Color, Command, Point, Vector};
 
-use crate::canvas::mode::{Mode, PlaceCurveProgress, PlaceEdgeProgress};
-use crate::canvas::NetworkCanvas;
-use crate::netwk::NetworkGraph;
+use crate::analysis::{self, Analysis, Table};
+use crate::canvas::camera::Camera;
+use crate::canvas::mode::{Mode, PairTool, PlaceCurveProgress, PlaceEdgeProgress};
+use crate::canvas::style::NodeStyle;
+use crate::canvas::{NetworkCanvas, View};
+use crate::netwk::algorithms::coloring::Strategy;
+use crate::netwk::algorithms::path::Method;
+use crate::netwk::algorithms::Network;
+use crate::netwk::annotation::{Shape, ShapeKind};
+use crate::netwk::arrange::spring_layout;
+use crate::netwk::attribute::EdgeAttributes;
+use crate::netwk::document::{Document, Snapshot};
+use crate::netwk::format::{DocumentData, GraphData};
+use crate::netwk::geometry;
+use crate::netwk::history::History;
+use crate::netwk::job::{Job, Progress, Status};
+use crate::netwk::layer::Layers;
 
 /// This creates the UI for the application.
 /// NetworkUI holds any data needed by the application.
 /// Message defines the message passing protocol from the components to the internal state
 
-#[derive(Default)]
 pub struct NetworkUI {
     pen_mode: Option<Mode>,
-    graph: NetworkGraph,
-    canvas_cache: canvas::Cache,
+    tabs: Vec<Tab>,
+    active: usize,
+    prompt: Option<Prompt>,
+    /// Shared between tabs so things can be copied from one document into another
+    clipboard: Option<GraphData>,
+    frame_times: bool,
+    /// Work running in the background. Only one at a time; starting another cancels it
+    job: Option<Running>,
+    next_job: usize,
+    /// Used by the shortest path tool
+    path_method: Method,
+    /// Whether spanning trees are the heaviest rather than the lightest
+    spanning_max: bool,
+    /// Used when colouring nodes
+    coloring: Strategy,
+    /// Something the user should know about, shown until dismissed
+    status: Option<String>,
+}
+
+/// How the application was started from the command line.
+#[derive(Debug, Default)]
+pub struct Flags {
+    /// Opened in place of the usual empty document
+    pub document: Option<DocumentData>,
+    /// Show how long the canvas takes to build each frame
+    pub frame_times: bool,
+}
+
+/// One open document, with its own undo history, selection and views.
+struct Tab {
+    document: Document,
+    history: History,
+    /// The document as of its last undo step, which the next one will go back to
+    current: Snapshot,
+    /// File the document was last saved to or opened from
+    path: Option<String>,
+    /// What was last saved, to tell whether there are unsaved changes
+    saved: DocumentData,
+    dirty: bool,
+    selection: HashSet<String>,
+    node_styles: HashMap<String, NodeStyle>,
+    /// One view, or two side by side when split
+    views: Vec<View>,
+    /// Result of the last analysis, until cleared or the graph changes
+    analysis: Option<Analysis>,
+    /// Counts edits, so a job started before the latest can tell its result is out of date
+    generation: usize,
+}
+
+/// A background job and what it is being done for.
+struct Running {
+    id: usize,
+    job: Job<Outcome>,
+    /// Tab the result goes back to, for jobs working on a document
+    tab: Option<usize>,
+    /// The tab's generation when the job started
+    generation: usize,
+    status: Status,
+}
+
+/// What a finished job hands back to be applied on the UI thread.
+enum Outcome {
+    /// New positions for the nodes of the job's tab
+    Arrange(HashMap<String, geometry::Point>),
+    /// A file read in, or None if it couldn't be
+    Open(String, Option<DocumentData>),
+    /// Findings for the job's tab
+    Analysis(Analysis),
+}
+
+/// Text entry shown above the canvas for edits which need a typed value.
+#[derive(Debug, Clone)]
+struct Prompt {
+    kind: PromptKind,
+    value: String,
+}
+
+#[derive(Debug, Clone)]
+enum PromptKind {
+    RenameNode(String),
+    EdgeWeight(String, String),
+    AnnotationText(usize),
+    GroupName,
+    LayerName,
+    Save,
+    Open,
+    ExportDot,
+    ExportCsv,
+    /// Amount for a tool run between two nodes, such as how many paths to find
+    PairAmount(PairTool, String, String),
+    /// Capacity and cost, comma separated
+    EdgeAttributes(String, String, usize),
 }
 
 #[derive(Debug, Clone)]
@@ -28,93 +136,1714 @@ pub enum Message {
     Ack,
     ChangePenMode(Mode),
     AddNode(Point),
-    RemoveNode,
+    RemoveNode(String),
     AddEdge(Point, Point),
     AddCurve(Point, Point, Point),
-    RemoveEdge,
-    EditNode,
-    EditEdge,
+    RemoveEdge(String, String),
+    EditNode(String),
+    EditEdge(String, String),
+    EditEdgeAttributes(String, String, usize),
+    ReverseEdge(String, String),
+    SetEdgeControl(String, String, Option<Point>),
+    SelectNeighbours(String),
+    SelectAll,
+    SelectNode(String, bool),
+    ClearSelection,
+    Copy,
+    Cut,
+    CopyAsDot,
+    Paste(Point),
+    Pasted(Option<String>, Point),
+    Duplicate,
+    CycleNodeStyle(String),
+    MoveNode(String, Vector),
+    GroupSelection,
+    MoveGroup(String, Vector),
+    CollapseGroup(String),
+    ExpandGroup(String),
+    Ungroup(String),
+    AddAnnotation(Shape),
+    MoveAnnotation(usize, Vector),
+    RemoveAnnotation(usize),
+    EditAnnotationText(usize),
+    CycleAnnotationColor(usize),
+    ToggleAnnotationFill(usize),
+    ToggleAnnotationAbove(usize),
+    AddLayer,
+    RemoveLayer(usize),
+    SetActiveLayer(usize),
+    SetLayerVisible(usize, bool),
+    SetLayerLocked(usize, bool),
+    MoveSelectionToLayer,
+    SaveAs,
+    OpenFrom,
+    ExportDot,
+    SetCamera(usize, Camera),
+    ToggleDirected,
+    Undo,
+    Redo,
+    NewTab,
+    SelectTab(usize),
+    CloseTab(usize),
+    ToggleSplit,
+    PromptChanged(String),
+    PromptSubmit,
+    PromptCancel,
     Clear,
+    Arrange,
+    JobProgress(usize, Status),
+    CancelJob,
+    PickedPair(PairTool, Point, Point),
+    Distances,
+    ExportTable,
+    ShowStep(Option<usize>),
+    SpanningTree,
+    Connectivity,
+    ToggleSpanningMax,
+    ExtractSubgraph,
+    CyclePathMethod,
+    ColorNodes,
+    ColorEdges,
+    CycleColoring,
+    FindCycle,
+    TopologicalOrder,
+    SimpleCycles,
+    Centrality,
+    SortTable(usize),
+    ClearAnalysis,
+    DismissStatus,
+    Repair,
 }
 
-impl Sandbox for NetworkUI {
+impl Application for NetworkUI {
+    type Executor = executor::Default;
     type Message = Message;
+    type Flags = Flags;
 
-    fn new() -> Self {
-        NetworkUI::default()
+    fn new(flags: Flags) -> (Self, Command<Message>) {
+        let mut ui = NetworkUI { frame_times: flags.frame_times, ..NetworkUI::default() };
+
+        if let Some(data) = flags.document {
+            ui.tabs = vec![Tab::from_data(data, None)];
+            ui.report_repairs(0);
+        }
+
+        (ui, Command::none())
     }
 
     fn title(&self) -> String {
-        String::from("ntwk ui")
+        let tab = &self.tabs[self.active];
+
+        format!("{}{} - ntwk ui", tab.title(), if tab.dirty { "*" } else { "" })
     }
 
-    fn update(&mut self, message: Self::Message) {
+    fn update(&mut self, message: Self::Message) -> Command<Message> {
+        let mut command = Command::none();
+
+        // Edits are recorded after the fact against the last snapshot, which is only behind the
+        // document after a drag. Later steps of a drag are covered by the undo step from its first.
+        let editing = self.active;
+        let edit = message.is_edit();
+        let group = message.edit_group();
+
+        if edit && !self.tabs[editing].history.continues(group.as_deref()) {
+            self.tabs[editing].catch_up();
+        }
+
+        let tab = &mut self.tabs[self.active];
+
         self.pen_mode = match message {
             Message::Clear => {
-                self.graph = NetworkGraph::default();
-                self.canvas_cache.clear();
+                tab.document = Document::default();
+                tab.selection.clear();
+                tab.node_styles.clear();
+                tab.redraw();
 
                 None
             }
             Message::AddNode(point) => {
-                self.graph.add_node(&point);
-                self.canvas_cache.clear();
+                tab.document.add_node(point.into());
+
+                None
+            }
+            Message::RemoveNode(name) => {
+                if tab.document.remove_node(&name) {
+                    tab.selection.remove(&name);
+                    tab.node_styles.remove(&name);
+                }
 
                 None
             }
             Message::AddEdge(from, to) => {
-                if let Some(point_from) = self.graph.get_exact_point(from) {
-                    if let Some(point_to) = self.graph.get_exact_point(to) {
-                        self.graph.add_edge(&point_from, &point_to);
+                let index = tab.document.index();
 
-                        self.canvas_cache.clear();
-                    }
+                if let (Some(from), Some(to)) = (index.nearest_node(from.into(), 0.0, |_| true), index.nearest_node(to.into(), 0.0, |_| true)) {
+                    let (from, to) = (from.clone(), to.clone());
+                    tab.document.add_edge(&from, &to, None);
                 }
 
                 None
             }
             Message::AddCurve(from, to, control) => {
-                if let Some(from) = self.graph.get_exact_point(from) {
-                    if let Some(to) = self.graph.get_exact_point(to) {
-                        self.graph.add_curve(&from, &to, control);
-                        self.canvas_cache.clear();
+                let index = tab.document.index();
+
+                if let (Some(from), Some(to)) = (index.nearest_node(from.into(), 0.0, |_| true), index.nearest_node(to.into(), 0.0, |_| true)) {
+                    let (from, to) = (from.clone(), to.clone());
+                    tab.document.add_edge(&from, &to, Some(control.into()));
+                }
+
+                None
+            }
+            Message::RemoveEdge(from, to) => {
+                tab.document.remove_edge(&from, &to);
+
+                None
+            }
+            Message::EditNode(name) => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::RenameNode(name.clone()),
+                    value: name,
+                });
+
+                None
+            }
+            Message::EditEdge(from, to) => {
+                let graph = &tab.document.graph;
+                let weight = match (graph.get_node(&from), graph.get_node(&to)) {
+                    (Some(from), Some(to)) => graph.edges_between(&from, &to).find_map(|edge| edge.weight()),
+                    _ => None,
+                };
+
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::EdgeWeight(from, to),
+                    value: weight.map(|w| w.to_string()).unwrap_or_default(),
+                });
+
+                None
+            }
+            Message::EditEdgeAttributes(from, to, nth) => {
+                let attributes = tab.document.attributes().edge(&from, &to, nth);
+                let show = |value: Option<f32>| value.map(|value| value.to_string()).unwrap_or_default();
+
+                self.prompt = Some(Prompt {
+                    value: format!("{}, {}", show(attributes.capacity), show(attributes.cost)),
+                    kind: PromptKind::EdgeAttributes(from, to, nth),
+                });
+
+                None
+            }
+            Message::ReverseEdge(from, to) => {
+                if let (Some(from), Some(to)) = (tab.document.graph.get_node(&from), tab.document.graph.get_node(&to)) {
+                    tab.document.graph.reverse_edge(&from, &to);
+                }
+
+                None
+            }
+            Message::SetEdgeControl(from, to, control) => {
+                if let (Some(from), Some(to)) = (tab.document.graph.get_node(&from), tab.document.graph.get_node(&to)) {
+                    tab.document.graph.set_control(&from, &to, control.map(Into::into));
+                }
+
+                None
+            }
+            Message::SelectNeighbours(name) => {
+                if let Some(node) = tab.document.graph.get_node(&name) {
+                    tab.selection = tab.document.graph.neighbours(&node).map(|node| node.name().clone()).collect();
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::SelectAll => {
+                tab.selection = tab.document.interactive_nodes();
+                tab.redraw_overlays();
+
+                None
+            }
+            Message::SelectNode(name, extend) => {
+                if !extend {
+                    tab.selection.clear();
+                }
+                if !tab.selection.remove(&name) {
+                    tab.selection.insert(name);
+                }
+                tab.redraw_overlays();
+
+                None
+            }
+            Message::ClearSelection => {
+                if !tab.selection.is_empty() {
+                    tab.selection.clear();
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::Copy => {
+                command = copy_to_clipboard(tab.document.export(&tab.selection), &mut self.clipboard);
+
+                None
+            }
+            Message::Cut => {
+                // Locked nodes stay put, so only what can be removed is taken
+                let names: HashSet<String> =
+                    tab.selection.iter().filter(|name| tab.document.layers().node_interactive(name)).cloned().collect();
+                command = copy_to_clipboard(tab.document.export(&names), &mut self.clipboard);
+
+                for name in names {
+                    tab.document.remove_node(&name);
+                    tab.selection.remove(&name);
+                    tab.node_styles.remove(&name);
+                }
+
+                None
+            }
+            Message::CopyAsDot => {
+                let data = tab.document.export(&tab.selection);
+
+                if !data.is_empty() {
+                    command = clipboard::write(data.to_dot());
+                }
+
+                None
+            }
+            Message::Paste(position) => {
+                command = clipboard::read(move |text| Message::Pasted(text, position));
+
+                None
+            }
+            Message::Pasted(text, position) => {
+                // Prefer whatever is on the system clipboard so pastes work between windows,
+                // falling back to our own copy if it isn't something we wrote.
+                let data = text
+                    .as_deref()
+                    .and_then(GraphData::from_json)
+                    .or_else(|| self.clipboard.clone());
+
+                if let Some(data) = data {
+                    if let Some(region) = data.bounds() {
+                        tab.paste(&data, geometry::Point::from(position) - region.center());
                     }
                 }
 
                 None
             }
-            Message::ChangePenMode(mode) => Some(mode),
-            _ => None,
-        };
-        // If we didn't want to change the mode of the canvas pen here then set it to None so it doesnt change.
-    }
+            Message::Duplicate => {
+                let data = tab.document.export(&tab.selection);
+                tab.paste(&data, geometry::Vector::new(20.0, 20.0));
 
-    fn view(&self) -> Element<'_, Self::Message> {
-        Column::new()
-            .padding(20)
-            .align_items(Alignment::Center)
-            .push(
-                Row::new()
-                    .padding(20)
-                    .align_items(Alignment::Fill)
-                    .push(Button::new("Add Node").on_press(Message::ChangePenMode(Mode::PlaceNode)))
-                    .push(Button::new("Add Edge").on_press(Message::ChangePenMode(
-                        Mode::PlaceEdge(PlaceEdgeProgress::None),
-                    )))
-                    .push(Button::new("Add Curve").on_press(Message::ChangePenMode(
-                        Mode::PlaceCurve(PlaceCurveProgress::None),
-                    )))
-                    .push(Button::new("Clear").on_press(Message::Clear)),
-            )
-            .push(
-                Canvas::new(NetworkCanvas::new(
-                    &self.graph,
-                    &self.canvas_cache,
-                    &self.pen_mode,
-                ))
-                .width(iced::Length::Fill)
-                .height(iced::Length::Fill),
-            )
-            .into()
+                None
+            }
+            Message::CycleNodeStyle(name) => {
+                let style = tab.node_styles.entry(name).or_default();
+                *style = style.next();
+                tab.redraw();
+
+                None
+            }
+            Message::MoveNode(name, delta) => {
+                if let Some(node) = tab.document.graph.get_node(&name) {
+                    tab.document.graph.move_node(&node, tab.document.graph.position(&name) + delta.into());
+                }
+
+                None
+            }
+            Message::GroupSelection => {
+                if !tab.selection.is_empty() {
+                    self.prompt = Some(Prompt { kind: PromptKind::GroupName, value: String::new() });
+                }
+
+                None
+            }
+            Message::MoveGroup(name, delta) => {
+                tab.document.graph.move_group(&name, delta.into());
+
+                None
+            }
+            Message::CollapseGroup(name) => {
+                tab.document.graph.collapse_group(&name);
+                tab.selection.clear();
+
+                None
+            }
+            Message::ExpandGroup(name) => {
+                tab.document.graph.expand_group(&name);
+                tab.selection.clear();
+
+                None
+            }
+            Message::Ungroup(name) => {
+                tab.document.graph.remove_group(&name);
+
+                None
+            }
+            Message::AddAnnotation(shape) => {
+                let text = shape.kind() == ShapeKind::Text;
+                let id = tab.document.add_annotation(shape);
+
+                if text {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::AnnotationText(id),
+                        value: String::new(),
+                    });
+                }
+                tab.redraw_overlays();
+
+                None
+            }
+            Message::MoveAnnotation(id, delta) => {
+                if let Some(annotation) = tab.document.annotations_mut().get_mut(id) {
+                    annotation.shape.translate(delta.into());
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::RemoveAnnotation(id) => {
+                tab.document.remove_annotation(id);
+                tab.redraw_overlays();
+
+                None
+            }
+            Message::EditAnnotationText(id) => {
+                if let Some(Shape::Text { content, .. }) = tab.document.annotations().get(id).map(|a| &a.shape) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::AnnotationText(id),
+                        value: content.clone(),
+                    });
+                }
+
+                None
+            }
+            Message::CycleAnnotationColor(id) => {
+                if let Some(annotation) = tab.document.annotations_mut().get_mut(id) {
+                    let next = ANNOTATION_COLORS
+                        .iter()
+                        .position(|&color| geometry::Color::from(color) == annotation.style.color)
+                        .map(|i| (i + 1) % ANNOTATION_COLORS.len())
+                        .unwrap_or(0);
+
+                    annotation.style.color = ANNOTATION_COLORS[next].into();
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::ToggleAnnotationFill(id) => {
+                if let Some(annotation) = tab.document.annotations_mut().get_mut(id) {
+                    annotation.style.filled = !annotation.style.filled;
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::ToggleAnnotationAbove(id) => {
+                if let Some(annotation) = tab.document.annotations_mut().get_mut(id) {
+                    annotation.above = !annotation.above;
+                    tab.redraw_overlays();
+                }
+
+                None
+            }
+            Message::AddLayer => {
+                self.prompt = Some(Prompt { kind: PromptKind::LayerName, value: String::new() });
+
+                None
+            }
+            Message::RemoveLayer(id) => {
+                tab.document.layers_mut().remove(id);
+                tab.redraw();
+
+                None
+            }
+            Message::SetActiveLayer(id) => {
+                tab.document.layers_mut().set_active(id);
+
+                None
+            }
+            Message::SetLayerVisible(id, visible) => {
+                if let Some(layer) = tab.document.layers_mut().get_mut(id) {
+                    layer.visible = visible;
+                }
+                // Hidden nodes can't stay selected or they'd get copied and moved about unseen
+                let visible_nodes = tab.document.visible_nodes();
+                tab.selection.retain(|name| visible_nodes.contains(name));
+                tab.redraw();
+
+                None
+            }
+            Message::SetLayerLocked(id, locked) => {
+                if let Some(layer) = tab.document.layers_mut().get_mut(id) {
+                    layer.locked = locked;
+                }
+
+                None
+            }
+            Message::MoveSelectionToLayer => {
+                let active = tab.document.layers().active();
+                tab.document.move_to_layer(&tab.selection, active);
+                tab.redraw();
+
+                None
+            }
+            Message::ExportDot => {
+                self.prompt = Some(Prompt { kind: PromptKind::ExportDot, value: String::from("diagram.dot") });
+
+                None
+            }
+            Message::SaveAs => {
+                let value = tab.path.clone().unwrap_or_else(|| String::from("diagram.json"));
+                self.prompt = Some(Prompt { kind: PromptKind::Save, value });
+
+                None
+            }
+            Message::OpenFrom => {
+                self.prompt = Some(Prompt { kind: PromptKind::Open, value: String::from("diagram.json") });
+
+                None
+            }
+            Message::SetCamera(index, camera) => {
+                if let Some(view) = tab.views.get_mut(index) {
+                    // Tiles only depend on the scale, so a pan can keep them
+                    if view.camera.scale == camera.scale {
+                        view.moved();
+                    } else {
+                        view.clear();
+                    }
+                    view.camera = camera;
+                }
+
+                None
+            }
+            Message::ToggleDirected => {
+                tab.document.graph.set_directed(!tab.document.graph.is_directed());
+
+                None
+            }
+            Message::PromptChanged(value) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.value = value;
+                }
+
+                None
+            }
+            Message::PromptSubmit => {
+                if let Some(prompt) = self.prompt.take() {
+                    command = self.submit_prompt(prompt);
+                }
+
+                None
+            }
+            Message::PromptCancel => {
+                self.prompt = None;
+
+                None
+            }
+            Message::DismissStatus => {
+                self.status = None;
+
+                None
+            }
+            Message::Repair => {
+                if tab.document.repair().is_some() {
+                    tab.redraw();
+                }
+
+                None
+            }
+            Message::Undo => {
+                tab.catch_up();
+                if let Some(snapshot) = tab.history.undo(tab.current.clone()) {
+                    tab.restore(snapshot);
+                }
+
+                None
+            }
+            Message::Redo => {
+                tab.catch_up();
+                if let Some(snapshot) = tab.history.redo(tab.current.clone()) {
+                    tab.restore(snapshot);
+                }
+
+                None
+            }
+            Message::ToggleSplit => {
+                if tab.views.len() > 1 {
+                    tab.views.truncate(1);
+                } else {
+                    // Start the new view where the old one is, it can be moved off from there
+                    let camera = tab.views[0].camera;
+                    tab.views.push(View { camera, ..View::default() });
+                }
+
+                None
+            }
+            Message::NewTab => {
+                self.tabs.push(Tab::new());
+                self.active = self.tabs.len() - 1;
+                self.prompt = None;
+
+                None
+            }
+            Message::SelectTab(index) => {
+                if index < self.tabs.len() {
+                    self.active = index;
+                    self.prompt = None;
+                }
+
+                None
+            }
+            Message::CloseTab(index) => {
+                if index < self.tabs.len() {
+                    self.tabs.remove(index);
+                    self.forget_tab(index);
+                }
+                if self.tabs.is_empty() {
+                    self.tabs.push(Tab::new());
+                }
+                if self.active > index || self.active >= self.tabs.len() {
+                    self.active = self.active.saturating_sub(1);
+                }
+                self.prompt = None;
+
+                None
+            }
+            Message::Arrange => {
+                let data = tab.document.graph.to_data();
+                command = self.start_job(Some(editing), "Arranging", move |progress| {
+                    spring_layout(&data, progress).map(Outcome::Arrange)
+                });
+
+                None
+            }
+            Message::JobProgress(id, status) => {
+                command = self.job_progress(id, status);
+
+                None
+            }
+            Message::CancelJob => {
+                if let Some(running) = &self.job {
+                    running.job.cancel();
+                }
+
+                None
+            }
+            Message::PickedPair(tool, from, to) => {
+                let index = tab.document.index();
+                let (from, to) = (index.nearest_node(from.into(), 0.0, |_| true).cloned(), index.nearest_node(to.into(), 0.0, |_| true).cloned());
+
+                if let (Some(from), Some(to)) = (from, to) {
+                    command = self.run_pair_tool(editing, tool, &from, &to, None);
+                }
+
+                None
+            }
+            Message::Distances => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Measuring distances", move |progress| {
+                    analysis::distances(&net, progress).map(Outcome::Analysis)
+                });
+
+                None
+            }
+            Message::ExportTable => {
+                self.prompt = Some(Prompt { kind: PromptKind::ExportCsv, value: String::from("table.csv") });
+
+                None
+            }
+            Message::SpanningTree => {
+                let net = Network::new(&tab.document.graph);
+                let maximum = self.spanning_max;
+                command = self.start_job(Some(editing), "Finding spanning tree", move |_| {
+                    Some(Outcome::Analysis(analysis::spanning_forest(&net, maximum)))
+                });
+
+                None
+            }
+            Message::Connectivity => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Finding components", move |_| {
+                    Some(Outcome::Analysis(analysis::connectivity(&net)))
+                });
+
+                None
+            }
+            Message::ToggleSpanningMax => {
+                self.spanning_max = !self.spanning_max;
+
+                None
+            }
+            Message::ExtractSubgraph => {
+                if let Some(analysis) = &tab.analysis {
+                    let mut graph = tab.document.graph.to_data();
+                    analysis.extract(&mut graph);
+
+                    self.tabs.push(Tab::from_data(DocumentData { graph, ..DocumentData::default() }, None));
+                    self.active = self.tabs.len() - 1;
+                    self.prompt = None;
+                }
+
+                None
+            }
+            Message::ShowStep(step) => {
+                if let Some(analysis) = &mut tab.analysis {
+                    analysis.step = step;
+                    tab.redraw();
+                }
+
+                None
+            }
+            Message::CyclePathMethod => {
+                self.path_method = self.path_method.next();
+
+                None
+            }
+            Message::ColorNodes => {
+                let net = Network::new(&tab.document.graph);
+                let strategy = self.coloring;
+                command = self.start_job(Some(editing), "Colouring", move |progress| {
+                    analysis::node_coloring(&net, strategy, progress).map(Outcome::Analysis)
+                });
+
+                None
+            }
+            Message::ColorEdges => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Colouring edges", move |_| {
+                    Some(Outcome::Analysis(analysis::edge_coloring(&net)))
+                });
+
+                None
+            }
+            Message::CycleColoring => {
+                self.coloring = self.coloring.next();
+
+                None
+            }
+            Message::FindCycle => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Looking for cycles", move |_| {
+                    Some(Outcome::Analysis(analysis::find_cycle(&net)))
+                });
+
+                None
+            }
+            Message::TopologicalOrder => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Ordering", move |_| {
+                    Some(Outcome::Analysis(analysis::topological_order(&net)))
+                });
+
+                None
+            }
+            Message::Centrality => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Measuring centrality", move |progress| {
+                    analysis::centrality(&net, progress).map(Outcome::Analysis)
+                });
+
+                None
+            }
+            Message::SortTable(column) => {
+                if let Some(analysis) = &mut tab.analysis {
+                    analysis.sort_table(column);
+                }
+
+                None
+            }
+            Message::SimpleCycles => {
+                let net = Network::new(&tab.document.graph);
+                command = self.start_job(Some(editing), "Finding cycles", move |progress| {
+                    analysis::simple_cycles(&net, progress).map(Outcome::Analysis)
+                });
+
+                None
+            }
+            Message::ClearAnalysis => {
+                tab.clear_analysis();
+
+                None
+            }
+            Message::ChangePenMode(mode) => Some(mode),
+            _ => None,
+        };
+        // If we didn't want to change the mode of the canvas pen here then set it to None so it doesnt change.
+
+        // Redraw whatever the graph reports changing, however it was edited
+        if let Some(tab) = self.tabs.get_mut(editing) {
+            tab.sync();
+        }
+        self.report_repairs(editing);
+
+        match self.tabs.get_mut(editing) {
+            Some(tab) if edit => tab.commit(group),
+            Some(tab) => tab.history.seal(),
+            None => (),
+        }
+
+        command
+    }
+
+    fn view(&self) -> Element<'_, Self::Message> {
+        let tab = &self.tabs[self.active];
+
+        let mut content = Column::new()
+            .padding(20)
+            .align_items(Alignment::Center)
+            .push(self.tab_bar())
+            .push(
+                Row::new()
+                    .padding(20)
+                    .align_items(Alignment::Fill)
+                    .push(Button::new("Add Node").on_press(Message::ChangePenMode(Mode::PlaceNode)))
+                    .push(Button::new("Add Edge").on_press(Message::ChangePenMode(
+                        Mode::PlaceEdge(PlaceEdgeProgress::None),
+                    )))
+                    .push(Button::new("Add Curve").on_press(Message::ChangePenMode(
+                        Mode::PlaceCurve(PlaceCurveProgress::None),
+                    )))
+                    .push(Button::new(if tab.document.graph.is_directed() { "Directed" } else { "Undirected" })
+                        .on_press(Message::ToggleDirected))
+                    .push(Button::new("Clear").on_press(Message::Clear))
+                    .push(undo_button("Undo", tab.history.can_undo(), Message::Undo))
+                    .push(undo_button("Redo", tab.history.can_redo(), Message::Redo))
+                    .push(Button::new(if tab.views.len() > 1 { "Unsplit" } else { "Split" })
+                        .on_press(Message::ToggleSplit))
+                    .push(Button::new("Arrange").on_press(Message::Arrange)),
+            )
+            .push(
+                Row::new()
+                    .align_items(Alignment::Fill)
+                    .push(Button::new("Text").on_press(annotate(ShapeKind::Text)))
+                    .push(Button::new("Box").on_press(annotate(ShapeKind::Rectangle)))
+                    .push(Button::new("Ellipse").on_press(annotate(ShapeKind::Ellipse)))
+                    .push(Button::new("Arrow").on_press(annotate(ShapeKind::Arrow)))
+                    .push(Button::new("Open").on_press(Message::OpenFrom))
+                    .push(Button::new("Save").on_press(Message::SaveAs))
+                    .push(Button::new("Export").on_press(Message::ExportDot)),
+            )
+            .push(
+                Row::new()
+                    .align_items(Alignment::Fill)
+                    .push(Button::new("Shortest path").on_press(Message::ChangePenMode(
+                        Mode::PickPair(PairTool::ShortestPath, None),
+                    )))
+                    .push(Button::new(Text::new(self.path_method.to_string())).on_press(Message::CyclePathMethod))
+                    .push(Button::new("k paths").on_press(Message::ChangePenMode(
+                        Mode::PickPair(PairTool::KShortest, None),
+                    )))
+                    .push(Button::new("Equal cost").on_press(Message::ChangePenMode(
+                        Mode::PickPair(PairTool::EqualCost, None),
+                    )))
+                    .push(Button::new("Distances").on_press(Message::Distances))
+                    .push(Button::new("Spanning tree").on_press(Message::SpanningTree))
+                    .push(Button::new(if self.spanning_max { "Max" } else { "Min" }).on_press(Message::ToggleSpanningMax))
+                    .push(Button::new("Connectivity").on_press(Message::Connectivity))
+                    .push(Button::new("Max flow").on_press(Message::ChangePenMode(
+                        Mode::PickPair(PairTool::MaxFlow, None),
+                    )))
+                    .push(Button::new("Min cost flow").on_press(Message::ChangePenMode(
+                        Mode::PickPair(PairTool::MinCostFlow, None),
+                    ))),
+            )
+            .push(
+                Row::new()
+                    .align_items(Alignment::Fill)
+                    .push(Button::new("Colour nodes").on_press(Message::ColorNodes))
+                    .push(Button::new(Text::new(self.coloring.to_string())).on_press(Message::CycleColoring))
+                    .push(Button::new("Colour edges").on_press(Message::ColorEdges))
+                    .push(Button::new("Find cycle").on_press(Message::FindCycle))
+                    .push(Button::new("Topological order").on_press(Message::TopologicalOrder))
+                    .push(Button::new("All cycles").on_press(Message::SimpleCycles))
+                    .push(Button::new("Centrality").on_press(Message::Centrality)),
+            );
+
+        if let Some(running) = &self.job {
+            let label = if running.job.is_cancelled() { String::from("Cancelling") } else { running.status.stage.clone() };
+
+            content = content.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(format!("{}... {:.0}%", label, running.status.fraction * 100.0)))
+                    .push(Button::new("Cancel").on_press(Message::CancelJob)),
+            );
+        }
+
+        if let Some(status) = &self.status {
+            content = content.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(status.as_str()))
+                    .push(Button::new("Dismiss").on_press(Message::DismissStatus)),
+            );
+        }
+
+        if let Some(tab) = self.tabs.get(self.active).filter(|tab| !tab.document.problems().is_ok()) {
+            content = content.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(format!("Found problems in {}:\n{}", tab.title(), tab.document.problems())))
+                    .push(Button::new("Repair").on_press(Message::Repair)),
+            );
+        }
+
+        if let Some(prompt) = &self.prompt {
+            let placeholder = match prompt.kind {
+                PromptKind::RenameNode(_) => "Node name",
+                PromptKind::EdgeWeight(_, _) => "Weight (blank for none)",
+                PromptKind::AnnotationText(_) => "Text",
+                PromptKind::GroupName => "Group name",
+                PromptKind::LayerName => "Layer name",
+                PromptKind::Save => "Save to file",
+                PromptKind::Open => "Open file",
+                PromptKind::ExportDot => "Export DOT to file",
+                PromptKind::ExportCsv => "Export table to file",
+                PromptKind::PairAmount(PairTool::KShortest, _, _) => "Number of paths",
+                PromptKind::PairAmount(_, _, _) => "Demand",
+                PromptKind::EdgeAttributes(..) => "Capacity, cost (blank for the weight)",
+            };
+
+            content = content.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new(placeholder, &prompt.value, Message::PromptChanged)
+                            .on_submit(Message::PromptSubmit)
+                            .padding(5),
+                    )
+                    .push(Button::new("OK").on_press(Message::PromptSubmit))
+                    .push(Button::new("Cancel").on_press(Message::PromptCancel)),
+            );
+        }
+
+        if let Some(panel) = self.analysis_panel() {
+            content = content.push(panel);
+        }
+
+        // Every view draws the same document, so an edit made in one shows up in the others
+        let canvases = tab.views.iter().enumerate().fold(Row::new().spacing(10), |row, (index, view)| {
+            row.push(
+                Canvas::new(NetworkCanvas::new(
+                    &tab.document,
+                    view,
+                    index,
+                    &self.pen_mode,
+                    &tab.selection,
+                    &tab.node_styles,
+                    tab.analysis.as_ref().map(Analysis::shown),
+                    self.frame_times,
+                ))
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill),
+            )
+        });
+
+        content
+            .push(canvases.push(self.layers_panel()))
+            .into()
+    }
+}
+
+/// Colours annotations cycle through from their context menu.
+const ANNOTATION_COLORS: [Color; 4] = [
+    Color { r: 0.4, g: 0.4, b: 0.4, a: 1.0 },
+    Color { r: 0.85, g: 0.2, b: 0.2, a: 1.0 },
+    Color { r: 0.2, g: 0.4, b: 0.85, a: 1.0 },
+    Color { r: 0.2, g: 0.65, b: 0.3, a: 1.0 },
+];
+
+fn annotate(kind: ShapeKind) -> Message {
+    Message::ChangePenMode(Mode::PlaceAnnotation(kind, None))
+}
+
+/// Buttons without a message are shown disabled.
+fn undo_button(label: &str, enabled: bool, message: Message) -> Button<'_, Message> {
+    let button = Button::new(label);
+
+    if enabled {
+        button.on_press(message)
+    } else {
+        button
+    }
+}
+
+/// Most of a table that is shown in the panel; the rest is only in the CSV export.
+const TABLE_ROWS: usize = 50;
+const TABLE_COLUMNS: usize = 10;
+
+/// The top left of a table, scrolling downwards. Clicking a heading sorts by that column.
+fn table_view(table: &Table, sorted: Option<(usize, bool)>) -> Element<'_, Message> {
+    let mut rows = table
+        .rows
+        .iter()
+        .take(TABLE_ROWS)
+        .fold(Column::new().spacing(2), |rows, cells| rows.push(table_row(cells)));
+
+    if table.rows.len() > TABLE_ROWS || table.header.len() > TABLE_COLUMNS {
+        rows = rows.push(Text::new(format!("{} rows in all, export for the rest", table.rows.len())).size(14));
+    }
+
+    Column::new()
+        .spacing(2)
+        .push(table_header(&table.header, sorted))
+        .push(Scrollable::new(rows).height(iced::Length::Units(160)))
+        .into()
+}
+
+/// Buttons for looking at each step on its own, or all of them together.
+fn steps_view(analysis: &Analysis) -> Element<'_, Message> {
+    let marked = |label: &str, shown: bool| if shown { format!("> {}", label) } else { label.to_string() };
+
+    let steps = analysis.steps.iter().enumerate().fold(Column::new().spacing(2), |steps, (i, step)| {
+        steps.push(
+            Button::new(Text::new(marked(&step.label, analysis.step == Some(i))).size(14))
+                .on_press(Message::ShowStep(Some(i))),
+        )
+    });
+
+    Column::new()
+        .spacing(2)
+        .push(Button::new(Text::new(marked("All", analysis.step.is_none())).size(14)).on_press(Message::ShowStep(None)))
+        .push(Scrollable::new(steps).height(iced::Length::Units(160)))
+        .into()
+}
+
+fn table_header(header: &[String], sorted: Option<(usize, bool)>) -> Row<'_, Message> {
+    header.iter().enumerate().take(TABLE_COLUMNS).fold(Row::new().spacing(5), |row, (i, heading)| {
+        let label = match sorted {
+            Some((column, true)) if column == i => format!("{} v", heading),
+            Some((column, false)) if column == i => format!("{} ^", heading),
+            _ => heading.clone(),
+        };
+
+        row.push(Button::new(Text::new(label).size(14)).width(iced::Length::Units(70)).on_press(Message::SortTable(i)))
+    })
+}
+
+fn table_row(cells: &[String]) -> Row<'_, Message> {
+    cells.iter().take(TABLE_COLUMNS).fold(Row::new().spacing(5), |row, cell| {
+        row.push(Text::new(cell).size(14).width(iced::Length::Units(70)))
+    })
+}
+
+/// Keep a copy of the subgraph and put it on the system clipboard as JSON.
+fn copy_to_clipboard(data: GraphData, store: &mut Option<GraphData>) -> Command<Message> {
+    if data.is_empty() {
+        return Command::none();
+    }
+
+    let text = data.to_json();
+    *store = Some(data);
+
+    clipboard::write(text)
+}
+
+impl Message {
+    /// Could handling this message change the document. Only these are given an undo step.
+    fn is_edit(&self) -> bool {
+        !matches!(
+            self,
+            Message::Ack
+                | Message::ChangePenMode(_)
+                | Message::EditNode(_)
+                | Message::EditEdge(_, _)
+                | Message::EditEdgeAttributes(..)
+                | Message::SelectNeighbours(_)
+                | Message::SelectAll
+                | Message::SelectNode(_, _)
+                | Message::ClearSelection
+                | Message::Copy
+                | Message::CopyAsDot
+                | Message::Paste(_)
+                | Message::CycleNodeStyle(_)
+                | Message::GroupSelection
+                | Message::EditAnnotationText(_)
+                | Message::AddLayer
+                | Message::SaveAs
+                | Message::OpenFrom
+                | Message::ExportDot
+                | Message::SetCamera(_, _)
+                | Message::Undo
+                | Message::Redo
+                | Message::NewTab
+                | Message::SelectTab(_)
+                | Message::CloseTab(_)
+                | Message::ToggleSplit
+                | Message::PromptChanged(_)
+                | Message::PromptCancel
+                | Message::Arrange
+                // Results are applied to whichever tab the job is for, which records its own undo step
+                | Message::JobProgress(_, _)
+                | Message::CancelJob
+                | Message::PickedPair(_, _, _)
+                | Message::Distances
+                | Message::ExportTable
+                | Message::ShowStep(_)
+                | Message::SpanningTree
+                | Message::Connectivity
+                | Message::ToggleSpanningMax
+                | Message::ExtractSubgraph
+                | Message::CyclePathMethod
+                | Message::ColorNodes
+                | Message::ColorEdges
+                | Message::CycleColoring
+                | Message::FindCycle
+                | Message::TopologicalOrder
+                | Message::SimpleCycles
+                | Message::Centrality
+                | Message::SortTable(_)
+                | Message::ClearAnalysis
+                | Message::DismissStatus
+        )
+    }
+
+    /// Drags arrive as a stream of small moves, which are undone together.
+    fn edit_group(&self) -> Option<String> {
+        match self {
+            Message::MoveNode(name, _) => Some(format!("node {}", name)),
+            Message::MoveGroup(name, _) => Some(format!("group {}", name)),
+            Message::MoveAnnotation(id, _) => Some(format!("annotation {}", id)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for NetworkUI {
+    fn default() -> Self {
+        NetworkUI {
+            pen_mode: None,
+            tabs: vec![Tab::new()],
+            active: 0,
+            prompt: None,
+            clipboard: None,
+            frame_times: false,
+            job: None,
+            next_job: 0,
+            path_method: Method::Dijkstra,
+            spanning_max: false,
+            coloring: Strategy::DSatur,
+            status: None,
+        }
+    }
+}
+
+impl Tab {
+    fn new() -> Self {
+        Tab::from_data(DocumentData::default(), None)
+    }
+
+    fn from_data(data: DocumentData, path: Option<String>) -> Self {
+        let document = Document::from_data(&data);
+
+        Tab {
+            // Compare against the document's own idea of its data, in case loading tidied anything up
+            saved: document.to_data(),
+            current: document.snapshot(),
+            document,
+            history: History::new(),
+            path,
+            dirty: false,
+            selection: HashSet::new(),
+            node_styles: HashMap::new(),
+            views: vec![View::default()],
+            analysis: None,
+            generation: 0,
+        }
+    }
+
+    /// File name without the directories, or a placeholder for a document never saved.
+    fn title(&self) -> String {
+        self.path
+            .as_deref()
+            .and_then(|path| std::path::Path::new(path).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("Untitled"))
+    }
+
+    fn redraw(&mut self) {
+        for view in self.views.iter_mut() {
+            view.clear();
+        }
+    }
+
+    /// Redraw after a change confined to one area of the graph, such as a node being dragged.
+    /// Clears the overlays too, since they show the selection and group boxes around it.
+    fn redraw_area(&mut self, area: geometry::Rectangle) {
+        for view in self.views.iter_mut() {
+            view.tiles.invalidate(&view.camera, area.into());
+            view.under.clear();
+            view.over.clear();
+            view.minimap.clear();
+        }
+    }
+
+    /// Catch the spatial index up with the graph and redraw the area that changed.
+    fn sync(&mut self) {
+        if let Some(area) = self.document.sync() {
+            self.redraw_area(area);
+        }
+    }
+
+    /// Redraw everything except the graph itself, for changes to the selection or annotations.
+    fn redraw_overlays(&mut self) {
+        for view in self.views.iter_mut() {
+            view.under.clear();
+            view.over.clear();
+        }
+    }
+
+    /// Add an undo step if anything changed since the last snapshot, copying only the parts
+    /// which did. Later steps of a drag leave the snapshot behind until the drag is over.
+    fn commit(&mut self, group: Option<String>) {
+        if self.document.revision() == self.current.revision() {
+            return;
+        }
+
+        self.dirty = true;
+        self.generation += 1;
+
+        if self.history.continues(group.as_deref()) {
+            self.clear_analysis();
+            return;
+        }
+
+        let after = self.document.snapshot_since(&self.current);
+
+        // Whatever was found no longer holds once the graph itself changes
+        if after.graph_changed_since(&self.current) {
+            self.clear_analysis();
+        }
+
+        let before = std::mem::replace(&mut self.current, after);
+        self.history.record(before, group);
+    }
+
+    /// Bring the last snapshot up to date, after edits which weren't given an undo step of their own.
+    fn catch_up(&mut self) {
+        if self.document.revision() != self.current.revision() {
+            self.current = self.document.snapshot_since(&self.current);
+        }
+    }
+
+    /// Go back to a snapshot from the history.
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.document = Document::from_snapshot(&snapshot);
+        self.dirty = !snapshot.matches(&self.saved);
+        self.current = snapshot;
+        self.analysis = None;
+        self.generation += 1;
+
+        let graph = &self.document.graph;
+        self.selection.retain(|name| graph.get_node(name).is_some());
+        self.redraw();
+    }
+
+    fn mark_saved(&mut self, path: String) {
+        self.saved = self.document.to_data();
+        self.path = Some(path);
+        self.dirty = false;
+    }
+
+    fn clear_analysis(&mut self) {
+        if self.analysis.take().is_some() {
+            self.redraw();
+        }
+    }
+
+    /// Insert a copy of the data and select the newly created nodes.
+    fn paste(&mut self, data: &GraphData, offset: geometry::Vector) {
+        if data.is_empty() {
+            return;
+        }
+
+        self.selection = self.document.insert(data, offset).into_iter().collect();
+    }
+}
+
+impl NetworkUI {
+    /// One button per open document, marked when it has unsaved changes.
+    fn tab_bar(&self) -> Element<'_, Message> {
+        let mut bar = Row::new().spacing(5).align_items(Alignment::Center);
+
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let mut title = tab.title();
+            if tab.dirty {
+                title.push('*');
+            }
+            if index == self.active {
+                title = format!("[{}]", title);
+            }
+
+            bar = bar
+                .push(Button::new(Text::new(title)).on_press(Message::SelectTab(index)))
+                .push(Button::new("x").on_press(Message::CloseTab(index)));
+        }
+
+        bar.push(Button::new("+").on_press(Message::NewTab)).into()
+    }
+
+    /// List of layers with their visibility and lock toggles. Clicking a name makes it active.
+    fn layers_panel(&self) -> Element<'_, Message> {
+        let layers = self.tabs[self.active].document.layers();
+        let mut panel = Column::new().spacing(5).width(iced::Length::Units(170)).push(Text::new("Layers"));
+
+        for layer in layers.iter() {
+            let id = layer.id();
+            let name = if id == layers.active() { format!("> {}", layer.name) } else { layer.name.clone() };
+
+            let mut row = Row::new()
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .push(Checkbox::new(layer.visible, "", move |visible| Message::SetLayerVisible(id, visible)))
+                .push(Checkbox::new(layer.locked, "", move |locked| Message::SetLayerLocked(id, locked)))
+                .push(Button::new(Text::new(name)).on_press(Message::SetActiveLayer(id)));
+
+            if id != Layers::DEFAULT {
+                row = row.push(Button::new("x").on_press(Message::RemoveLayer(id)));
+            }
+
+            panel = panel.push(row);
+        }
+
+        panel
+            .push(Text::new("Visible / Locked").size(14))
+            .push(Button::new("New layer").on_press(Message::AddLayer))
+            .push(Button::new("Move selection here").on_press(Message::MoveSelectionToLayer))
+            .into()
+    }
+
+    /// Findings of the last analysis run on the open document.
+    fn analysis_panel(&self) -> Option<Element<'_, Message>> {
+        let analysis = self.tabs[self.active].analysis.as_ref()?;
+
+        let heading = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new(&analysis.title))
+            .push(Button::new("Clear").on_press(Message::ClearAnalysis));
+
+        let mut panel = analysis
+            .lines
+            .iter()
+            .fold(Column::new().spacing(5).push(heading), |panel, line| panel.push(Text::new(line).size(14)));
+
+        if !analysis.steps.is_empty() {
+            panel = panel.push(steps_view(analysis));
+        }
+
+        if !analysis.subgraph.is_empty() {
+            panel = panel.push(Button::new("Extract to new document").on_press(Message::ExtractSubgraph));
+        }
+
+        if let Some(table) = &analysis.table {
+            panel = panel.push(table_view(table, analysis.sorted)).push(Button::new("Export CSV").on_press(Message::ExportTable));
+        }
+
+        Some(panel.into())
+    }
+
+    /// Run an analysis between two nodes of a tab's document in the background. Tools which
+    /// need an amount as well ask for it first, and run once it is given.
+    fn run_pair_tool(&mut self, tab: usize, tool: PairTool, from: &str, to: &str, amount: Option<f32>) -> Command<Message> {
+        let amount = match (tool, amount) {
+            (PairTool::KShortest | PairTool::MinCostFlow, None) => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::PairAmount(tool, from.to_string(), to.to_string()),
+                    value: String::from(if tool == PairTool::KShortest { "5" } else { "1" }),
+                });
+
+                return Command::none();
+            }
+            (_, amount) => amount.unwrap_or_default(),
+        };
+
+        let document = &self.tabs[tab].document;
+        let graph = &document.graph;
+        let net = Network::new(graph);
+
+        let (from, to) = match (net.index(from), net.index(to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Command::none(),
+        };
+
+        match tool {
+            PairTool::ShortestPath => {
+                let positions: Vec<geometry::Point> = net.names.iter().map(|name| graph.position(name)).collect();
+                let method = self.path_method;
+
+                self.start_job(Some(tab), "Finding path", move |_| {
+                    Some(Outcome::Analysis(analysis::shortest_path(&net, &positions, from, to, method)))
+                })
+            }
+            PairTool::KShortest => {
+                let k = amount.round().max(1.0) as usize;
+
+                self.start_job(Some(tab), "Finding paths", move |_| {
+                    Some(Outcome::Analysis(analysis::k_shortest(&net, from, to, k)))
+                })
+            }
+            PairTool::EqualCost => self.start_job(Some(tab), "Finding paths", move |_| {
+                Some(Outcome::Analysis(analysis::equal_cost(&net, from, to)))
+            }),
+            PairTool::MaxFlow => {
+                let net = net.with_weights(analysis::edge_values(&net, document.attributes(), |edge| edge.capacity));
+
+                self.start_job(Some(tab), "Finding maximum flow", move |_| {
+                    Some(Outcome::Analysis(analysis::max_flow(&net, from, to)))
+                })
+            }
+            PairTool::MinCostFlow => {
+                let capacities = analysis::edge_values(&net, document.attributes(), |edge| edge.capacity);
+                let net = net.with_weights(analysis::edge_values(&net, document.attributes(), |edge| edge.cost));
+
+                self.start_job(Some(tab), "Routing demand", move |_| {
+                    Some(Outcome::Analysis(analysis::min_cost_flow(&net, &capacities, from, to, amount)))
+                })
+            }
+        }
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt) -> Command<Message> {
+        let tab = &mut self.tabs[self.active];
+
+        match &prompt.kind {
+            PromptKind::RenameNode(name) => {
+                let new_name = prompt.value.trim();
+
+                // Keeping the name is nothing to do, not a clash with itself
+                if new_name == name.as_str() {
+                    return Command::none();
+                }
+
+                if new_name.is_empty() || !tab.document.rename_node(name, new_name) {
+                    // Blank or clashing, leave the prompt up so it can be corrected
+                    self.prompt = Some(prompt);
+                    return Command::none();
+                }
+
+                if tab.selection.remove(name) {
+                    tab.selection.insert(new_name.to_string());
+                }
+                if let Some(style) = tab.node_styles.remove(name) {
+                    tab.node_styles.insert(new_name.to_string(), style);
+                }
+            }
+            PromptKind::EdgeWeight(from, to) => {
+                let weight = match prompt.value.trim() {
+                    "" => None,
+                    value => match value.parse::<f32>() {
+                        Ok(weight) if weight.is_finite() => Some(weight),
+                        _ => {
+                            self.prompt = Some(prompt);
+                            return Command::none();
+                        }
+                    },
+                };
+
+                if let (Some(from), Some(to)) = (tab.document.graph.get_node(from), tab.document.graph.get_node(to)) {
+                    tab.document.graph.set_weight(&from, &to, weight);
+                }
+            }
+            PromptKind::AnnotationText(id) => {
+                if let Some(Shape::Text { content, .. }) = tab.document.annotations_mut().get_mut(*id).map(|a| &mut a.shape) {
+                    *content = prompt.value.clone();
+                }
+            }
+            PromptKind::GroupName => {
+                if !tab.document.graph.create_group(prompt.value.clone(), &tab.selection) {
+                    // Name already in use
+                    self.prompt = Some(prompt);
+                    return Command::none();
+                }
+            }
+            PromptKind::LayerName => {
+                let id = tab.document.layers_mut().add(prompt.value.clone());
+                tab.document.layers_mut().set_active(id);
+            }
+            PromptKind::Save => {
+                if let Err(err) = std::fs::write(&prompt.value, tab.document.to_data().to_json()) {
+                    self.status = Some(format!("Couldn't save to {}: {}", prompt.value, err));
+                    self.prompt = Some(prompt);
+                    return Command::none();
+                }
+
+                tab.mark_saved(prompt.value);
+                return Command::none();
+            }
+            PromptKind::PairAmount(tool, from, to) => {
+                match prompt.value.trim().parse::<f32>() {
+                    Ok(amount) if amount > 0.0 => return self.run_pair_tool(self.active, *tool, from, to, Some(amount)),
+                    _ => {
+                        self.prompt = Some(prompt);
+                        return Command::none();
+                    }
+                }
+            }
+            PromptKind::EdgeAttributes(from, to, nth) => {
+                let mut values = prompt.value.split(',').map(|value| match value.trim() {
+                    "" => Ok(None),
+                    value => match value.parse::<f32>() {
+                        Ok(value) if value.is_finite() => Ok(Some(value)),
+                        _ => Err(()),
+                    },
+                });
+
+                match (values.next().unwrap_or(Ok(None)), values.next().unwrap_or(Ok(None)), values.next()) {
+                    (Ok(capacity), Ok(cost), None) => {
+                        tab.document.attributes_mut().set_edge(from, to, *nth, EdgeAttributes { capacity, cost });
+                    }
+                    _ => {
+                        self.prompt = Some(prompt);
+                        return Command::none();
+                    }
+                }
+            }
+            PromptKind::ExportCsv => {
+                let csv = tab.analysis.as_ref().and_then(|analysis| analysis.table.as_ref()).map(Table::to_csv);
+
+                if let Some(Err(err)) = csv.map(|csv| std::fs::write(&prompt.value, csv)) {
+                    self.status = Some(format!("Couldn't export to {}: {}", prompt.value, err));
+                    self.prompt = Some(prompt);
+                }
+
+                return Command::none();
+            }
+            PromptKind::ExportDot => {
+                if let Err(err) = std::fs::write(&prompt.value, tab.document.export_visible().to_dot()) {
+                    self.status = Some(format!("Couldn't export to {}: {}", prompt.value, err));
+                    self.prompt = Some(prompt);
+                    return Command::none();
+                }
+            }
+            PromptKind::Open => {
+                let path = prompt.value;
+
+                // Opened files get a tab of their own rather than replacing what is open
+                return self.start_job(None, "Opening", move |progress| {
+                    let text = std::fs::read_to_string(&path).ok();
+                    progress.report(0.5, "Parsing");
+
+                    Some(Outcome::Open(path, text.and_then(|text| DocumentData::from_json(&text))))
+                });
+            }
+        }
+
+        tab.redraw();
+
+        Command::none()
+    }
+
+    /// Run work on a background thread, watching its progress from here. Anything already
+    /// running is cancelled, since only one job is shown at a time.
+    fn start_job(
+        &mut self,
+        tab: Option<usize>,
+        stage: &str,
+        work: impl FnOnce(&Progress) -> Option<Outcome> + Send + 'static,
+    ) -> Command<Message> {
+        if let Some(running) = self.job.take() {
+            running.job.cancel();
+        }
+
+        let id = self.next_job;
+        self.next_job += 1;
+
+        let generation = tab.and_then(|tab| self.tabs.get(tab)).map_or(0, |tab| tab.generation);
+        let job = Job::spawn(stage, work);
+        let status = job.status();
+        let next = job.next(&status);
+
+        self.job = Some(Running { id, job, tab, generation, status });
+
+        Command::perform(next, move |status| Message::JobProgress(id, status))
+    }
+
+    /// Keep watching until the job finishes, then apply its result as a single undo step.
+    fn job_progress(&mut self, id: usize, status: Status) -> Command<Message> {
+        let running = match &mut self.job {
+            // Anything else is from a job which has since been cancelled and replaced
+            Some(running) if running.id == id => running,
+            _ => return Command::none(),
+        };
+
+        if !status.finished {
+            let next = running.job.next(&status);
+            running.status = status;
+
+            return Command::perform(next, move |status| Message::JobProgress(id, status));
+        }
+
+        let running = match self.job.take() {
+            Some(running) => running,
+            None => return Command::none(),
+        };
+
+        // Results for a tab which has been edited since would undo or misdescribe those edits
+        let stale = running.tab.and_then(|tab| self.tabs.get(tab)).is_some_and(|tab| tab.generation != running.generation);
+
+        match running.job.take() {
+            Some(Outcome::Arrange(_) | Outcome::Analysis(_)) if stale => {
+                self.status = Some(String::from("The document changed while the job was running, so its result was dropped"));
+            }
+            Some(Outcome::Arrange(positions)) => {
+                if let Some(tab) = running.tab.and_then(|tab| self.tabs.get_mut(tab)) {
+                    tab.catch_up();
+                    tab.document.graph.set_positions(&positions);
+                    tab.sync();
+                    tab.commit(None);
+                }
+                if let Some(tab) = running.tab {
+                    self.report_repairs(tab);
+                }
+            }
+            Some(Outcome::Open(path, Some(document))) => {
+                self.tabs.push(Tab::from_data(document, Some(path)));
+                self.active = self.tabs.len() - 1;
+                self.report_repairs(self.active);
+            }
+            Some(Outcome::Analysis(analysis)) => {
+                if let Some(tab) = running.tab.and_then(|tab| self.tabs.get_mut(tab)) {
+                    tab.analysis = Some(analysis);
+                    tab.redraw();
+                }
+            }
+            Some(Outcome::Open(path, None)) => {
+                self.status = Some(format!("Couldn't open {}", path));
+                self.prompt = Some(Prompt { kind: PromptKind::Open, value: path });
+            }
+            // Cancelled, or the work gave up
+            None => (),
+        }
+
+        Command::none()
+    }
+
+    /// Tell the user about anything the tab's document had to fix, on loading or since.
+    fn report_repairs(&mut self, tab: usize) {
+        if let Some(tab) = self.tabs.get_mut(tab) {
+            let report = tab.document.take_repairs();
+
+            if !report.is_ok() {
+                self.status = Some(format!("Fixed problems in {}:\n{}", tab.title(), report));
+            }
+        }
+    }
+
+    /// Keep a running job pointed at the right tab once one has been closed,
+    /// giving up on it if it was working on the closed tab.
+    fn forget_tab(&mut self, index: usize) {
+        let running = match &mut self.job {
+            Some(running) => running,
+            None => return,
+        };
+
+        match running.tab {
+            Some(tab) if tab == index => {
+                running.job.cancel();
+                self.job = None;
+            }
+            Some(tab) if tab > index => running.tab = Some(tab - 1),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Let a job finish and hand its result over, as its last progress message would.
+    fn finish_job(ui: &mut NetworkUI) {
+        let id = ui.job.as_ref().unwrap().id;
+
+        while !ui.job.as_ref().unwrap().job.status().finished {
+            std::thread::yield_now();
+        }
+
+        let status = ui.job.as_ref().unwrap().job.status();
+        let _ = ui.job_progress(id, status);
+    }
+
+    /// The spanning tree found in the background can be taken out into a tab of its own.
+    #[test]
+    pub fn test_extract_subgraph () {
+        let mut ui = NetworkUI::default();
+        let document = &mut ui.tabs[0].document;
+        for x in [0.0, 10.0, 20.0] {
+            document.add_node(geometry::Point::new(x, 0.0));
+        }
+        for (from, to) in [("0", "1"), ("1", "2"), ("0", "2")] {
+            document.add_edge(from, to, None);
+        }
+        ui.tabs[0].sync();
+
+        let _ = ui.update(Message::SpanningTree);
+        finish_job(&mut ui);
+        assert!(ui.tabs[0].analysis.is_some());
+
+        let _ = ui.update(Message::ExtractSubgraph);
+        assert_eq!(ui.tabs.len(), 2);
+        assert_eq!(ui.active, 1);
+        assert_eq!(ui.tabs[1].document.graph.to_data().edges.len(), 2);
+        assert_eq!(ui.tabs[1].document.graph.node_list().len(), 3);
+    }
+
+    /// A drag is one undo step however many moves it takes, edits which change nothing add
+    /// none, and undo and redo go back and forth between the states either side.
+    #[test]
+    pub fn test_undo_steps () {
+        let mut ui = NetworkUI::default();
+        let _ = ui.update(Message::AddNode(Point::new(0.0, 0.0)));
+
+        for _ in 0..3 {
+            let _ = ui.update(Message::MoveNode(String::from("0"), Vector::new(10.0, 0.0)));
+        }
+        let _ = ui.update(Message::RemoveNode(String::from("missing")));
+        let _ = ui.update(Message::AddNode(Point::new(0.0, 50.0)));
+
+        let position = |ui: &NetworkUI| ui.tabs[0].document.graph.get_node("0").map(|node| ui.tabs[0].document.graph.position(node.name()));
+
+        let _ = ui.update(Message::Undo);
+        assert_eq!(ui.tabs[0].document.graph.node_list().len(), 1);
+        assert_eq!(position(&ui), Some(geometry::Point::new(30.0, 0.0)));
+
+        let _ = ui.update(Message::Undo);
+        assert_eq!(position(&ui), Some(geometry::Point::new(0.0, 0.0)));
+
+        let _ = ui.update(Message::Undo);
+        assert_eq!(position(&ui), None);
+        assert!(!ui.tabs[0].history.can_undo());
+        assert!(!ui.tabs[0].dirty);
+
+        let _ = ui.update(Message::Redo);
+        let _ = ui.update(Message::Redo);
+        assert_eq!(position(&ui), Some(geometry::Point::new(30.0, 0.0)));
+    }
+
+    /// New positions worked out for an older version of the document are dropped rather than
+    /// undoing the edits made since.
+    #[test]
+    pub fn test_stale_job () {
+        let mut ui = NetworkUI::default();
+        let _ = ui.update(Message::AddNode(Point::new(0.0, 0.0)));
+
+        let positions: HashMap<String, geometry::Point> = [(String::from("0"), geometry::Point::new(50.0, 50.0))].into_iter().collect();
+        let arrange = |positions: HashMap<String, geometry::Point>| move |_: &Progress| Some(Outcome::Arrange(positions));
+
+        let _ = ui.start_job(Some(0), "Arranging", arrange(positions.clone()));
+        let _ = ui.update(Message::AddNode(Point::new(10.0, 0.0)));
+        finish_job(&mut ui);
+
+        assert_eq!(ui.tabs[0].document.graph.position("0"), geometry::Point::new(0.0, 0.0));
+        assert!(ui.status.is_some());
+
+        let _ = ui.start_job(Some(0), "Arranging", arrange(positions));
+        finish_job(&mut ui);
+
+        assert_eq!(ui.tabs[0].document.graph.position("0"), geometry::Point::new(50.0, 50.0));
     }
 }